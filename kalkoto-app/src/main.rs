@@ -1,35 +1,127 @@
 #![allow(non_snake_case)]
 // Import the Dioxus prelude to gain access to the `rsx!` macro and the `Scope` and `Element` types.
 use dioxus::prelude::*;
-use kalkoto_lib::entities::menage::{Caracteristique, Menage};
+use kalkoto_lib::adapters::input_adapters::csv_input_adapter::CsvInputAdapter;
+use kalkoto_lib::adapters::input_adapters::toml_input_adapter::TomlInputAdapter;
+use kalkoto_lib::adapters::output_adapters::memory_output_adapter::{
+    MemoryOutputAdapter, MemoryResults,
+};
+use kalkoto_lib::entities::simulator::SimulatorBuilder;
+use kalkoto_lib::KalkotoResult;
+
+// Inputs d'exemple proposés à l'ouverture de l'application
+const EXEMPLE_MENAGES: &str = include_str!("../exemples/menages.csv");
+const EXEMPLE_BASELINE: &str = include_str!("../exemples/baseline.toml");
+const EXEMPLE_VARIANTE: &str = include_str!("../exemples/variante.toml");
 
 fn main() {
     // Launch the web application using the App component as the root.
     dioxus::launch(App);
 }
 
-// Define a component that renders a div with constant text
+// Simulation complète en mémoire, sans système de fichiers ni interpréteur Python :
+// seules les composantes définies par une formula peuvent être calculées
+fn simule(menages: &str, baseline: &str, variante: &str) -> KalkotoResult<MemoryResults> {
+    let output_adapter = MemoryOutputAdapter::new();
+
+    let mut simulation = SimulatorBuilder::new()
+        .add_menage_input(CsvInputAdapter::new().populate_from_memory(menages.as_bytes())?)?
+        .add_valid_baseline_policy(
+            TomlInputAdapter::new().populate_from_memory(baseline.as_bytes())?,
+        )?;
+    simulation.simulate_baseline_policy()?;
+    simulation.export_baseline(&output_adapter)?;
+
+    let mut simulation = simulation.add_valid_variante_policy(
+        TomlInputAdapter::new().populate_from_memory(variante.as_bytes())?,
+    )?;
+    simulation.simulate_variante_policy()?;
+    simulation.export_variante_and_diff(output_adapter.clone())?;
+
+    Ok(output_adapter.resultats())
+}
+
 #[component]
 fn App() -> Element {
-    let mut test_menage = Menage::new(1);
-    test_menage.caracteristiques.insert(
-        "Name".to_owned(),
-        Caracteristique::Textuel("Jeannot".to_owned()),
-    );
-    test_menage
-        .caracteristiques
-        .insert("Age".to_owned(), Caracteristique::Entier(35));
+    let mut menages = use_signal(|| EXEMPLE_MENAGES.to_owned());
+    let mut baseline = use_signal(|| EXEMPLE_BASELINE.to_owned());
+    let mut variante = use_signal(|| EXEMPLE_VARIANTE.to_owned());
+    let mut resultats = use_signal(|| None::<Result<MemoryResults, String>>);
+
     rsx! {
-        div {
-            "Coucou de mon appli Simulaction Sociale !"
+        div { class: "p-4 flex flex-col gap-4",
+            h1 { class: "text-xl font-bold", "Simulaction Sociale" }
+            div { class: "grid grid-cols-3 gap-4",
+                ZoneInput { titre: "Ménages (CSV)", valeur: menages, on_change: move |v| menages.set(v) }
+                ZoneInput { titre: "Politique baseline (TOML)", valeur: baseline, on_change: move |v| baseline.set(v) }
+                ZoneInput { titre: "Politique variante (TOML)", valeur: variante, on_change: move |v| variante.set(v) }
+            }
+            button {
+                class: "border rounded px-4 py-2 w-64",
+                onclick: move |_| {
+                    let resultat = simule(&menages.read(), &baseline.read(), &variante.read())
+                        .map_err(|e| format!("{:?}", e));
+                    resultats.set(Some(resultat));
+                },
+                "Lancer la simulation"
+            }
+            match &*resultats.read() {
+                None => rsx! {},
+                Some(Err(erreur)) => rsx! {
+                    pre { class: "text-red-600 whitespace-pre-wrap", "{erreur}" }
+                },
+                Some(Ok(resultats)) => rsx! {
+                    TableResultats { titre: "Résultats baseline", csv: resultats.baseline.clone().unwrap_or_default() }
+                    TableResultats { titre: "Résultats variante", csv: resultats.variante.clone().unwrap_or_default() }
+                    TableResultats { titre: "Écarts variante - baseline", csv: resultats.diff.clone().unwrap_or_default() }
+                },
+            }
         }
-    TestComponentWithProps {menage: test_menage}
     }
 }
 
 #[component]
-fn TestComponentWithProps(menage: Menage) -> Element {
+fn ZoneInput(titre: String, valeur: Signal<String>, on_change: EventHandler<String>) -> Element {
     rsx! {
-        "Exposition des entités de la librairie. Exemple :\n {menage}"
+        label { class: "flex flex-col gap-1",
+            span { class: "font-bold", "{titre}" }
+            textarea {
+                class: "border rounded font-mono text-sm h-64",
+                value: "{valeur}",
+                oninput: move |evt| on_change.call(evt.value()),
+            }
+        }
+    }
+}
+
+// Affichage d'un export CSV (séparateur ;) sous forme de tableau
+#[component]
+fn TableResultats(titre: String, csv: String) -> Element {
+    let mut lignes = csv
+        .lines()
+        .map(|ligne| ligne.split(';').map(String::from).collect::<Vec<String>>());
+    let entetes = lignes.next().unwrap_or_default();
+    let lignes: Vec<Vec<String>> = lignes.collect();
+
+    rsx! {
+        h2 { class: "text-lg font-bold", "{titre}" }
+        table { class: "border-collapse",
+            thead {
+                tr {
+                    for entete in entetes {
+                        th { class: "border px-2", "{entete}" }
+                    }
+                }
+            }
+            tbody {
+                for ligne in lignes {
+                    tr {
+                        for valeur in ligne {
+                            td { class: "border px-2 text-right", "{valeur}" }
+                        }
+                    }
+                }
+            }
+        }
     }
 }
@@ -1,24 +1,51 @@
 use clap::Parser;
 use crossterm::style::Stylize;
+use csv_input_adapter::{Encodage, OptionsCsv};
 use kalkoto_lib::adapters::input_adapters::arrow_input_adapter::ArrowInputAdapter;
+use kalkoto_lib::adapters::input_adapters::generateur_input_adapter::GenerateurInputAdapter;
+use kalkoto_lib::adapters::input_adapters::jsonl_input_adapter::JsonLinesInputAdapter;
+use kalkoto_lib::adapters::input_adapters::parquet_input_adapter::ParquetInputAdapter;
+use kalkoto_lib::adapters::input_adapters::sqlite_input_adapter::SqliteInputAdapter;
+use kalkoto_lib::adapters::input_adapters::tableur_input_adapter::TableurInputAdapter;
 use kalkoto_lib::adapters::input_adapters::*;
 use kalkoto_lib::adapters::output_adapters::arrow_output_adapter::ArrowOutputAdapter;
 use kalkoto_lib::adapters::output_adapters::csv_output_adapter::CSVOutputAdapter;
-use kalkoto_lib::adapters::output_adapters::{OutputAdapter, OutputWriter};
+use kalkoto_lib::adapters::output_adapters::{OutputAdapter, OutputAdapterError, OutputWriter};
+#[cfg(feature = "python")]
+use kalkoto_lib::entities::evaluator::MOTEUR_PYTHON;
+use kalkoto_lib::entities::formula::Filtre;
+use kalkoto_lib::entities::menage_input::{TraitementManquant, ValeursManquantes};
+#[cfg(feature = "python")]
+use kalkoto_lib::entities::python_environnement::EnvironnementPython;
+#[cfg(feature = "python")]
+use kalkoto_lib::entities::python_evaluator::PythonEvaluator;
+use kalkoto_lib::entities::schema_menages::SchemaMenages;
 use kalkoto_lib::entities::simulator::{
     EmptyBaselineInput, EmptyMenageInput, EmptyVarianteInput, SimulatorBuilder,
 };
+use kalkoto_lib::entities::trace::MenageTrace;
 use kalkoto_lib::KalkotoResult;
-use std::path::Path;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
 use toml_input_adapter::TomlInputAdapter;
 
 #[derive(Parser)]
 #[command(author,version,about,long_about = None)]
 struct Args {
-    #[arg(short, long, value_name = "Type du fichier ménages (csv ou arrow)")]
+    #[arg(
+        short,
+        long,
+        value_name = "Type du fichier ménages (csv, jsonl, arrow, parquet, sqlite, tableur ou generateur)"
+    )]
     type_menage_input: String,
 
-    #[arg(short, long, value_name = "Chemin vers le fichier ménages")]
+    #[arg(
+        short,
+        long,
+        value_name = "Chemin vers le fichier ménages (- pour l'entrée standard en csv, jsonl ou arrow)"
+    )]
     menage_input: String,
 
     #[arg(
@@ -37,6 +64,277 @@ struct Args {
 
     #[arg(short, long, value_name = "Préfixe pour les fichiers de sortie")]
     prefix: Option<String>,
+
+    #[arg(
+        long,
+        value_delimiter = ',',
+        value_name = "Identifiants (--id-column) ou à défaut index des ménages dont le calcul est détaillé pas à pas"
+    )]
+    trace: Vec<String>,
+
+    #[arg(
+        long,
+        help = "Exporte aussi le détail des calculs tracés au format JSON"
+    )]
+    trace_json: bool,
+
+    #[arg(
+        long,
+        default_value_t = 0,
+        value_name = "Graine des tirages aléatoires des composantes"
+    )]
+    seed: u64,
+
+    #[arg(
+        long,
+        value_name = "Chemin vers le fichier TOML du schéma des ménages (types déclarés)"
+    )]
+    schema: Option<String>,
+
+    #[command(flatten)]
+    csv: ArgsCsv,
+
+    #[arg(
+        long,
+        value_name = "Feuille du classeur contenant les ménages (la première par défaut)"
+    )]
+    feuille: Option<String>,
+
+    #[arg(
+        long,
+        default_value_t = 1,
+        value_name = "Numéro de la ligne d'en-tête de la feuille des ménages"
+    )]
+    ligne_entete: u32,
+
+    #[arg(
+        long,
+        value_name = "Requête SELECT ou nom de la table des ménages (base SQLite)"
+    )]
+    requete: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "Colonne identifiant les ménages, reprise comme clé des résultats"
+    )]
+    id_column: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "Colonne des poids de sondage des ménages, utilisés pour les totaux"
+    )]
+    weight_column: Option<String>,
+
+    #[arg(
+        long,
+        requires = "id_column",
+        value_name = "Fichier CSV des individus, rattachés aux ménages par --individus-menage"
+    )]
+    individus: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "Colonne du fichier individus donnant l'identifiant de leur ménage (--id-column par défaut)"
+    )]
+    individus_menage: Option<String>,
+
+    #[arg(
+        long,
+        value_delimiter = ',',
+        value_name = "Colonnes du fichier ménages reprises dans les résultats, en plus de celles dont dépendent les politiques"
+    )]
+    keep_columns: Vec<String>,
+
+    #[arg(
+        long,
+        help = "Charge toutes les colonnes du fichier ménages, et pas seulement celles dont dépendent les politiques"
+    )]
+    all_columns: bool,
+
+    #[arg(
+        long,
+        value_parser = parse_filtre,
+        value_name = "Expression que doivent vérifier les ménages simulés, ex. \"Age >= 60 and GIR <= 4\""
+    )]
+    filter: Option<Filtre>,
+
+    #[arg(
+        long,
+        value_parser = parse_manquant,
+        value_name = "Traitement des valeurs manquantes : [COLONNE=](erreur|none|VALEUR)"
+    )]
+    manquant: Vec<(Option<String>, TraitementManquant)>,
+
+    #[cfg(feature = "python")]
+    #[arg(
+        long,
+        value_delimiter = ',',
+        value_name = "Répertoires ajoutés à sys.path pour les composantes Python"
+    )]
+    python_path: Vec<PathBuf>,
+
+    #[cfg(feature = "python")]
+    #[arg(
+        long,
+        value_name = "Virtualenv des composantes Python (par défaut celui de VIRTUAL_ENV)"
+    )]
+    virtualenv: Option<PathBuf>,
+}
+
+// Dialecte du fichier ménages CSV
+#[derive(clap::Args)]
+struct ArgsCsv {
+    #[arg(
+        long,
+        value_parser = parse_caractere,
+        value_name = "Délimiteur des champs CSV (détecté par défaut ; tab pour la tabulation)"
+    )]
+    delimiteur: Option<u8>,
+
+    #[arg(
+        long,
+        value_parser = parse_caractere,
+        default_value = ".",
+        value_name = "Séparateur décimal des nombres CSV (. ou ,)"
+    )]
+    decimal: u8,
+
+    #[arg(
+        long,
+        default_value = "utf-8",
+        value_name = "Encodage du fichier CSV (utf-8 ou latin1)"
+    )]
+    encodage: Encodage,
+
+    #[arg(
+        long,
+        help = "Les guillemets du fichier CSV sont lus comme des caractères"
+    )]
+    sans_guillemets: bool,
+
+    #[arg(
+        long,
+        help = "Le fichier CSV n'a pas de ligne d'en-tête (colonnes nommées par --colonnes)"
+    )]
+    sans_entete: bool,
+
+    #[arg(
+        long,
+        value_delimiter = ',',
+        value_name = "Noms des colonnes d'un fichier CSV sans en-tête"
+    )]
+    colonnes: Option<Vec<String>>,
+
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_value = "true",
+        value_name = "Écritures du booléen vrai dans le fichier CSV, ex. oui,O"
+    )]
+    vrai: Vec<String>,
+
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_value = "false",
+        value_name = "Écritures du booléen faux dans le fichier CSV, ex. non,N"
+    )]
+    faux: Vec<String>,
+
+    #[arg(
+        long = "format-date",
+        default_value = "%Y-%m-%d",
+        value_name = "Format des dates du fichier CSV, ex. %d/%m/%Y (option répétable)"
+    )]
+    formats_dates: Vec<String>,
+}
+
+impl From<&ArgsCsv> for OptionsCsv {
+    fn from(args: &ArgsCsv) -> Self {
+        OptionsCsv {
+            delimiteur: args.delimiteur,
+            separateur_decimal: args.decimal,
+            encodage: args.encodage,
+            guillemet: (!args.sans_guillemets).then_some(b'"'),
+            entete: !args.sans_entete,
+            noms_colonnes: args.colonnes.clone(),
+            vrai: args.vrai.clone(),
+            faux: args.faux.clone(),
+            formats_dates: args.formats_dates.clone(),
+        }
+    }
+}
+
+fn parse_caractere(option: &str) -> Result<u8, String> {
+    match option {
+        "tab" | "\\t" => Ok(b'\t'),
+        _ => match option.as_bytes() {
+            [octet] => Ok(*octet),
+            _ => Err(format!("{option} n'est pas un caractère ASCII unique")),
+        },
+    }
+}
+
+fn parse_filtre(option: &str) -> Result<Filtre, String> {
+    Filtre::try_from(option.to_string())
+}
+
+// "Revenu=0" ne concerne que la colonne Revenu, "none" s'applique à toutes les autres colonnes
+fn parse_manquant(option: &str) -> Result<(Option<String>, TraitementManquant), String> {
+    match option.split_once('=') {
+        Some((colonne, traitement)) => Ok((Some(colonne.to_string()), traitement.parse()?)),
+        None => Ok((None, option.parse()?)),
+    }
+}
+
+fn valeurs_manquantes(options: &[(Option<String>, TraitementManquant)]) -> ValeursManquantes {
+    options.iter().fold(
+        ValeursManquantes::new(),
+        |valeurs, (colonne, traitement)| match colonne {
+            Some(colonne) => valeurs.colonne(colonne, traitement.clone()),
+            None => ValeursManquantes {
+                par_defaut: traitement.clone(),
+                ..valeurs
+            },
+        },
+    )
+}
+
+fn export_traces(
+    traces: &Option<Vec<MenageTrace>>,
+    nom_simulation: &str,
+    prefix: &Option<String>,
+    trace_json: bool,
+) -> KalkotoResult<()> {
+    let Some(traces) = traces else {
+        return Ok(());
+    };
+
+    for trace in traces {
+        println!("{}", trace);
+    }
+
+    if trace_json {
+        let output_path = match prefix {
+            Some(prefix) => format!("{}-{}-traces.json", prefix, nom_simulation),
+            _ => format!("{}-traces.json", nom_simulation),
+        };
+        fs::write(output_path, MenageTrace::to_json(traces)?).map_err(OutputAdapterError::IO)?;
+    }
+
+    Ok(())
+}
+
+// Totaux des composantes, pondérés par les poids de sondage des ménages s'ils en ont
+fn affiche_totaux(nom_simulation: &str, totaux: &BTreeMap<String, f64>, effectif: f64) {
+    println!(
+        "Totaux {} sur {} ménages représentés :",
+        nom_simulation, effectif
+    );
+    for (composante, total) in totaux {
+        println!("- {} : {}", composante, total);
+    }
+    println!();
 }
 
 struct Adapters<I, O>
@@ -48,14 +346,44 @@ where
     output_adapter: O,
 }
 
-fn dispatch_adapters<P: AsRef<Path>>(
-    type_fichier_menages: &str,
-    menage_input_path: P,
-    prefix: &Option<String>,
+fn dispatch_adapters(
+    args: &Args,
+    schema: Option<SchemaMenages>,
+    colonnes: Option<HashSet<String>>,
 ) -> KalkotoResult<Adapters<MenageAdapter, OutputAdapter>> {
-    match type_fichier_menages {
+    let menage_input_path = Path::new(&args.menage_input);
+    let prefix = &args.prefix;
+
+    // "-" désigne l'entrée standard, lue d'un bloc pour le CSV et en flux sinon
+    let entree_standard = args.menage_input == "-";
+    if entree_standard && !["csv", "jsonl", "arrow"].contains(&args.type_menage_input.as_str()) {
+        return Err(MenageListAdapterError::FileFormat(format!(
+            "Les ménages {} ne peuvent pas être lus sur l'entrée standard (csv, jsonl ou arrow seulement)",
+            args.type_menage_input
+        ))
+        .into());
+    }
+
+    match args.type_menage_input.as_str() {
+        // Sur l'entrée standard, les ménages sont un flux Arrow IPC
         "arrow" => {
-            let input_adapter = ArrowInputAdapter::new().populate_from_path(menage_input_path)?;
+            let input_adapter = ArrowInputAdapter::new();
+            let input_adapter = match args.id_column.as_deref() {
+                Some(id_column) => input_adapter.with_colonne_identifiant(id_column),
+                None => input_adapter,
+            };
+            let input_adapter = match colonnes {
+                Some(colonnes) => input_adapter.with_colonnes(colonnes),
+                None => input_adapter,
+            };
+            let input_adapter = match schema {
+                Some(schema) => input_adapter.with_schema(schema),
+                None => input_adapter,
+            };
+            let input_adapter = match entree_standard {
+                true => input_adapter.populate_from_stream(std::io::stdin().lock())?,
+                false => input_adapter.populate_from_path(menage_input_path)?,
+            };
             let mut output_adapter = ArrowOutputAdapter::new();
             if let Some(prefix) = prefix.as_deref() {
                 output_adapter = output_adapter.add_output_prefix(prefix.to_string())
@@ -65,10 +393,168 @@ fn dispatch_adapters<P: AsRef<Path>>(
                 output_adapter: OutputAdapter::Arrow(output_adapter),
             })
         }
+        // Les résultats restent typés : ils sont exportés au format Arrow
+        "parquet" => {
+            let input_adapter = ParquetInputAdapter::new();
+            let input_adapter = match args.id_column.as_deref() {
+                Some(id_column) => input_adapter.with_colonne_identifiant(id_column),
+                None => input_adapter,
+            };
+            let input_adapter = match colonnes {
+                Some(colonnes) => input_adapter.with_colonnes(colonnes),
+                None => input_adapter,
+            };
+            let input_adapter = match schema {
+                Some(schema) => input_adapter.with_schema(schema),
+                None => input_adapter,
+            }
+            .populate_from_path(menage_input_path)?;
+            let mut output_adapter = ArrowOutputAdapter::new();
+            if let Some(prefix) = prefix.as_deref() {
+                output_adapter = output_adapter.add_output_prefix(prefix.to_string())
+            };
+            Ok(Adapters {
+                input_adapter: MenageAdapter::Parquet(input_adapter),
+                output_adapter: OutputAdapter::Arrow(output_adapter),
+            })
+        }
+        // Classeur Excel ou OpenDocument ; les résultats sont exportés en CSV
+        "tableur" => {
+            let mut input_adapter = TableurInputAdapter::new().with_ligne_entete(args.ligne_entete);
+            if let Some(feuille) = args.feuille.as_deref() {
+                input_adapter = input_adapter.with_feuille(feuille)
+            }
+            let input_adapter = match args.id_column.as_deref() {
+                Some(id_column) => input_adapter.with_colonne_identifiant(id_column),
+                None => input_adapter,
+            };
+            let input_adapter = match colonnes {
+                Some(colonnes) => input_adapter.with_colonnes(colonnes),
+                None => input_adapter,
+            };
+            let input_adapter = match schema {
+                Some(schema) => input_adapter.with_schema(schema),
+                None => input_adapter,
+            }
+            .populate_from_path(menage_input_path)?;
+            let mut output_adapter = CSVOutputAdapter::new();
+            if let Some(prefix) = prefix.as_deref() {
+                output_adapter = output_adapter.add_output_prefix(prefix.to_string())
+            }
+            Ok(Adapters {
+                input_adapter: MenageAdapter::Tableur(input_adapter),
+                output_adapter: OutputAdapter::CSV(output_adapter),
+            })
+        }
+        // La requête est un SELECT ou le nom d'une table ; les résultats sont exportés en CSV
+        "sqlite" => {
+            let requete = args.requete.as_deref().ok_or_else(|| {
+                MenageListAdapterError::FileFormat(
+                    "Une base SQLite doit être lue avec l'option --requete".into(),
+                )
+            })?;
+            let input_adapter = SqliteInputAdapter::new();
+            let input_adapter = match args.id_column.as_deref() {
+                Some(id_column) => input_adapter.with_colonne_identifiant(id_column),
+                None => input_adapter,
+            };
+            let input_adapter = match colonnes {
+                Some(colonnes) => input_adapter.with_colonnes(colonnes),
+                None => input_adapter,
+            };
+            let input_adapter = match schema {
+                Some(schema) => input_adapter.with_schema(schema),
+                None => input_adapter,
+            }
+            .populate_from_path(menage_input_path, requete)?;
+            let mut output_adapter = CSVOutputAdapter::new();
+            if let Some(prefix) = prefix.as_deref() {
+                output_adapter = output_adapter.add_output_prefix(prefix.to_string())
+            }
+            Ok(Adapters {
+                input_adapter: MenageAdapter::Sqlite(input_adapter),
+                output_adapter: OutputAdapter::CSV(output_adapter),
+            })
+        }
+        // Spécification TOML d'une grille de cas-types ; les résultats sont exportés en CSV
+        "generateur" => {
+            let input_adapter = GenerateurInputAdapter::new();
+            let input_adapter = match args.id_column.as_deref() {
+                Some(id_column) => input_adapter.with_colonne_identifiant(id_column),
+                None => input_adapter,
+            };
+            let input_adapter = match colonnes {
+                Some(colonnes) => input_adapter.with_colonnes(colonnes),
+                None => input_adapter,
+            };
+            let input_adapter = match schema {
+                Some(schema) => input_adapter.with_schema(schema),
+                None => input_adapter,
+            }
+            .populate_from_path(menage_input_path)?;
+            let mut output_adapter = CSVOutputAdapter::new();
+            if let Some(prefix) = prefix.as_deref() {
+                output_adapter = output_adapter.add_output_prefix(prefix.to_string())
+            }
+            Ok(Adapters {
+                input_adapter: MenageAdapter::Generateur(input_adapter),
+                output_adapter: OutputAdapter::CSV(output_adapter),
+            })
+        }
+        // Un objet JSON par ligne ; les résultats sont exportés en CSV
+        "jsonl" => {
+            let input_adapter = JsonLinesInputAdapter::new();
+            let input_adapter = match args.id_column.as_deref() {
+                Some(id_column) => input_adapter.with_colonne_identifiant(id_column),
+                None => input_adapter,
+            };
+            let input_adapter = match colonnes {
+                Some(colonnes) => input_adapter.with_colonnes(colonnes),
+                None => input_adapter,
+            };
+            let input_adapter = match schema {
+                Some(schema) => input_adapter.with_schema(schema),
+                None => input_adapter,
+            };
+            let input_adapter = match entree_standard {
+                true => input_adapter.populate_from_reader(std::io::stdin().lock())?,
+                false => input_adapter.populate_from_path(menage_input_path)?,
+            };
+            let mut output_adapter = CSVOutputAdapter::new();
+            if let Some(prefix) = prefix.as_deref() {
+                output_adapter = output_adapter.add_output_prefix(prefix.to_string())
+            }
+            Ok(Adapters {
+                input_adapter: MenageAdapter::JsonLines(input_adapter),
+                output_adapter: OutputAdapter::CSV(output_adapter),
+            })
+        }
         "csv" => {
             let mut csv_empty_buf = String::new();
-            let input_adapter = csv_input_adapter::CsvInputAdapter::new()
-                .populate_from_path(menage_input_path, &mut csv_empty_buf)?;
+            let input_adapter =
+                csv_input_adapter::CsvInputAdapter::new().with_options(OptionsCsv::from(&args.csv));
+            let input_adapter = match args.id_column.as_deref() {
+                Some(id_column) => input_adapter.with_colonne_identifiant(id_column),
+                None => input_adapter,
+            };
+            let input_adapter = match colonnes {
+                Some(colonnes) => input_adapter.with_colonnes(colonnes),
+                None => input_adapter,
+            };
+            let input_adapter = match schema {
+                Some(schema) => input_adapter.with_schema(schema),
+                None => input_adapter,
+            };
+            let input_adapter = match entree_standard {
+                true => {
+                    let mut input_buf = vec![];
+                    std::io::stdin()
+                        .read_to_end(&mut input_buf)
+                        .map_err(MenageListAdapterError::IO)?;
+                    input_adapter.populate_from_memory(&input_buf)?
+                }
+                false => input_adapter.populate_from_path(menage_input_path, &mut csv_empty_buf)?,
+            };
             let mut output_adapter = CSVOutputAdapter::new();
             if let Some(prefix) = prefix.as_deref() {
                 output_adapter = output_adapter.add_output_prefix(prefix.to_string())
@@ -79,7 +565,7 @@ fn dispatch_adapters<P: AsRef<Path>>(
             })
         }
         _ => Err(MenageListAdapterError::FileFormat(
-            "Le fichier indiqué n'est pas un Arrow dataframe".into(),
+            "Le type de fichier ménages doit être csv, jsonl, arrow, parquet, sqlite, tableur ou generateur".into(),
         )
         .into()),
     }
@@ -88,16 +574,79 @@ fn dispatch_adapters<P: AsRef<Path>>(
 fn main() -> KalkotoResult<()> {
     let args = Args::parse();
 
-    let menage_input_path = Path::new(&args.menage_input);
+    // Les politiques sont lues avant les ménages, pour ne charger que les colonnes dont elles
+    // dépendent
+    let mut baseline_empty_buf = String::new();
+    let toml_input_adapter_baseline = TomlInputAdapter::new()
+        .populate_from_path(&args.baseline_policy_input, &mut baseline_empty_buf)?;
+
+    let mut variante_empty_buf = String::new();
+    let toml_input_adapter_variante = args
+        .variante_policy_input
+        .as_deref()
+        .map(|variante_input| {
+            TomlInputAdapter::new().populate_from_path(variante_input, &mut variante_empty_buf)
+        })
+        .transpose()?;
+
+    let colonnes = (!args.all_columns).then(|| {
+        toml_input_adapter_baseline
+            .caracteristiques_menages()
+            .into_iter()
+            .chain(
+                toml_input_adapter_variante
+                    .iter()
+                    .flat_map(TomlInputAdapter::caracteristiques_menages),
+            )
+            .chain(args.keep_columns.iter().cloned())
+            .chain(args.filter.iter().flat_map(Filtre::caracteristiques))
+            .chain(args.weight_column.iter().cloned())
+            .collect()
+    });
 
     let Adapters {
         input_adapter,
         output_adapter,
-    } = dispatch_adapters(&args.type_menage_input, menage_input_path, &args.prefix)?;
+    } = dispatch_adapters(
+        &args,
+        args.schema
+            .as_deref()
+            .map(SchemaMenages::populate_from_path)
+            .transpose()?,
+        colonnes,
+    )?;
     // let menage_input_adapter = dispatch_input_adapter(menage_input_path)?;
 
     let sim_builder =
-        SimulatorBuilder::<EmptyMenageInput, EmptyBaselineInput, EmptyVarianteInput>::new();
+        SimulatorBuilder::<EmptyMenageInput, EmptyBaselineInput, EmptyVarianteInput>::new()
+            .add_menages_traces(args.trace.iter().cloned().collect())
+            .add_seed(args.seed)
+            .add_valeurs_manquantes(valeurs_manquantes(&args.manquant));
+
+    let sim_builder = match args.filter.clone() {
+        Some(filtre) => sim_builder.add_filtre(filtre),
+        None => sim_builder,
+    };
+
+    let sim_builder = match args.weight_column.as_deref() {
+        Some(weight_column) => sim_builder.add_colonne_poids(weight_column),
+        None => sim_builder,
+    };
+
+    #[cfg(feature = "python")]
+    let sim_builder = sim_builder.add_evaluateur(
+        MOTEUR_PYTHON,
+        PythonEvaluator::new(EnvironnementPython {
+            paths: args.python_path.clone(),
+            virtualenv: args
+                .virtualenv
+                .clone()
+                .or_else(|| std::env::var_os("VIRTUAL_ENV").map(PathBuf::from)),
+            modules: vec![],
+        }),
+    );
+
+    println!("Graine des tirages aléatoires : {}", args.seed);
 
     println!(
         "{}",
@@ -109,6 +658,35 @@ fn main() -> KalkotoResult<()> {
 
     let sim_builder = sim_builder.add_menage_input(input_adapter)?;
 
+    // Les individus sont lus avec le même dialecte CSV que les ménages
+    let sim_builder = match (&args.individus, &args.id_column) {
+        (Some(individus_path), Some(id_column)) => {
+            let colonne_menage = args.individus_menage.as_deref().unwrap_or(id_column);
+            let mut individus_empty_buf = String::new();
+            let individus_adapter = csv_input_adapter::CsvInputAdapter::new()
+                .with_options(OptionsCsv::from(&args.csv))
+                .with_colonne_identifiant(colonne_menage);
+            let individus_adapter = match args.all_columns {
+                true => individus_adapter,
+                false => individus_adapter.with_colonnes(
+                    toml_input_adapter_baseline
+                        .caracteristiques_individus()
+                        .into_iter()
+                        .chain(
+                            toml_input_adapter_variante
+                                .iter()
+                                .flat_map(TomlInputAdapter::caracteristiques_individus),
+                        )
+                        .collect(),
+                ),
+            };
+            sim_builder.add_individus_input(
+                individus_adapter.populate_from_path(individus_path, &mut individus_empty_buf)?,
+            )?
+        }
+        _ => sim_builder,
+    };
+
     println!("{}", &sim_builder.menage_input.0);
 
     println!(
@@ -119,17 +697,29 @@ fn main() -> KalkotoResult<()> {
             .underlined()
     );
 
-    let mut baseline_empty_buf = String::new();
-
-    let toml_input_adapter_baseline = TomlInputAdapter::new()
-        .populate_from_path(&args.baseline_policy_input, &mut baseline_empty_buf)?;
-
     let mut sim_builder = sim_builder.add_valid_baseline_policy(toml_input_adapter_baseline)?;
 
     println!("{}", &sim_builder.policy_baseline.0);
 
     sim_builder.simulate_baseline_policy()?;
 
+    let totaux_baseline = sim_builder
+        .menage_input
+        .0
+        .totaux_ponderes(sim_builder.results_baseline.as_deref().unwrap_or_default());
+    affiche_totaux(
+        "baseline",
+        &totaux_baseline,
+        sim_builder.menage_input.0.effectif_pondere(),
+    );
+
+    export_traces(
+        &sim_builder.traces_baseline,
+        "baseline",
+        &args.prefix,
+        args.trace_json,
+    )?;
+
     println!(
         "{}",
         "Export des résultats de la simulation baseline\n"
@@ -139,7 +729,7 @@ fn main() -> KalkotoResult<()> {
 
     sim_builder.export_baseline(&output_adapter)?;
 
-    if let Some(variante_input) = args.variante_policy_input {
+    if let Some(toml_input_adapter_variante) = toml_input_adapter_variante {
         println!(
             "{}",
             "3) Import des informations du fichier de variante de politique publique + simulation"
@@ -148,17 +738,42 @@ fn main() -> KalkotoResult<()> {
                 .underlined()
         );
 
-        let mut variante_empty_buf = String::new();
-
-        let toml_input_adapter_variante =
-            TomlInputAdapter::new().populate_from_path(&variante_input, &mut variante_empty_buf)?;
-
         let mut sim_builder = sim_builder.add_valid_variante_policy(toml_input_adapter_variante)?;
 
         println!("{}", &sim_builder.policy_variante.0);
 
         sim_builder.simulate_variante_policy()?;
 
+        let totaux_variante = sim_builder
+            .menage_input
+            .0
+            .totaux_ponderes(sim_builder.results_variante.as_deref().unwrap_or_default());
+        affiche_totaux(
+            "variante",
+            &totaux_variante,
+            sim_builder.menage_input.0.effectif_pondere(),
+        );
+        let totaux_diff: BTreeMap<String, f64> = totaux_variante
+            .iter()
+            .filter_map(|(composante, total)| {
+                totaux_baseline
+                    .get(composante)
+                    .map(|total_baseline| (composante.clone(), total - total_baseline))
+            })
+            .collect();
+        affiche_totaux(
+            "diff",
+            &totaux_diff,
+            sim_builder.menage_input.0.effectif_pondere(),
+        );
+
+        export_traces(
+            &sim_builder.traces_variante,
+            "variante",
+            &args.prefix,
+            args.trace_json,
+        )?;
+
         println!(
             "{}",
             "Export des résultats de la simulation variante\n"
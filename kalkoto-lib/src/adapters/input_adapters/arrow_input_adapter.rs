@@ -1,12 +1,22 @@
-use crate::adapters::input_adapters::{MenageListAdapterError, MenageListCreator};
+use crate::adapters::input_adapters::{colonne_lue, MenageListAdapterError, MenageListCreator};
 use crate::entities::menage::{self, Caracteristique, Menage};
 use crate::entities::menage_input::{MenageInput, MenageInputBuilder};
+use crate::entities::schema_menages::SchemaMenages;
 use crate::{KalkotoError, KalkotoResult};
-use arrow::array::{Array, DataTypeLayout, Float64Array, Int32Array, StringArray};
-use arrow::ipc::reader::FileReader;
+use arrow::array::{
+    Array, ArrayRef, ArrowPrimitiveType, AsArray, PrimitiveArray, RecordBatch, RecordBatchReader,
+};
+use arrow::datatypes::{
+    DataType, Date32Type, Date64Type, Float16Type, Float32Type, Float64Type, Int16Type, Int32Type,
+    Int64Type, Int8Type, Schema, SchemaRef, UInt16Type, UInt32Type, UInt64Type, UInt8Type,
+};
+use arrow::error::ArrowError;
+use arrow::ipc::reader::{FileReader, StreamReader};
+#[cfg(feature = "mmap")]
 use memmap2::Mmap;
 use std::collections::HashSet;
 use std::ffi::{FromVecWithNulError, OsStr};
+use std::io::Read;
 use std::vec;
 use std::{fs::File, path::Path, sync::Arc};
 
@@ -15,6 +25,9 @@ pub struct ArrowInputAdapter {
     dataframe: Option<Vec<(String, Vec<Caracteristique>)>>,
     ncol: usize,
     nrow: usize,
+    schema: Option<SchemaMenages>,
+    colonne_identifiant: Option<String>,
+    colonnes: Option<HashSet<String>>,
 }
 
 impl ArrowInputAdapter {
@@ -22,12 +35,40 @@ impl ArrowInputAdapter {
         Self::default()
     }
 
+    // Les colonnes déclarées dans le schéma sont converties dans leur type
+    pub fn with_schema(self, schema: SchemaMenages) -> Self {
+        Self {
+            schema: Some(schema),
+            ..self
+        }
+    }
+
+    // Colonne dont les valeurs, uniques, identifient les ménages dans les résultats.
+    // Elle ne fait pas partie des caractéristiques
+    pub fn with_colonne_identifiant(self, colonne_identifiant: &str) -> Self {
+        Self {
+            colonne_identifiant: Some(colonne_identifiant.to_string()),
+            ..self
+        }
+    }
+
+    // Seules ces colonnes (et la colonne identifiant) sont lues : celles dont dépendent les
+    // politiques et celles reprises dans les résultats
+    pub fn with_colonnes(self, colonnes: HashSet<String>) -> Self {
+        Self {
+            colonnes: Some(colonnes),
+            ..self
+        }
+    }
+
+    // Fichier Arrow IPC au format fichier (.arrow) ou flux (.arrows) : le format
+    // est reconnu à son en-tête, et tous les batches sont lus
     pub fn populate_from_path<P>(self, path: P) -> KalkotoResult<Self>
     where
         P: AsRef<Path>,
     {
         match path.as_ref().extension().and_then(OsStr::to_str) {
-            Some("arrow") => (),
+            Some("arrow" | "arrows") => (),
             _ => {
                 return Err(From::from(MenageListAdapterError::FileFormat(
                     "Le fichier indiqué n'est pas un Arrow dataframe".into(),
@@ -39,200 +80,539 @@ impl ArrowInputAdapter {
             Ok(file) => file,
             Err(e) => return Err(From::from(MenageListAdapterError::IO(e))),
         };
-        let mmap = unsafe { Mmap::map(&file).map_err(MenageListAdapterError::IO)? };
 
-        let mut reader = FileReader::try_new(std::io::Cursor::new(mmap), None)
-            .map_err(MenageListAdapterError::Arrow)?;
+        #[cfg(feature = "mmap")]
+        let contenu = unsafe { Mmap::map(&file).map_err(MenageListAdapterError::IO)? };
 
-        let extracted_dataframe: KalkotoResult<Self> = match reader.next() {
-            Some(batch) => {
-                let batch = batch.map_err(MenageListAdapterError::Arrow)?;
-                let (schema, columns, nrow) = batch.into_parts();
+        #[cfg(not(feature = "mmap"))]
+        let contenu = {
+            let mut contenu = vec![];
+            file.read_to_end(&mut contenu)
+                .map_err(MenageListAdapterError::IO)?;
+            contenu
+        };
 
-                let ncol = columns.len();
+        self.populate_from_memory(&contenu)
+    }
 
-                let column_names: Vec<String> = schema
-                    .fields
-                    .iter()
-                    .map(|field| field.name().to_owned())
-                    .collect();
+    // Lecture d'un fichier Arrow déjà chargé en mémoire (application web notamment)
+    pub fn populate_from_memory(self, buf: &[u8]) -> KalkotoResult<Self> {
+        match buf.starts_with(ARROW_FILE_MAGIC) {
+            true => {
+                let reader = FileReader::try_new(std::io::Cursor::new(buf), None)
+                    .map_err(MenageListAdapterError::Arrow)?;
+                self.populate_from_batches(reader.schema(), reader)
+            }
+            false => self.populate_from_stream(buf),
+        }
+    }
 
-                let column_values: KalkotoResult<Vec<Vec<Caracteristique>>> = columns
-                    .into_iter()
-                    .map(|column| extract_values_from_arrow(column.clone(), nrow))
-                    .collect();
+    // Lecture d'un flux Arrow IPC (sortie d'un autre programme, entrée standard...)
+    pub fn populate_from_stream<R: Read>(self, input: R) -> KalkotoResult<Self> {
+        let reader = StreamReader::try_new(input, None).map_err(MenageListAdapterError::Arrow)?;
+        self.populate_from_batches(reader.schema(), reader)
+    }
 
-                let column_values = column_values?;
+    fn populate_from_batches<I>(self, schema: SchemaRef, batches: I) -> KalkotoResult<Self>
+    where
+        I: IntoIterator<Item = Result<RecordBatch, ArrowError>>,
+    {
+        let (dataframe, nrow) = extract_columns_from_batches(
+            &schema,
+            batches,
+            |nom| colonne_lue(&self.colonnes, &self.colonne_identifiant, nom),
+            self.colonne_identifiant.as_deref(),
+        )?;
 
-                let column_extract =
-                    column_names
-                        .into_iter()
-                        .zip(column_values)
-                        .collect::<Vec<(String, Vec<Caracteristique>)>>();
+        Ok(Self {
+            ncol: dataframe.len(),
+            dataframe: Some(dataframe),
+            nrow,
+            schema: self.schema,
+            colonne_identifiant: self.colonne_identifiant,
+            colonnes: self.colonnes,
+        })
+    }
+}
 
-                Ok(Self {
-                    dataframe: Some(column_extract),
-                    ncol,
-                    nrow,
-                })
-            }
-            _ => Err(KalkotoError::ListMenageError(
-                MenageListAdapterError::Arrow(arrow::error::ArrowError::ParseError(
-                    "Erreur à l'extraction des colonnes du Arrow Dataframe".into(),
+// Colonnes extraites d'un dataframe : nom et valeurs pour chaque ménage
+pub(crate) type Colonnes = Vec<(String, Vec<Caracteristique>)>;
+
+// En-tête des fichiers Arrow IPC, absent des flux
+const ARROW_FILE_MAGIC: &[u8] = b"ARROW1";
+
+// Concaténation des colonnes de tous les batches, qui doivent partager le même schéma.
+// Un fichier sans aucun batch est une erreur, pour ne jamais simuler une population vide.
+// Seules les colonnes pour lesquelles lue est vraie sont converties. La colonne identifiant est
+// convertie en texte avant lecture, pour ne pas réduire ses entiers à 32 bits
+pub(crate) fn extract_columns_from_batches<I, F>(
+    schema: &Schema,
+    batches: I,
+    lue: F,
+    colonne_identifiant: Option<&str>,
+) -> KalkotoResult<(Colonnes, usize)>
+where
+    I: IntoIterator<Item = Result<RecordBatch, ArrowError>>,
+    F: Fn(&str) -> bool,
+{
+    let column_names: Vec<String> = schema
+        .fields()
+        .iter()
+        .map(|field| field.name().to_owned())
+        .collect();
+
+    let mut column_values: Vec<Vec<Caracteristique>> = vec![vec![]; column_names.len()];
+    let mut schema_reference: Option<SchemaRef> = None;
+    let mut nrow = 0;
+
+    for (numero, batch) in batches.into_iter().enumerate() {
+        let batch = batch.map_err(MenageListAdapterError::Arrow)?;
+
+        let schema_batch = batch.schema();
+        let schema_reference = schema_reference.get_or_insert_with(|| schema_batch.clone());
+        let noms_batch = schema_batch.fields().iter().map(|field| field.name());
+
+        if schema_batch.fields() != schema_reference.fields() || !noms_batch.eq(column_names.iter())
+        {
+            return Err(From::from(MenageListAdapterError::Arrow(
+                ArrowError::SchemaError(format!(
+                    "Le batch {} n'a pas le même schéma que les précédents : {} au lieu de {}",
+                    numero + 1,
+                    schema_batch,
+                    schema_reference
                 )),
-            )),
-        };
+            )));
+        }
+
+        for ((values, column), nom_colonne) in column_values
+            .iter_mut()
+            .zip(batch.columns())
+            .zip(&column_names)
+            .filter(|(_, nom_colonne)| lue(nom_colonne))
+        {
+            let column = match colonne_identifiant == Some(nom_colonne.as_str()) {
+                true => cast(column, &DataType::Utf8)?,
+                false => column.clone(),
+            };
+            values.extend(extract_values_from_arrow(nom_colonne, column, nrow)?);
+        }
+        nrow += batch.num_rows();
+    }
 
-        extracted_dataframe
+    match schema_reference {
+        Some(_) => Ok((
+            column_names
+                .into_iter()
+                .zip(column_values)
+                .filter(|(nom_colonne, _)| lue(nom_colonne))
+                .collect(),
+            nrow,
+        )),
+        None => Err(KalkotoError::ListMenageError(
+            MenageListAdapterError::Arrow(ArrowError::ParseError(
+                "Le dataframe Arrow ne contient aucun batch".into(),
+            )),
+        )),
     }
 }
 
+// Conversion d'une colonne Arrow en caractéristiques. premiere_ligne est la position de la
+// première valeur de la colonne dans le fichier, pour situer les erreurs de conversion
 pub fn extract_values_from_arrow(
-    array: Arc<dyn Array>,
-    nrow: usize,
+    nom_colonne: &str,
+    array: ArrayRef,
+    premiere_ligne: usize,
 ) -> KalkotoResult<Vec<Caracteristique>> {
-    match array.data_type() {
-        // Cas 1 : Entiers (Int32)
-        arrow::datatypes::DataType::Int32 => {
-            let int_array = array.as_any().downcast_ref::<Int32Array>().ok_or_else(|| {
-                KalkotoError::ListMenageError(MenageListAdapterError::Arrow(
-                    arrow::error::ArrowError::CastError(
-                        "Erreur à la lecture d'une colonne de type Int32".into(),
-                    ),
-                ))
-            })?;
-
-            let values: KalkotoResult<Vec<Caracteristique>> = (0..nrow)
-                .map(|i| {
-                    if int_array.is_null(i) {
-                        Err(KalkotoError::ListMenageError(
-                            MenageListAdapterError::Validation {
-                                fault_index: i as i32,
-                                cause: "Valeur manquante (NA) !".into(),
-                                conseil: "Vérifier la construction du dataframe R".into(),
-                            },
-                        ))
-                    } else {
-                        Ok(Caracteristique::Entier(int_array.value(i)))
-                    }
-                })
-                .collect();
+    let erreur = |i: usize, cause: String| {
+        KalkotoError::ListMenageError(MenageListAdapterError::Conversion {
+            colonne: nom_colonne.to_owned(),
+            ligne: premiere_ligne + i + 1,
+            cause,
+        })
+    };
 
-            values
-        }
-        // Cas 2 : Flottants (Float64)
-        arrow::datatypes::DataType::Float64 => {
-            let float_array = array
-                .as_any()
-                .downcast_ref::<Float64Array>()
-                .ok_or_else(|| {
-                    KalkotoError::ListMenageError(MenageListAdapterError::Arrow(
-                        arrow::error::ArrowError::CastError(
-                            "Erreur à la lecture d'une colonne de type Float64".into(),
-                        ),
-                    ))
-                })?;
+    // Les valeurs nulles deviennent des valeurs manquantes, traitées à la validation
+    let entiers =
+        |valeurs: Vec<Option<Result<i32, String>>>| -> KalkotoResult<Vec<Caracteristique>> {
+            valeurs
+                .into_iter()
+                .enumerate()
+                .map(|(i, valeur)| match valeur {
+                    Some(valeur) => valeur
+                        .map(Caracteristique::Entier)
+                        .map_err(|e| erreur(i, e)),
+                    None => Ok(Caracteristique::Manquant),
+                })
+                .collect()
+        };
+    let manquant_ou = |valeur: Option<Caracteristique>| valeur.unwrap_or(Caracteristique::Manquant);
 
-            let values: KalkotoResult<Vec<Caracteristique>> = (0..nrow)
+    match array.data_type() {
+        DataType::Int8 => entiers(entiers_32_bits(array.as_primitive::<Int8Type>())),
+        DataType::Int16 => entiers(entiers_32_bits(array.as_primitive::<Int16Type>())),
+        DataType::Int32 => entiers(entiers_32_bits(array.as_primitive::<Int32Type>())),
+        DataType::Int64 => entiers(entiers_32_bits(array.as_primitive::<Int64Type>())),
+        DataType::UInt8 => entiers(entiers_32_bits(array.as_primitive::<UInt8Type>())),
+        DataType::UInt16 => entiers(entiers_32_bits(array.as_primitive::<UInt16Type>())),
+        DataType::UInt32 => entiers(entiers_32_bits(array.as_primitive::<UInt32Type>())),
+        DataType::UInt64 => entiers(entiers_32_bits(array.as_primitive::<UInt64Type>())),
+        DataType::Float16 => Ok(array
+            .as_primitive::<Float16Type>()
+            .iter()
+            .map(|valeur| manquant_ou(valeur.map(|x| Caracteristique::Numeric(x.to_f64()))))
+            .collect()),
+        DataType::Float32 => Ok(array
+            .as_primitive::<Float32Type>()
+            .iter()
+            .map(|valeur| manquant_ou(valeur.map(|x| Caracteristique::Numeric(x as f64))))
+            .collect()),
+        DataType::Float64 => Ok(array
+            .as_primitive::<Float64Type>()
+            .iter()
+            .map(|valeur| manquant_ou(valeur.map(Caracteristique::Numeric)))
+            .collect()),
+        DataType::Boolean => Ok(array
+            .as_boolean()
+            .iter()
+            .map(|valeur| manquant_ou(valeur.map(Caracteristique::Booleen)))
+            .collect()),
+        DataType::Date32 => {
+            let dates = array.as_primitive::<Date32Type>();
+            (0..dates.len())
                 .map(|i| {
-                    if float_array.is_null(i) {
-                        Err(KalkotoError::ListMenageError(
-                            MenageListAdapterError::Validation {
-                                fault_index: i as i32,
-                                cause: "Valeur manquante (NA) !".into(),
-                                conseil: "Vérifier la construction du dataframe R".into(),
-                            },
-                        ))
-                    } else {
-                        Ok(Caracteristique::Numeric(float_array.value(i)))
+                    if dates.is_null(i) {
+                        return Ok(Caracteristique::Manquant);
                     }
+                    dates
+                        .value_as_date(i)
+                        .map(Caracteristique::Date)
+                        .ok_or_else(|| erreur(i, "date hors des bornes représentables".into()))
                 })
-                .collect();
-
-            values
+                .collect()
         }
-        // Cas 3 : Chaînes de caractères
-        arrow::datatypes::DataType::Utf8 => {
-            let str_array = array
-                .as_any()
-                .downcast_ref::<StringArray>()
-                .ok_or_else(|| {
-                    KalkotoError::ListMenageError(MenageListAdapterError::Arrow(
-                        arrow::error::ArrowError::CastError(
-                            "Erreur à la lecture d'une colonne de type Utf8/String".into(),
-                        ),
-                    ))
-                })?;
-
-            let values: KalkotoResult<Vec<Caracteristique>> = (0..nrow)
+        DataType::Date64 => {
+            let dates = array.as_primitive::<Date64Type>();
+            (0..dates.len())
                 .map(|i| {
-                    if str_array.is_null(i) {
-                        Err(KalkotoError::ListMenageError(
-                            MenageListAdapterError::Validation {
-                                fault_index: i as i32,
-                                cause: "Valeur manquante (NA) !".into(),
-                                conseil: "Vérifier la construction du dataframe R".into(),
-                            },
-                        ))
-                    } else {
-                        Ok(Caracteristique::Textuel(str_array.value(i).to_string()))
+                    if dates.is_null(i) {
+                        return Ok(Caracteristique::Manquant);
                     }
+                    dates
+                        .value_as_date(i)
+                        .map(Caracteristique::Date)
+                        .ok_or_else(|| erreur(i, "date hors des bornes représentables".into()))
                 })
-                .collect();
-
-            values
+                .collect()
+        }
+        DataType::Utf8 => Ok(array
+            .as_string::<i32>()
+            .iter()
+            .map(|valeur| manquant_ou(valeur.map(|texte| Caracteristique::Textuel(texte.into()))))
+            .collect()),
+        // Chaînes longues, vues et facteurs R (dictionnaires) : conversion vers le type des
+        // valeurs, puis lecture de ce type
+        DataType::LargeUtf8 | DataType::Utf8View => {
+            extract_values_from_arrow(nom_colonne, cast(&array, &DataType::Utf8)?, premiere_ligne)
+        }
+        DataType::Dictionary(_, type_valeurs) => {
+            extract_values_from_arrow(nom_colonne, cast(&array, type_valeurs)?, premiere_ligne)
         }
         // Les autres types Arrow ne sont pas reconnus comme valides
-        _ => Err(KalkotoError::ListMenageError(
-            MenageListAdapterError::Arrow(arrow::error::ArrowError::SchemaError(
-                "Le type de la colonne n'est pas supporté".into(),
-            )),
+        type_colonne => Err(KalkotoError::ListMenageError(
+            MenageListAdapterError::Arrow(ArrowError::SchemaError(format!(
+                "Le type {} de la colonne {} n'est pas supporté",
+                type_colonne, nom_colonne
+            ))),
         )),
     }
 }
 
+fn entiers_32_bits<T>(array: &PrimitiveArray<T>) -> Vec<Option<Result<i32, String>>>
+where
+    T: ArrowPrimitiveType,
+    T::Native: TryInto<i32> + std::fmt::Display,
+{
+    array
+        .iter()
+        .map(|valeur| {
+            valeur.map(|valeur| {
+                valeur.try_into().map_err(|_| {
+                    format!(
+                        "la valeur {} dépasse les bornes d'un entier 32 bits",
+                        valeur
+                    )
+                })
+            })
+        })
+        .collect()
+}
+
+fn cast(array: &ArrayRef, type_cible: &DataType) -> KalkotoResult<ArrayRef> {
+    Ok(arrow::compute::cast(array, type_cible).map_err(MenageListAdapterError::Arrow)?)
+}
+
 impl MenageListCreator for ArrowInputAdapter {
     fn create_valid_menage_input(
         self,
         empty_menage_input: MenageInputBuilder<crate::entities::menage_input::EmptyList>,
     ) -> KalkotoResult<MenageInput> {
         match self.dataframe {
-            Some(dataframe) => {
-                let mut liste_menages = vec![Menage::new(0); self.nrow];
-                //(Some(set_caracteristiques), Some(liste_menages)) =
-                for i in (0..self.nrow) {
-                    let mut menage = Menage::new(i as i32);
-                    for (nom_caracteristique, valeurs_caracteristique) in
-                        dataframe.iter().take(self.ncol)
-                    {
-                        menage.caracteristiques.insert(
-                            nom_caracteristique.clone(),
-                            valeurs_caracteristique
-                                .get(i)
-                                .ok_or_else(|| MenageListAdapterError::Validation {
-                                    fault_index: i as i32,
-                                    cause: format!(
-                                        "Problème à la lecture de la composante {}",
-                                        nom_caracteristique
-                                    ),
-                                    conseil: "".into(),
-                                })?
-                                .clone(),
-                        );
-                        if let Some(menage_init) = liste_menages.get_mut(i) {
-                            *menage_init = menage.clone();
-                        }
-                    }
-                }
-
+            Some(dataframe) => create_menage_input_from_columns(
+                &dataframe,
+                self.ncol,
+                self.nrow,
                 empty_menage_input
-                    .from_unvalidated_liste_menage(&liste_menages)
-                    .validate_liste_menage()?
-                    .build_valide_menage_input()
-            }
+                    .with_schema(self.schema)
+                    .with_colonne_identifiant(self.colonne_identifiant)
+                    .with_colonnes(self.colonnes),
+            ),
             _ => Err(KalkotoError::ListMenageError(
                 MenageListAdapterError::Uninitialized,
             )),
         }
     }
 }
+
+// Construction de la liste des ménages à partir de colonnes déjà extraites
+// (fichiers Arrow et Parquet)
+pub(crate) fn create_menage_input_from_columns(
+    dataframe: &[(String, Vec<Caracteristique>)],
+    ncol: usize,
+    nrow: usize,
+    empty_menage_input: MenageInputBuilder<crate::entities::menage_input::EmptyList>,
+) -> KalkotoResult<MenageInput> {
+    let mut liste_menages = vec![Menage::new(0); nrow];
+    //(Some(set_caracteristiques), Some(liste_menages)) =
+    for i in (0..nrow) {
+        let mut menage = Menage::new(i as i32);
+        for (nom_caracteristique, valeurs_caracteristique) in dataframe.iter().take(ncol) {
+            menage.caracteristiques.insert(
+                nom_caracteristique.clone(),
+                valeurs_caracteristique
+                    .get(i)
+                    .ok_or_else(|| MenageListAdapterError::Validation {
+                        fault_index: i as i32,
+                        cause: format!(
+                            "Problème à la lecture de la composante {}",
+                            nom_caracteristique
+                        ),
+                        conseil: "".into(),
+                    })?
+                    .clone(),
+            );
+            if let Some(menage_init) = liste_menages.get_mut(i) {
+                *menage_init = menage.clone();
+            }
+        }
+    }
+
+    empty_menage_input
+        .from_unvalidated_liste_menage(&liste_menages)
+        .validate_liste_menage()?
+        .build_valide_menage_input()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entities::menage_input::EmptyList;
+    use arrow::array::{
+        BooleanArray, Date32Array, DictionaryArray, Float32Array, Float64Array, Int32Array,
+        Int64Array, LargeStringArray,
+    };
+    use arrow::datatypes::Field;
+    use arrow::ipc::writer::{FileWriter, StreamWriter};
+    use chrono::NaiveDate;
+
+    fn create_test_batches(schema: &SchemaRef) -> Vec<RecordBatch> {
+        [vec![80, 70], vec![60]]
+            .into_iter()
+            .map(|ages| {
+                RecordBatch::try_new(schema.clone(), vec![Arc::new(Int32Array::from(ages))])
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    fn ages(adapter: ArrowInputAdapter) -> KalkotoResult<Vec<Caracteristique>> {
+        Ok(adapter
+            .create_valid_menage_input(MenageInputBuilder::<EmptyList>::new())?
+            .liste_menage_valide
+            .iter()
+            .map(|menage| menage.caracteristiques["Age"].clone())
+            .collect())
+    }
+
+    #[test]
+    fn ok_all_batches_file_and_stream() -> KalkotoResult<()> {
+        let schema = Arc::new(Schema::new(vec![Field::new("Age", DataType::Int32, false)]));
+
+        let mut fichier = vec![];
+        let mut writer =
+            FileWriter::try_new(&mut fichier, &schema).map_err(MenageListAdapterError::Arrow)?;
+        let mut flux = vec![];
+        let mut stream_writer =
+            StreamWriter::try_new(&mut flux, &schema).map_err(MenageListAdapterError::Arrow)?;
+        for batch in create_test_batches(&schema) {
+            writer
+                .write(&batch)
+                .map_err(MenageListAdapterError::Arrow)?;
+            stream_writer
+                .write(&batch)
+                .map_err(MenageListAdapterError::Arrow)?;
+        }
+        writer.finish().map_err(MenageListAdapterError::Arrow)?;
+        stream_writer
+            .finish()
+            .map_err(MenageListAdapterError::Arrow)?;
+        drop(writer);
+        drop(stream_writer);
+
+        let wanted = vec![
+            Caracteristique::Entier(80),
+            Caracteristique::Entier(70),
+            Caracteristique::Entier(60),
+        ];
+        assert_eq!(
+            wanted,
+            ages(ArrowInputAdapter::new().populate_from_memory(&fichier)?)?
+        );
+        assert_eq!(
+            wanted,
+            ages(ArrowInputAdapter::new().populate_from_stream(flux.as_slice())?)?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn ok_identifiant_au_dela_de_32_bits() -> KalkotoResult<()> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("Age", DataType::Int32, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int64Array::from(vec![2019000000001, 2019000000002])),
+                Arc::new(Int32Array::from(vec![80, 70])),
+            ],
+        )
+        .map_err(MenageListAdapterError::Arrow)?;
+
+        let mut flux = vec![];
+        let mut writer =
+            StreamWriter::try_new(&mut flux, &schema).map_err(MenageListAdapterError::Arrow)?;
+        writer
+            .write(&batch)
+            .map_err(MenageListAdapterError::Arrow)?;
+        writer.finish().map_err(MenageListAdapterError::Arrow)?;
+        drop(writer);
+
+        let menage_input = ArrowInputAdapter::new()
+            .with_colonne_identifiant("id")
+            .populate_from_stream(flux.as_slice())?
+            .create_valid_menage_input(MenageInputBuilder::<EmptyList>::new())?;
+
+        assert_eq!(
+            vec!["2019000000001", "2019000000002"],
+            menage_input
+                .liste_menage_valide
+                .iter()
+                .map(Menage::cle)
+                .collect::<Vec<_>>()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn err_inconsistent_batch_schema() -> KalkotoResult<()> {
+        let schema = Arc::new(Schema::new(vec![Field::new("Age", DataType::Int32, false)]));
+        let schema_float = Arc::new(Schema::new(vec![Field::new(
+            "Age",
+            DataType::Float64,
+            false,
+        )]));
+        let batch_float =
+            RecordBatch::try_new(schema_float, vec![Arc::new(Float64Array::from(vec![50.5]))])
+                .map_err(MenageListAdapterError::Arrow)?;
+
+        let batches = create_test_batches(&schema)
+            .into_iter()
+            .chain([batch_float])
+            .map(Ok);
+        let result = extract_columns_from_batches(&schema, batches, |_| true, None);
+
+        let wanted = true;
+        let result = matches!(
+            result,
+            Err(KalkotoError::ListMenageError(
+                MenageListAdapterError::Arrow(ArrowError::SchemaError(_))
+            ))
+        );
+        assert_eq!(wanted, result);
+
+        Ok(())
+    }
+
+    #[test]
+    fn ok_wider_arrow_types() -> KalkotoResult<()> {
+        let facteur: DictionaryArray<Int32Type> =
+            vec!["urbain", "rural", "urbain"].into_iter().collect();
+        let colonnes: Vec<(&str, ArrayRef)> = vec![
+            ("Int64", Arc::new(Int64Array::from(vec![1, 2, 3]))),
+            ("Float32", Arc::new(Float32Array::from(vec![0.5, 1.5, 2.5]))),
+            (
+                "Boolean",
+                Arc::new(BooleanArray::from(vec![true, false, true])),
+            ),
+            ("Dictionary", Arc::new(facteur)),
+            ("Date32", Arc::new(Date32Array::from(vec![0, 19723, 365]))),
+            (
+                "LargeUtf8",
+                Arc::new(LargeStringArray::from(vec!["a", "b", "c"])),
+            ),
+        ];
+
+        let result: Vec<Caracteristique> = colonnes
+            .into_iter()
+            .map(|(nom, colonne)| extract_values_from_arrow(nom, colonne, 0))
+            .collect::<KalkotoResult<Vec<Vec<Caracteristique>>>>()?
+            .into_iter()
+            .map(|valeurs| valeurs[1].clone())
+            .collect();
+
+        let wanted = vec![
+            Caracteristique::Entier(2),
+            Caracteristique::Numeric(1.5),
+            Caracteristique::Booleen(false),
+            Caracteristique::Textuel("rural".into()),
+            Caracteristique::Date(NaiveDate::from_ymd_opt(2024, 1, 1).unwrap()),
+            Caracteristique::Textuel("b".into()),
+        ];
+        assert_eq!(wanted, result);
+
+        Ok(())
+    }
+
+    #[test]
+    fn err_int64_overflow_located() -> KalkotoResult<()> {
+        let colonne = Arc::new(Int64Array::from(vec![1, 2, i64::from(i32::MAX) + 1]));
+
+        let result = extract_values_from_arrow("Revenu", colonne, 10);
+
+        let wanted = true;
+        let result = matches!(
+            result,
+            Err(KalkotoError::ListMenageError(MenageListAdapterError::Conversion {
+                ref colonne,
+                ligne: 13,
+                ..
+            })) if colonne == "Revenu"
+        );
+        assert_eq!(wanted, result);
+
+        Ok(())
+    }
+}
@@ -1,9 +1,14 @@
-use crate::adapters::input_adapters::{MenageListAdapterError, MenageListCreator};
+use crate::adapters::input_adapters::{colonne_lue, MenageListAdapterError, MenageListCreator};
 use crate::entities::menage::{Caracteristique, Menage};
 use crate::entities::menage_input::{MenageInput, MenageInputBuilder};
+use crate::entities::schema_menages::{SchemaMenages, TypeCaracteristique};
 use crate::{KalkotoError, KalkotoResult};
+use chrono::NaiveDate;
 use csv::{Reader, ReaderBuilder, StringRecord};
+use encoding_rs::WINDOWS_1252;
+use std::borrow::Cow;
 use std::ffi::OsStr;
+use std::str::FromStr;
 use std::{
     collections::{HashMap, HashSet},
     error::Error,
@@ -22,75 +27,345 @@ impl From<csv::Error> for MenageListAdapterError {
     }
 }
 
+// Encodage du fichier CSV. Latin1 est lu comme Windows-1252, son sur-ensemble utilisé par
+// les exports des tableurs sous Windows
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Encodage {
+    #[default]
+    Utf8,
+    Latin1,
+}
+
+impl FromStr for Encodage {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "utf8" | "utf-8" => Ok(Encodage::Utf8),
+            "latin1" | "latin-1" | "iso-8859-1" | "windows-1252" | "cp1252" => Ok(Encodage::Latin1),
+            autre => Err(format!(
+                "Encodage {autre} inconnu : utiliser utf-8 ou latin1"
+            )),
+        }
+    }
+}
+
+// Dialecte du fichier CSV :
+// - delimiteur : séparateur des champs, détecté sur la ligne d'en-tête s'il n'est pas indiqué
+// - separateur_decimal : '.' ou ',' (exports en locale française : "1234,56")
+// - guillemet : caractère d'encadrement des champs, None pour lire les guillemets tels quels
+// - entete : sans ligne d'en-tête, les colonnes sont nommées par noms_colonnes,
+//   ou à défaut colonne_1, colonne_2...
+// - vrai, faux : écritures des booléens ("oui"/"non" par exemple)
+// - formats_dates : formats chrono des dates ("%d/%m/%Y" par exemple), essayés dans l'ordre
+#[derive(Debug, Clone, PartialEq)]
+pub struct OptionsCsv {
+    pub delimiteur: Option<u8>,
+    pub separateur_decimal: u8,
+    pub encodage: Encodage,
+    pub guillemet: Option<u8>,
+    pub entete: bool,
+    pub noms_colonnes: Option<Vec<String>>,
+    pub vrai: Vec<String>,
+    pub faux: Vec<String>,
+    pub formats_dates: Vec<String>,
+}
+
+impl Default for OptionsCsv {
+    fn default() -> Self {
+        OptionsCsv {
+            delimiteur: None,
+            separateur_decimal: b'.',
+            encodage: Encodage::Utf8,
+            guillemet: Some(b'"'),
+            entete: true,
+            noms_colonnes: None,
+            vrai: vec!["true".into()],
+            faux: vec!["false".into()],
+            formats_dates: vec!["%Y-%m-%d".into()],
+        }
+    }
+}
+
+const DELIMITEURS_CANDIDATS: [u8; 4] = [b';', b',', b'\t', b'|'];
+
+impl OptionsCsv {
+    pub fn new() -> Self {
+        OptionsCsv::default()
+    }
+
+    // Délimiteur le plus fréquent hors guillemets sur la première ligne, ';' par défaut
+    fn detecte_delimiteur(&self, contenu: &str) -> u8 {
+        let premiere_ligne = contenu.lines().next().unwrap_or_default();
+        let mut entre_guillemets = false;
+        let mut occurrences = [0usize; DELIMITEURS_CANDIDATS.len()];
+
+        for octet in premiere_ligne.bytes() {
+            if Some(octet) == self.guillemet {
+                entre_guillemets = !entre_guillemets;
+            } else if let Some(position) = DELIMITEURS_CANDIDATS
+                .iter()
+                .position(|candidat| *candidat == octet && !entre_guillemets)
+            {
+                occurrences[position] += 1;
+            }
+        }
+
+        DELIMITEURS_CANDIDATS
+            .iter()
+            .zip(occurrences)
+            .filter(|(_, nombre)| *nombre > 0)
+            .max_by_key(|(_, nombre)| *nombre)
+            .map_or(b';', |(candidat, _)| *candidat)
+    }
+
+    fn decode<'a>(&self, input_buf: &'a [u8]) -> KalkotoResult<Cow<'a, str>> {
+        match self.encodage {
+            Encodage::Utf8 => {
+                let input_buf = input_buf.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(input_buf);
+                std::str::from_utf8(input_buf)
+                    .map(Cow::Borrowed)
+                    .map_err(|e| {
+                        From::from(MenageListAdapterError::Validation {
+                            fault_index: -1,
+                            cause: format!("Le fichier CSV n'est pas encodé en UTF-8 ({e})"),
+                            conseil: "Indiquer l'encodage du fichier (latin1 par exemple)"
+                                .to_string(),
+                        })
+                    })
+            }
+            Encodage::Latin1 => Ok(WINDOWS_1252.decode_without_bom_handling(input_buf).0),
+        }
+    }
+
+    // Avec la virgule décimale, "1234,56" est lu comme le nombre 1234.56
+    fn caracteristique(&self, champ: &str) -> Caracteristique {
+        if self.vrai.iter().any(|vrai| vrai == champ) {
+            return Caracteristique::Booleen(true);
+        }
+        if self.faux.iter().any(|faux| faux == champ) {
+            return Caracteristique::Booleen(false);
+        }
+        if let Some(date) = self
+            .formats_dates
+            .iter()
+            .find_map(|format| NaiveDate::parse_from_str(champ, format).ok())
+        {
+            return Caracteristique::Date(date);
+        }
+        if self.separateur_decimal != b'.' && champ.contains(self.separateur_decimal as char) {
+            let champ_point = champ.replace(self.separateur_decimal as char, ".");
+            if let Ok(numeric) = champ_point.parse::<f64>() {
+                return Caracteristique::Numeric(numeric);
+            }
+        }
+        Caracteristique::from(champ.to_string())
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct CsvInputAdapter {
     set_caracteristiques: Option<HashSet<String>>,
     liste_menages: Option<Vec<Menage>>,
+    options: OptionsCsv,
+    schema: Option<SchemaMenages>,
+    colonne_identifiant: Option<String>,
+    colonnes: Option<HashSet<String>>,
 }
 
 impl CsvInputAdapter {
     pub fn new() -> Self {
         CsvInputAdapter::default()
     }
+
+    pub fn with_options(self, options: OptionsCsv) -> Self {
+        CsvInputAdapter { options, ..self }
+    }
+
+    // Les colonnes déclarées dans le schéma sont lues selon leur type
+    pub fn with_schema(self, schema: SchemaMenages) -> Self {
+        CsvInputAdapter {
+            schema: Some(schema),
+            ..self
+        }
+    }
+
+    // Colonne dont les valeurs, uniques, identifient les ménages dans les résultats. Elle ne
+    // fait pas partie des caractéristiques et ses champs sont lus tels quels (zéros initiaux)
+    pub fn with_colonne_identifiant(self, colonne_identifiant: &str) -> Self {
+        CsvInputAdapter {
+            colonne_identifiant: Some(colonne_identifiant.to_string()),
+            ..self
+        }
+    }
+
+    // Seules ces colonnes (et la colonne identifiant) sont lues : celles dont dépendent les
+    // politiques et celles reprises dans les résultats
+    pub fn with_colonnes(self, colonnes: HashSet<String>) -> Self {
+        CsvInputAdapter {
+            colonnes: Some(colonnes),
+            ..self
+        }
+    }
 }
 
 impl CsvInputAdapter {
     pub fn populate_from_buf(
-        self,
+        &self,
         input_buf: &[u8],
     ) -> KalkotoResult<(HashSet<String>, Vec<Menage>)> {
-        let mut rdr = ReaderBuilder::new()
-            .delimiter(b';')
-            .has_headers(false)
-            .from_reader(input_buf);
+        self.lit_contenu(&self.options.decode(input_buf)?)
+    }
 
-        let mut headers_row: Vec<String> = vec![];
-        let mut vec_menage: Vec<Menage> = vec![];
+    fn lit_contenu(&self, contenu: &str) -> KalkotoResult<(HashSet<String>, Vec<Menage>)> {
+        let delimiteur = self
+            .options
+            .delimiteur
+            .unwrap_or_else(|| self.options.detecte_delimiteur(contenu));
 
-        if let Some(result) = rdr.records().next() {
-            let headers = result.map_err(|e| MenageListAdapterError::Validation {
-                fault_index: -1,
-                cause: "Problème à la lecture du header du CSV".to_string(),
-                conseil: "Vérifier le fichier CSV".to_string(),
-            })?;
+        let mut rdr_builder = ReaderBuilder::new();
+        rdr_builder.delimiter(delimiteur).has_headers(false);
+        match self.options.guillemet {
+            Some(guillemet) => rdr_builder.quote(guillemet),
+            None => rdr_builder.quoting(false),
+        };
+        let mut rdr = rdr_builder.from_reader(contenu.as_bytes());
 
-            headers_row = headers
-                .iter()
-                .map(|str| str.to_string())
-                .collect::<Vec<String>>();
+        let mut records = rdr.records().peekable();
 
-            for (index, row) in rdr.records().enumerate() {
-                let caracteristiques_vec: Vec<Caracteristique> = row
+        let headers_row: Vec<String> = match (self.options.entete, &self.options.noms_colonnes) {
+            (true, _) => match records.next() {
+                Some(result) => result
                     .map_err(|e| MenageListAdapterError::Validation {
-                        fault_index: index as i32,
-                        cause: "Les caractéristiques de ces ménages semblent invalides".to_string(),
+                        fault_index: -1,
+                        cause: "Problème à la lecture du header du CSV".to_string(),
                         conseil: "Vérifier le fichier CSV".to_string(),
                     })?
                     .iter()
                     .map(|str| str.to_string())
-                    .map(Caracteristique::from)
-                    .collect();
+                    .collect(),
+                None => vec![],
+            },
+            (false, Some(noms_colonnes)) => noms_colonnes.clone(),
+            (false, None) => match records.peek() {
+                Some(Ok(premiere_ligne)) => (1..=premiere_ligne.len())
+                    .map(|numero| format!("colonne_{numero}"))
+                    .collect(),
+                _ => vec![],
+            },
+        };
 
-                let caracteristiques: HashMap<String, Caracteristique> = headers_row
-                    .iter()
-                    .cloned()
-                    .zip(caracteristiques_vec.iter().cloned())
-                    .collect();
+        // Les champs des colonnes hors de la projection ne sont pas convertis
+        let lues: Vec<bool> = headers_row
+            .iter()
+            .map(|nom_colonne| colonne_lue(&self.colonnes, &self.colonne_identifiant, nom_colonne))
+            .collect();
 
-                let menage = Menage {
-                    index: (index as i32) + 1i32,
-                    caracteristiques,
-                };
+        let mut vec_menage: Vec<Menage> = vec![];
 
-                vec_menage.push(menage);
+        for (index, row) in records.enumerate() {
+            let row = row.map_err(|e| MenageListAdapterError::Validation {
+                fault_index: index as i32,
+                cause: "Les caractéristiques de ces ménages semblent invalides".to_string(),
+                conseil: "Vérifier le fichier CSV".to_string(),
+            })?;
+
+            if row.len() != headers_row.len() {
+                return Err(From::from(MenageListAdapterError::Validation {
+                    fault_index: (index as i32) + 1i32,
+                    cause: format!(
+                        "Ce ménage a {} caractéristiques pour {} colonnes",
+                        row.len(),
+                        headers_row.len()
+                    ),
+                    conseil: "Vérifier le délimiteur et les noms de colonnes du fichier CSV"
+                        .to_string(),
+                }));
             }
+
+            let caracteristiques: HashMap<String, Caracteristique> = headers_row
+                .iter()
+                .zip(row.iter())
+                .zip(&lues)
+                .filter(|(_, lue)| **lue)
+                .map(|((nom_colonne, champ), _)| {
+                    let caracteristique =
+                        self.caracteristique(nom_colonne, champ, (index as i32) + 1i32)?;
+                    Ok((nom_colonne.clone(), caracteristique))
+                })
+                .collect::<Result<_, MenageListAdapterError>>()?;
+
+            let menage = Menage {
+                index: (index as i32) + 1i32,
+                identifiant: None,
+                poids: None,
+                caracteristiques,
+                individus: vec![],
+            };
+
+            vec_menage.push(menage);
         }
 
-        let headers_set: HashSet<String> = headers_row.into_iter().collect();
+        let headers_set: HashSet<String> = headers_row
+            .into_iter()
+            .zip(lues)
+            .filter_map(|(nom_colonne, lue)| lue.then_some(nom_colonne))
+            .collect();
 
         Ok((headers_set, vec_menage))
     }
 
+    // Champ lu selon le type déclaré dans le schéma, ou deviné si sa colonne n'y est pas
+    fn caracteristique(
+        &self,
+        nom_colonne: &str,
+        champ: &str,
+        menage: i32,
+    ) -> Result<Caracteristique, MenageListAdapterError> {
+        if self.colonne_identifiant.as_deref() == Some(nom_colonne) {
+            return Ok(match champ.is_empty() {
+                true => Caracteristique::Manquant,
+                false => Caracteristique::Textuel(champ.to_string()),
+            });
+        }
+
+        let Some(schema) = &self.schema else {
+            return Ok(self.options.caracteristique(champ));
+        };
+
+        match schema
+            .caracteristique(nom_colonne)
+            .map(|declaration| declaration.type_caracteristique)
+        {
+            Some(TypeCaracteristique::Numeric) => schema.lit(
+                nom_colonne,
+                &champ.replace(self.options.separateur_decimal as char, "."),
+                menage,
+            ),
+            // Lus selon les écritures et formats du dialecte, puis convertis par le schéma
+            Some(TypeCaracteristique::Booleen | TypeCaracteristique::Date) => {
+                Ok(self.options.caracteristique(champ))
+            }
+            Some(_) => schema.lit(nom_colonne, champ, menage),
+            None => Ok(self.options.caracteristique(champ)),
+        }
+    }
+
+    // Lecture d'un CSV déjà chargé en mémoire (application web notamment)
+    pub fn populate_from_memory(self, input_buf: &[u8]) -> KalkotoResult<Self> {
+        let (set_caracteristiques, liste_menages) = self.populate_from_buf(input_buf)?;
+
+        Ok(CsvInputAdapter {
+            set_caracteristiques: Some(set_caracteristiques),
+            liste_menages: Some(liste_menages),
+            options: self.options,
+            schema: self.schema,
+            colonne_identifiant: self.colonne_identifiant,
+            colonnes: self.colonnes,
+        })
+    }
+
+    // Le contenu décodé du fichier est conservé dans buf_string
     pub fn populate_from_path<P>(self, path: P, buf_string: &mut String) -> KalkotoResult<Self>
     where
         P: AsRef<Path>,
@@ -104,24 +379,18 @@ impl CsvInputAdapter {
             }
         }
 
-        let mut f = match File::open(path) {
-            Ok(file) => file,
-            Err(e) => return Err(From::from(MenageListAdapterError::IO(e))),
-        };
-
-        // read the whole file
-        let _ = match f.read_to_string(buf_string) {
-            Ok(nbytes) => nbytes,
-            Err(e) => return Err(From::from(MenageListAdapterError::IO(e))),
-        };
+        let input_buf = std::fs::read(path).map_err(MenageListAdapterError::IO)?;
+        buf_string.push_str(&self.options.decode(&input_buf)?);
 
-        let input_slice = buf_string.as_bytes();
-
-        let (set_caracteristiques, liste_menages) = self.populate_from_buf(input_slice)?;
+        let (set_caracteristiques, liste_menages) = self.lit_contenu(buf_string)?;
 
         Ok(CsvInputAdapter {
             set_caracteristiques: Some(set_caracteristiques),
             liste_menages: Some(liste_menages),
+            options: self.options,
+            schema: self.schema,
+            colonne_identifiant: self.colonne_identifiant,
+            colonnes: self.colonnes,
         })
     }
 }
@@ -133,6 +402,9 @@ impl MenageListCreator for CsvInputAdapter {
     ) -> KalkotoResult<MenageInput> {
         match (self.set_caracteristiques, self.liste_menages) {
             (Some(set_caracteristiques), Some(liste_menages)) => empty_menage_input
+                .with_schema(self.schema)
+                .with_colonne_identifiant(self.colonne_identifiant)
+                .with_colonnes(self.colonnes)
                 .from_unvalidated_liste_menage(&liste_menages)
                 .validate_liste_menage()?
                 .build_valide_menage_input(),
@@ -151,6 +423,8 @@ impl MenageListCreator for CsvInputAdapter {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::entities::formula::Filtre;
+    use crate::entities::menage_input::{TraitementManquant, ValeursManquantes};
     use std::fs;
     use tempdir::TempDir;
 
@@ -173,7 +447,10 @@ mod tests {
 
         let wanted_vec_menage = vec![Menage {
             index: 1,
+            identifiant: None,
+            poids: None,
             caracteristiques: wanted_hashmap,
+            individus: vec![],
         }];
 
         let (result_hashset, result_vec_menage) =
@@ -249,13 +526,17 @@ mod tests {
 
         let wanted_vec_menage = vec![Menage {
             index: 1,
+            identifiant: None,
+            poids: None,
             caracteristiques: wanted_hashmap,
+            individus: vec![],
         }];
 
         let mut csv_content = String::new();
         let CsvInputAdapter {
             set_caracteristiques: result_hashset,
             liste_menages: result_vec_menage,
+            ..
         } = CsvInputAdapter::new().populate_from_path(file_path, &mut csv_content)?;
 
         let wanted = true;
@@ -307,4 +588,535 @@ mod tests {
 
         Ok(())
     }
+
+    static MANQUANT_CSV_BYTES: &[u8] = "Age;Revenu;Commune\n35;;Paris\n40;1000.5;\n".as_bytes();
+
+    #[test]
+    fn err_csv_valeur_manquante_par_defaut() -> KalkotoResult<()> {
+        let result = CsvInputAdapter::new()
+            .populate_from_memory(MANQUANT_CSV_BYTES)?
+            .create_valid_menage_input(MenageInputBuilder::new());
+
+        let wanted = true;
+        let result = matches!(
+            result,
+            Err(KalkotoError::ListMenageError(
+                MenageListAdapterError::ValeurManquante { menage: 1, ref colonne }
+            )) if colonne == "Revenu"
+        );
+        assert_eq!(wanted, result);
+
+        Ok(())
+    }
+
+    #[test]
+    fn ok_csv_valeurs_manquantes_par_colonne() -> KalkotoResult<()> {
+        let valeurs_manquantes = ValeursManquantes::pour_toutes(TraitementManquant::Transmettre)
+            .colonne(
+                "Revenu",
+                TraitementManquant::Defaut(Caracteristique::Numeric(0.0)),
+            );
+
+        let menage_input = CsvInputAdapter::new()
+            .populate_from_memory(MANQUANT_CSV_BYTES)?
+            .create_valid_menage_input(
+                MenageInputBuilder::new().with_valeurs_manquantes(valeurs_manquantes),
+            )?;
+
+        let menages = menage_input.liste_menage_valide;
+        assert_eq!(
+            Caracteristique::Numeric(0.0),
+            menages[0].caracteristiques["Revenu"]
+        );
+        assert_eq!(
+            Caracteristique::Manquant,
+            menages[1].caracteristiques["Commune"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn err_csv_types_differents_malgre_valeur_manquante() -> KalkotoResult<()> {
+        static CSV_BYTES: &[u8] = "Age;Revenu\n35;1000.5\n40;\n45;Paris\n".as_bytes();
+
+        let result = CsvInputAdapter::new()
+            .populate_from_memory(CSV_BYTES)?
+            .create_valid_menage_input(MenageInputBuilder::new().with_valeurs_manquantes(
+                ValeursManquantes::pour_toutes(TraitementManquant::Transmettre),
+            ));
+
+        let wanted = true;
+        let result = matches!(
+            result,
+            Err(KalkotoError::ListMenageError(
+                MenageListAdapterError::Validation { fault_index: 3, .. }
+            ))
+        );
+        assert_eq!(wanted, result);
+
+        Ok(())
+    }
+
+    #[test]
+    fn ok_csv_virgule_decimale_latin1() -> KalkotoResult<()> {
+        // "Commune" vaut "Orléans" en Windows-1252 (é = 0xE9)
+        static LATIN1_CSV_BYTES: &[u8] = b"Age;Revenu;Commune\n35;1234,56;Orl\xE9ans\n";
+
+        let options = OptionsCsv {
+            separateur_decimal: b',',
+            encodage: Encodage::Latin1,
+            ..OptionsCsv::new()
+        };
+        let (_, menages) = CsvInputAdapter::new()
+            .with_options(options)
+            .populate_from_buf(LATIN1_CSV_BYTES)?;
+
+        assert_eq!(
+            Caracteristique::Numeric(1234.56),
+            menages[0].caracteristiques["Revenu"]
+        );
+        assert_eq!(
+            Caracteristique::Textuel("Orléans".into()),
+            menages[0].caracteristiques["Commune"]
+        );
+
+        let result = CsvInputAdapter::new().populate_from_buf(LATIN1_CSV_BYTES);
+        let wanted = true;
+        assert_eq!(wanted, result.is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn ok_csv_delimiteur_detecte() -> KalkotoResult<()> {
+        static VIRGULE_CSV_BYTES: &[u8] =
+            "\u{FEFF}Age,\"Type; logement\"\n35,\"Locataire, HLM\"\n".as_bytes();
+
+        let (caracteristiques, menages) =
+            CsvInputAdapter::new().populate_from_buf(VIRGULE_CSV_BYTES)?;
+
+        let wanted: HashSet<String> = ["Age".to_string(), "Type; logement".to_string()].into();
+        assert_eq!(wanted, caracteristiques);
+        assert_eq!(
+            Caracteristique::Textuel("Locataire, HLM".into()),
+            menages[0].caracteristiques["Type; logement"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn ok_csv_sans_entete() -> KalkotoResult<()> {
+        static SANS_ENTETE_CSV_BYTES: &[u8] = "35\t\"HLM\"\n40\tMaison\n".as_bytes();
+
+        let options = OptionsCsv {
+            delimiteur: Some(b'\t'),
+            guillemet: None,
+            entete: false,
+            noms_colonnes: Some(vec!["Age".into(), "Logement".into()]),
+            ..OptionsCsv::new()
+        };
+        let (_, menages) = CsvInputAdapter::new()
+            .with_options(options.clone())
+            .populate_from_buf(SANS_ENTETE_CSV_BYTES)?;
+
+        assert_eq!(2, menages.len());
+        assert_eq!(
+            Caracteristique::Textuel("\"HLM\"".into()),
+            menages[0].caracteristiques["Logement"]
+        );
+
+        let options = OptionsCsv {
+            noms_colonnes: Some(vec!["Age".into()]),
+            ..options
+        };
+        let result = CsvInputAdapter::new()
+            .with_options(options)
+            .populate_from_buf(SANS_ENTETE_CSV_BYTES);
+        let wanted = true;
+        assert_eq!(wanted, result.is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn ok_csv_booleens_et_dates() -> KalkotoResult<()> {
+        static CSV_BYTES: &[u8] =
+            "Locataire;Naissance;Entree\noui;14/07/1950;2024-01-15\nnon;01/02/1948;\n".as_bytes();
+
+        let options = OptionsCsv {
+            vrai: vec!["oui".into()],
+            faux: vec!["non".into()],
+            formats_dates: vec!["%d/%m/%Y".into(), "%Y-%m-%d".into()],
+            ..OptionsCsv::new()
+        };
+        let (_, menages) = CsvInputAdapter::new()
+            .with_options(options)
+            .populate_from_buf(CSV_BYTES)?;
+
+        let date = |annee, mois, jour| NaiveDate::from_ymd_opt(annee, mois, jour).unwrap();
+        assert_eq!(
+            Caracteristique::Booleen(false),
+            menages[1].caracteristiques["Locataire"]
+        );
+        assert_eq!(
+            Caracteristique::Date(date(1950, 7, 14)),
+            menages[0].caracteristiques["Naissance"]
+        );
+        assert_eq!(
+            Caracteristique::Date(date(2024, 1, 15)),
+            menages[0].caracteristiques["Entree"]
+        );
+        assert_eq!(
+            Caracteristique::Manquant,
+            menages[1].caracteristiques["Entree"]
+        );
+
+        // Sans ces écritures, "oui" reste un texte, refusé par un schéma booléen
+        let result = CsvInputAdapter::new()
+            .with_schema(SchemaMenages::populate_from_memory(
+                "[[caracteristique]]\nname = \"Locataire\"\ntype = \"booleen\"\n".as_bytes(),
+            )?)
+            .populate_from_memory(CSV_BYTES)?
+            .create_valid_menage_input(MenageInputBuilder::new());
+
+        let wanted = true;
+        let result = matches!(
+            result,
+            Err(KalkotoError::ListMenageError(
+                MenageListAdapterError::Schema { menage: 1, .. }
+            ))
+        );
+        assert_eq!(wanted, result);
+
+        Ok(())
+    }
+
+    #[test]
+    fn err_csv_identifiant_en_double() -> KalkotoResult<()> {
+        static CSV_BYTES: &[u8] = "Dossier;Age\nA1;80\nA2;70\nA1;60\n".as_bytes();
+
+        let result = CsvInputAdapter::new()
+            .with_colonne_identifiant("Dossier")
+            .populate_from_memory(CSV_BYTES)?
+            .create_valid_menage_input(MenageInputBuilder::new());
+
+        let wanted = true;
+        let result = matches!(
+            result,
+            Err(KalkotoError::ListMenageError(
+                MenageListAdapterError::Validation { fault_index: 3, .. }
+            ))
+        );
+        assert_eq!(wanted, result);
+
+        Ok(())
+    }
+
+    #[test]
+    fn ok_csv_projection_colonnes() -> KalkotoResult<()> {
+        static CSV_BYTES: &[u8] =
+            "Dossier;Age;GIR;Commentaire\nA1;80;1;texte libre\nA2;70;2;\n".as_bytes();
+        let colonnes: HashSet<String> = ["Age", "GIR"].map(String::from).into();
+
+        let menage_input = CsvInputAdapter::new()
+            .with_colonne_identifiant("Dossier")
+            .with_colonnes(colonnes.clone())
+            .populate_from_memory(CSV_BYTES)?
+            .create_valid_menage_input(MenageInputBuilder::new())?;
+
+        assert_eq!(colonnes, menage_input.set_caracteristiques_valide);
+        assert_eq!(
+            Some("A2".to_string()),
+            menage_input.liste_menage_valide[1].identifiant
+        );
+
+        let result = CsvInputAdapter::new()
+            .with_colonnes(["Age", "Revenu"].map(String::from).into())
+            .populate_from_memory(CSV_BYTES)?
+            .create_valid_menage_input(MenageInputBuilder::new());
+
+        let wanted = true;
+        let result = matches!(
+            result,
+            Err(KalkotoError::ListMenageError(MenageListAdapterError::Validation { ref cause, .. }))
+                if cause.contains("Revenu")
+        );
+        assert_eq!(wanted, result);
+
+        Ok(())
+    }
+
+    #[test]
+    fn ok_csv_filtre_menages() -> KalkotoResult<()> {
+        static CSV_BYTES: &[u8] = "Age;GIR\n80;1\n45;2\n70;5\n65;3\n".as_bytes();
+        let filtre = Filtre::try_from("Age >= 60 and GIR <= 4".to_string())
+            .map_err(MenageListAdapterError::FileFormat)?;
+
+        let menage_input = CsvInputAdapter::new()
+            .populate_from_memory(CSV_BYTES)?
+            .create_valid_menage_input(MenageInputBuilder::new().with_filtre(Some(filtre)))?;
+
+        let index: Vec<i32> = menage_input
+            .liste_menage_valide
+            .iter()
+            .map(|menage| menage.index)
+            .collect();
+        assert_eq!(vec![1, 4], index);
+        assert_eq!(2, menage_input.menages_ecartes);
+
+        let result = CsvInputAdapter::new()
+            .populate_from_memory(CSV_BYTES)?
+            .create_valid_menage_input(
+                MenageInputBuilder::new().with_filtre(Some(
+                    Filtre::try_from("Age > 100".to_string())
+                        .map_err(MenageListAdapterError::FileFormat)?,
+                )),
+            );
+
+        let wanted = true;
+        let result = matches!(
+            result,
+            Err(KalkotoError::ListMenageError(MenageListAdapterError::Validation { ref cause, .. }))
+                if cause.contains("Aucun ménage")
+        );
+        assert_eq!(wanted, result);
+
+        Ok(())
+    }
+
+    #[test]
+    fn ok_csv_colonne_poids() -> KalkotoResult<()> {
+        static CSV_BYTES: &[u8] = "Age;Poids\n80;150.5\n70;300\n".as_bytes();
+
+        let menage_input = CsvInputAdapter::new()
+            .populate_from_memory(CSV_BYTES)?
+            .create_valid_menage_input(
+                MenageInputBuilder::new().with_colonne_poids(Some("Poids".into())),
+            )?;
+
+        assert_eq!(
+            HashSet::from(["Age".to_string()]),
+            menage_input.set_caracteristiques_valide
+        );
+        assert_eq!(450.5, menage_input.effectif_pondere());
+        let resultats = [
+            HashMap::from([("apa".to_string(), 2.0)]),
+            HashMap::from([("apa".to_string(), 1.0)]),
+        ];
+        assert_eq!(601.0, menage_input.totaux_ponderes(&resultats)["apa"]);
+
+        for csv in [
+            "Age;Poids\n80;0\n",
+            "Age;Poids\n80;-2.5\n",
+            "Age;Poids\n80;NA\n",
+        ] {
+            let result = CsvInputAdapter::new()
+                .populate_from_memory(csv.as_bytes())?
+                .create_valid_menage_input(
+                    MenageInputBuilder::new().with_colonne_poids(Some("Poids".into())),
+                );
+
+            let wanted = true;
+            let result = matches!(
+                result,
+                Err(KalkotoError::ListMenageError(
+                    MenageListAdapterError::Validation { fault_index: 1, .. }
+                ))
+            );
+            assert_eq!(wanted, result, "{}", csv);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn ok_csv_individus_rattaches() -> KalkotoResult<()> {
+        static CSV_BYTES: &[u8] = "Dossier;Revenu\nA1;1000\nA2;500\n".as_bytes();
+        static INDIVIDUS_BYTES: &[u8] = "Foyer;Age\nA2;40\nA1;80\nA2;12\n".as_bytes();
+
+        let mut menage_input = CsvInputAdapter::new()
+            .with_colonne_identifiant("Dossier")
+            .populate_from_memory(CSV_BYTES)?
+            .create_valid_menage_input(MenageInputBuilder::new())?;
+        let individus = CsvInputAdapter::new()
+            .with_colonne_identifiant("Foyer")
+            .populate_from_memory(INDIVIDUS_BYTES)?
+            .create_valid_menage_input(MenageInputBuilder::new().table_individus())?;
+        menage_input.rattache_individus(individus)?;
+
+        let index: Vec<Vec<i32>> = menage_input
+            .liste_menage_valide
+            .iter()
+            .map(|menage| {
+                menage
+                    .individus
+                    .iter()
+                    .map(|individu| individu.index)
+                    .collect()
+            })
+            .collect();
+        assert_eq!(vec![vec![2], vec![1, 3]], index);
+        assert_eq!(
+            HashSet::from(["Age".to_string()]),
+            menage_input.set_caracteristiques_individus
+        );
+
+        let individus = CsvInputAdapter::new()
+            .with_colonne_identifiant("Foyer")
+            .populate_from_memory("Foyer;Age\nA3;30\n".as_bytes())?
+            .create_valid_menage_input(MenageInputBuilder::new().table_individus())?;
+        let result = menage_input.rattache_individus(individus);
+
+        let wanted = true;
+        let result = matches!(
+            result,
+            Err(KalkotoError::ListMenageError(MenageListAdapterError::Validation { ref cause, .. }))
+                if cause.contains("A3")
+        );
+        assert_eq!(wanted, result);
+
+        Ok(())
+    }
+
+    #[test]
+    fn ok_csv_individus_menages_filtres() -> KalkotoResult<()> {
+        static CSV_BYTES: &[u8] = "Dossier;Revenu\nA1;1000\nA2;500\n".as_bytes();
+        static INDIVIDUS_BYTES: &[u8] = "Foyer;Age\nA2;40\nA1;80\n".as_bytes();
+        let filtre = Filtre::try_from("Revenu > 600".to_string())
+            .map_err(MenageListAdapterError::FileFormat)?;
+
+        let mut menage_input = CsvInputAdapter::new()
+            .with_colonne_identifiant("Dossier")
+            .populate_from_memory(CSV_BYTES)?
+            .create_valid_menage_input(MenageInputBuilder::new().with_filtre(Some(filtre)))?;
+        let individus = CsvInputAdapter::new()
+            .with_colonne_identifiant("Foyer")
+            .populate_from_memory(INDIVIDUS_BYTES)?
+            .create_valid_menage_input(MenageInputBuilder::new().table_individus())?;
+        menage_input.rattache_individus(individus)?;
+
+        assert_eq!(1, menage_input.liste_menage_valide.len());
+        assert_eq!(2, menage_input.liste_menage_valide[0].individus[0].index);
+
+        let individus = CsvInputAdapter::new()
+            .with_colonne_identifiant("Foyer")
+            .populate_from_memory("Foyer;Age\nA3;30\n".as_bytes())?
+            .create_valid_menage_input(MenageInputBuilder::new().table_individus())?;
+        let result = menage_input.rattache_individus(individus);
+
+        let wanted = true;
+        let result = matches!(
+            result,
+            Err(KalkotoError::ListMenageError(MenageListAdapterError::Validation { ref cause, .. }))
+                if cause.contains("A3")
+        );
+        assert_eq!(wanted, result);
+
+        Ok(())
+    }
+
+    static SCHEMA_TOML: &str = r#"
+[[caracteristique]]
+name = "CodePostal"
+type = "textuel"
+
+[[caracteristique]]
+name = "Revenu"
+type = "numeric"
+min = 0
+"#;
+
+    #[test]
+    fn ok_csv_schema_types_declares() -> KalkotoResult<()> {
+        static CSV_BYTES: &[u8] = "CodePostal;Revenu;Age\n01500;3;35\n75001;3.0;40\n".as_bytes();
+
+        let menage_input = CsvInputAdapter::new()
+            .with_schema(SchemaMenages::populate_from_memory(SCHEMA_TOML.as_bytes())?)
+            .populate_from_memory(CSV_BYTES)?
+            .create_valid_menage_input(MenageInputBuilder::new())?;
+
+        let menages = menage_input.liste_menage_valide;
+        assert_eq!(
+            Caracteristique::Textuel("01500".into()),
+            menages[0].caracteristiques["CodePostal"]
+        );
+        assert_eq!(
+            Caracteristique::Numeric(3.0),
+            menages[0].caracteristiques["Revenu"]
+        );
+        assert_eq!(
+            Caracteristique::Entier(40),
+            menages[1].caracteristiques["Age"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn ok_csv_schema_declare_colonne_identifiant() -> KalkotoResult<()> {
+        static SCHEMA_TOML: &str = r#"
+[[caracteristique]]
+name = "Dossier"
+type = "textuel"
+
+[[caracteristique]]
+name = "Age"
+type = "entier"
+"#;
+        static CSV_BYTES: &[u8] = "Dossier;Age\n00B;80\n012;70\n".as_bytes();
+
+        let menage_input = CsvInputAdapter::new()
+            .with_schema(SchemaMenages::populate_from_memory(SCHEMA_TOML.as_bytes())?)
+            .with_colonne_identifiant("Dossier")
+            .populate_from_memory(CSV_BYTES)?
+            .create_valid_menage_input(MenageInputBuilder::new())?;
+
+        let wanted: HashSet<String> = ["Age".to_string()].into();
+        assert_eq!(wanted, menage_input.set_caracteristiques_valide);
+        assert_eq!(
+            vec!["00B", "012"],
+            menage_input
+                .liste_menage_valide
+                .iter()
+                .map(Menage::cle)
+                .collect::<Vec<_>>()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn err_csv_schema_valeur_invalide() -> KalkotoResult<()> {
+        static CSV_BYTES: &[u8] = "CodePostal;Revenu\n01500;1000\n75001;-5\n".as_bytes();
+
+        let result = CsvInputAdapter::new()
+            .with_schema(SchemaMenages::populate_from_memory(SCHEMA_TOML.as_bytes())?)
+            .populate_from_memory(CSV_BYTES)?
+            .create_valid_menage_input(MenageInputBuilder::new());
+
+        let wanted = true;
+        let result = matches!(
+            result,
+            Err(KalkotoError::ListMenageError(
+                MenageListAdapterError::Schema { ref colonne, menage: 2, .. }
+            )) if colonne == "Revenu"
+        );
+        assert_eq!(wanted, result);
+
+        let result = CsvInputAdapter::new()
+            .with_schema(SchemaMenages::populate_from_memory(SCHEMA_TOML.as_bytes())?)
+            .populate_from_memory("CodePostal;Revenu\n01500;mille\n".as_bytes());
+        let wanted = true;
+        let result = matches!(
+            result,
+            Err(KalkotoError::ListMenageError(
+                MenageListAdapterError::Schema { ref attendu, menage: 1, .. }
+            )) if attendu == "numeric supérieur ou égal à 0"
+        );
+        assert_eq!(wanted, result);
+
+        Ok(())
+    }
 }
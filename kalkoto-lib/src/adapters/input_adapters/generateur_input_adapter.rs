@@ -0,0 +1,427 @@
+use crate::adapters::input_adapters::{MenageListAdapterError, MenageListCreator};
+use crate::entities::menage::{Caracteristique, Menage};
+use crate::entities::menage_input::{EmptyList, MenageInput, MenageInputBuilder};
+use crate::entities::schema_menages::{SchemaMenages, ValeurToml};
+use crate::{KalkotoError, KalkotoResult};
+use itertools::Itertools;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashSet};
+use std::ffi::OsStr;
+use std::path::Path;
+
+// Générateur de cas-types, décrit dans un fichier TOML :
+//
+// mode = "produit"          # ou "parallele"
+//
+// [[caracteristique]]
+// name = "Age"
+// valeurs = [60, 70, 80]
+//
+// [[caracteristique]]
+// name = "Revenu"
+// debut = 0
+// fin = 3000                # inclus
+// pas = 100
+//
+// [fixes]
+// Commune = "Paris"
+//
+// En mode produit, les ménages sont toutes les combinaisons des valeurs (la première
+// caractéristique varie le moins vite). En mode parallèle, le i-ème ménage prend la i-ème
+// valeur de chaque caractéristique, qui doivent donc en avoir autant. Les caractéristiques
+// fixes sont les mêmes pour tous les ménages
+#[derive(Default, Debug)]
+pub struct GenerateurInputAdapter {
+    liste_menages: Option<Vec<Menage>>,
+    schema: Option<SchemaMenages>,
+    colonne_identifiant: Option<String>,
+    colonnes: Option<HashSet<String>>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum ModeGenerateur {
+    #[default]
+    Produit,
+    Parallele,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct SpecGenerateur {
+    #[serde(default)]
+    mode: ModeGenerateur,
+    #[serde(rename = "caracteristique", default)]
+    caracteristiques: Vec<SpecCaracteristique>,
+    #[serde(default)]
+    fixes: BTreeMap<String, ValeurToml>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct SpecCaracteristique {
+    name: String,
+    valeurs: Option<Vec<ValeurToml>>,
+    debut: Option<ValeurToml>,
+    fin: Option<ValeurToml>,
+    pas: Option<ValeurToml>,
+}
+
+// Garde-fou contre une grille mal déclarée qui épuiserait la mémoire
+const NOMBRE_MAXIMAL_MENAGES: usize = 10_000_000;
+
+impl SpecCaracteristique {
+    fn valeurs(self) -> Result<Vec<Caracteristique>, String> {
+        let valeurs = match (self.valeurs, self.debut, self.fin) {
+            (Some(valeurs), None, None) if self.pas.is_none() => valeurs
+                .into_iter()
+                .map(Caracteristique::try_from)
+                .collect::<Result<Vec<_>, _>>()?,
+            (None, Some(debut), Some(fin)) => {
+                intervalle(debut, fin, self.pas.unwrap_or(ValeurToml::Entier(1)))?
+            }
+            _ => {
+                return Err(format!(
+                    "{} doit être décrite par une liste de valeurs ou par debut, fin (et pas)",
+                    self.name
+                ))
+            }
+        };
+
+        match valeurs.is_empty() {
+            true => Err(format!("{} n'a aucune valeur", self.name)),
+            false => Ok(valeurs),
+        }
+    }
+}
+
+// Valeurs de debut à fin incluse : des entiers si les trois bornes le sont, des réels sinon.
+// Chaque valeur est calculée depuis le début pour ne pas cumuler les erreurs d'arrondi
+fn intervalle(
+    debut: ValeurToml,
+    fin: ValeurToml,
+    pas: ValeurToml,
+) -> Result<Vec<Caracteristique>, String> {
+    let nombre = |valeur: &ValeurToml| match valeur {
+        ValeurToml::Entier(entier) => Ok(*entier as f64),
+        ValeurToml::Numeric(numeric) => Ok(*numeric),
+        ValeurToml::Textuel(texte) => Err(format!("{texte} n'est pas un nombre")),
+        ValeurToml::Booleen(booleen) => Err(format!("{booleen} n'est pas un nombre")),
+    };
+    let entiers = [&debut, &fin, &pas]
+        .iter()
+        .all(|valeur| matches!(valeur, ValeurToml::Entier(_)));
+    let (debut, fin, pas) = (nombre(&debut)?, nombre(&fin)?, nombre(&pas)?);
+
+    if let Some((borne, valeur)) = [("debut", debut), ("fin", fin), ("pas", pas)]
+        .into_iter()
+        .find(|(_, valeur)| !valeur.is_finite())
+    {
+        return Err(format!("{borne} = {valeur} n'est pas un nombre fini"));
+    }
+
+    if pas <= 0.0 || fin < debut {
+        return Err(format!(
+            "l'intervalle de {debut} à {fin} par pas de {pas} est vide"
+        ));
+    }
+
+    // Comparaison en f64 avant la conversion, qui saturerait pour un intervalle trop grand
+    let nombre_valeurs = ((fin - debut) / pas + 1e-9).floor() + 1.0;
+    if nombre_valeurs.is_nan() || nombre_valeurs > NOMBRE_MAXIMAL_MENAGES as f64 {
+        return Err(format!(
+            "l'intervalle de {debut} à {fin} par pas de {pas} a trop de valeurs"
+        ));
+    }
+    let nombre_valeurs = nombre_valeurs as usize;
+
+    (0..nombre_valeurs)
+        .map(|i| debut + i as f64 * pas)
+        .map(|valeur| match entiers {
+            true => Caracteristique::try_from(ValeurToml::Entier(valeur as i64)),
+            false => Ok(Caracteristique::Numeric(valeur)),
+        })
+        .collect()
+}
+
+impl GenerateurInputAdapter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Les colonnes déclarées dans le schéma sont converties dans leur type
+    pub fn with_schema(self, schema: SchemaMenages) -> Self {
+        Self {
+            schema: Some(schema),
+            ..self
+        }
+    }
+
+    // Caractéristique générée dont les valeurs, uniques, identifient les cas-types dans les
+    // résultats (une liste de noms en mode parallèle par exemple)
+    pub fn with_colonne_identifiant(self, colonne_identifiant: &str) -> Self {
+        Self {
+            colonne_identifiant: Some(colonne_identifiant.to_string()),
+            ..self
+        }
+    }
+
+    // Seules ces colonnes (et la colonne identifiant) sont lues : celles dont dépendent les
+    // politiques et celles reprises dans les résultats
+    pub fn with_colonnes(self, colonnes: HashSet<String>) -> Self {
+        Self {
+            colonnes: Some(colonnes),
+            ..self
+        }
+    }
+
+    pub fn populate_from_memory(self, buf: &[u8]) -> KalkotoResult<Self> {
+        let erreur = |cause: String| {
+            MenageListAdapterError::FileFormat(format!(
+                "Spécification du générateur de cas-types invalide : {cause}"
+            ))
+        };
+
+        let s = std::str::from_utf8(buf).map_err(|e| erreur(e.to_string()))?;
+        let spec: SpecGenerateur = toml::from_str(s).map_err(|e| erreur(e.to_string()))?;
+
+        Ok(Self {
+            liste_menages: Some(genere_menages(spec).map_err(erreur)?),
+            ..self
+        })
+    }
+
+    pub fn populate_from_path<P>(self, path: P) -> KalkotoResult<Self>
+    where
+        P: AsRef<Path>,
+    {
+        match path.as_ref().extension().and_then(OsStr::to_str) {
+            Some("toml") => (),
+            _ => {
+                return Err(From::from(MenageListAdapterError::FileFormat(
+                    "La spécification du générateur de cas-types n'est pas un fichier TOML".into(),
+                )))
+            }
+        }
+
+        let buf = std::fs::read(path).map_err(MenageListAdapterError::IO)?;
+        self.populate_from_memory(&buf)
+    }
+}
+
+fn genere_menages(spec: SpecGenerateur) -> Result<Vec<Menage>, String> {
+    let fixes: Vec<(String, Caracteristique)> = spec
+        .fixes
+        .into_iter()
+        .map(|(nom, valeur)| Ok((nom, Caracteristique::try_from(valeur)?)))
+        .collect::<Result<_, String>>()?;
+
+    let grille: Vec<(String, Vec<Caracteristique>)> = spec
+        .caracteristiques
+        .into_iter()
+        .map(|caracteristique| Ok((caracteristique.name.clone(), caracteristique.valeurs()?)))
+        .collect::<Result<_, String>>()?;
+
+    if let Some(nom) = grille.iter().map(|(nom, _)| nom).duplicates().next() {
+        return Err(format!(
+            "la caractéristique {nom} est déclarée plusieurs fois"
+        ));
+    }
+
+    if let Some((nom, _)) = grille
+        .iter()
+        .find(|(nom, _)| fixes.iter().any(|(fixe, _)| fixe == nom))
+    {
+        return Err(format!("{nom} est à la fois fixe et variable"));
+    }
+
+    let nombre_menages = match spec.mode {
+        ModeGenerateur::Produit => grille
+            .iter()
+            .try_fold(1usize, |nombre, (_, valeurs)| {
+                nombre.checked_mul(valeurs.len())
+            })
+            .filter(|nombre| *nombre <= NOMBRE_MAXIMAL_MENAGES)
+            .ok_or_else(|| format!("la grille dépasse {NOMBRE_MAXIMAL_MENAGES} ménages"))?,
+        ModeGenerateur::Parallele => {
+            let nombre = grille.first().map_or(1, |(_, valeurs)| valeurs.len());
+            if let Some((nom, valeurs)) = grille.iter().find(|(_, valeurs)| valeurs.len() != nombre)
+            {
+                return Err(format!(
+                    "en mode parallèle, {nom} a {} valeurs au lieu de {nombre}",
+                    valeurs.len()
+                ));
+            }
+            nombre
+        }
+    };
+
+    Ok((0..nombre_menages)
+        .map(|position| {
+            let mut menage = Menage::new(position as i32 + 1);
+            menage.caracteristiques.extend(fixes.iter().cloned());
+
+            // En mode produit, la position du ménage s'écrit en base mixte, un chiffre par
+            // caractéristique, la dernière variant le plus vite
+            let mut reste = position;
+            for (nom, valeurs) in grille.iter().rev() {
+                let rang = match spec.mode {
+                    ModeGenerateur::Produit => {
+                        let rang = reste % valeurs.len();
+                        reste /= valeurs.len();
+                        rang
+                    }
+                    ModeGenerateur::Parallele => position,
+                };
+                menage
+                    .caracteristiques
+                    .insert(nom.clone(), valeurs[rang].clone());
+            }
+            menage
+        })
+        .collect())
+}
+
+impl MenageListCreator for GenerateurInputAdapter {
+    fn create_valid_menage_input(
+        self,
+        empty_menage_input: MenageInputBuilder<EmptyList>,
+    ) -> KalkotoResult<MenageInput> {
+        match self.liste_menages {
+            Some(liste_menages) => empty_menage_input
+                .with_schema(self.schema)
+                .with_colonne_identifiant(self.colonne_identifiant)
+                .with_colonnes(self.colonnes)
+                .from_unvalidated_liste_menage(&liste_menages)
+                .validate_liste_menage()?
+                .build_valide_menage_input(),
+            None => Err(KalkotoError::ListMenageError(
+                MenageListAdapterError::Uninitialized,
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static SPEC_TOML: &str = r#"
+[[caracteristique]]
+name = "Age"
+valeurs = [60, 70, 80]
+
+[[caracteristique]]
+name = "GIR"
+debut = 1
+fin = 4
+
+[[caracteristique]]
+name = "Revenu"
+debut = 0
+fin = 0.3
+pas = 0.1
+
+[fixes]
+Commune = "Paris"
+"#;
+
+    #[test]
+    fn ok_generateur_produit_cartesien() -> KalkotoResult<()> {
+        let menage_input = GenerateurInputAdapter::new()
+            .populate_from_memory(SPEC_TOML.as_bytes())?
+            .create_valid_menage_input(MenageInputBuilder::new())?;
+
+        let menages = menage_input.liste_menage_valide;
+        assert_eq!(3 * 4 * 4, menages.len());
+
+        let dernier = &menages[menages.len() - 1];
+        assert_eq!(Caracteristique::Entier(80), dernier.caracteristiques["Age"]);
+        assert_eq!(Caracteristique::Entier(4), dernier.caracteristiques["GIR"]);
+        assert_eq!(
+            Caracteristique::Textuel("Paris".into()),
+            dernier.caracteristiques["Commune"]
+        );
+
+        let revenus: Vec<Caracteristique> = menages[..4]
+            .iter()
+            .map(|menage| menage.caracteristiques["Revenu"].clone())
+            .collect();
+        let wanted: Vec<Caracteristique> = [0.0, 0.1, 0.2, 0.30000000000000004]
+            .map(Caracteristique::Numeric)
+            .to_vec();
+        assert_eq!(wanted, revenus);
+        assert_eq!(
+            Caracteristique::Entier(2),
+            menages[4].caracteristiques["GIR"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn err_generateur_bornes_non_finies() -> KalkotoResult<()> {
+        for (bornes, borne) in [
+            ("debut = 0\nfin = inf", "fin = inf"),
+            ("debut = -inf\nfin = 10", "debut = -inf"),
+            ("debut = 0\nfin = 10\npas = nan", "pas = NaN"),
+        ] {
+            let spec = format!("[[caracteristique]]\nname = \"Revenu\"\n{bornes}\n");
+
+            let result = GenerateurInputAdapter::new().populate_from_memory(spec.as_bytes());
+
+            let wanted = true;
+            let result = matches!(
+                result,
+                Err(KalkotoError::ListMenageError(MenageListAdapterError::FileFormat(ref message)))
+                    if message.contains(&format!("{borne} n'est pas un nombre fini"))
+            );
+            assert_eq!(wanted, result);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn err_generateur_caracteristique_dupliquee() -> KalkotoResult<()> {
+        static SPEC_DUPLIQUEE_TOML: &str = r#"
+[[caracteristique]]
+name = "Age"
+valeurs = [60, 70]
+
+[[caracteristique]]
+name = "Age"
+valeurs = [80]
+"#;
+
+        let result =
+            GenerateurInputAdapter::new().populate_from_memory(SPEC_DUPLIQUEE_TOML.as_bytes());
+
+        let wanted = true;
+        let result = matches!(
+            result,
+            Err(KalkotoError::ListMenageError(MenageListAdapterError::FileFormat(ref message)))
+                if message.contains("Age est déclarée plusieurs fois")
+        );
+        assert_eq!(wanted, result);
+
+        Ok(())
+    }
+
+    #[test]
+    fn err_generateur_parallele_longueurs() -> KalkotoResult<()> {
+        let spec = format!("mode = \"parallele\"\n{SPEC_TOML}");
+
+        let result = GenerateurInputAdapter::new().populate_from_memory(spec.as_bytes());
+
+        let wanted = true;
+        let result = matches!(
+            result,
+            Err(KalkotoError::ListMenageError(MenageListAdapterError::FileFormat(ref message)))
+                if message.contains("GIR a 4 valeurs au lieu de 3")
+        );
+        assert_eq!(wanted, result);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,308 @@
+use crate::adapters::input_adapters::{colonne_lue, MenageListAdapterError, MenageListCreator};
+use crate::entities::menage::{Caracteristique, Menage};
+use crate::entities::menage_input::{EmptyList, MenageInput, MenageInputBuilder};
+use crate::entities::schema_menages::SchemaMenages;
+use crate::{KalkotoError, KalkotoResult};
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+use std::ffi::OsStr;
+use std::io::BufRead;
+use std::path::Path;
+
+// Ménages au format JSON-lines : un objet JSON par ligne, dont les clés sont les
+// caractéristiques. Les lignes vides sont ignorées
+#[derive(Default, Debug)]
+pub struct JsonLinesInputAdapter {
+    liste_menages: Option<Vec<Menage>>,
+    schema: Option<SchemaMenages>,
+    colonne_identifiant: Option<String>,
+    colonnes: Option<HashSet<String>>,
+}
+
+impl JsonLinesInputAdapter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Les colonnes déclarées dans le schéma sont converties dans leur type
+    pub fn with_schema(self, schema: SchemaMenages) -> Self {
+        Self {
+            schema: Some(schema),
+            ..self
+        }
+    }
+
+    // Colonne dont les valeurs, uniques, identifient les ménages dans les résultats.
+    // Elle ne fait pas partie des caractéristiques
+    pub fn with_colonne_identifiant(self, colonne_identifiant: &str) -> Self {
+        Self {
+            colonne_identifiant: Some(colonne_identifiant.to_string()),
+            ..self
+        }
+    }
+
+    // Seules ces colonnes (et la colonne identifiant) sont lues : celles dont dépendent les
+    // politiques et celles reprises dans les résultats
+    pub fn with_colonnes(self, colonnes: HashSet<String>) -> Self {
+        Self {
+            colonnes: Some(colonnes),
+            ..self
+        }
+    }
+
+    pub fn populate_from_path<P>(self, path: P) -> KalkotoResult<Self>
+    where
+        P: AsRef<Path>,
+    {
+        match path.as_ref().extension().and_then(OsStr::to_str) {
+            Some("jsonl" | "ndjson") => (),
+            _ => {
+                return Err(From::from(MenageListAdapterError::FileFormat(
+                    "Le fichier indiqué n'est pas un fichier JSON-lines".into(),
+                )))
+            }
+        }
+
+        let file = std::fs::File::open(path).map_err(MenageListAdapterError::IO)?;
+        self.populate_from_reader(std::io::BufReader::new(file))
+    }
+
+    // Lecture de lignes JSON déjà chargées en mémoire (application web notamment)
+    pub fn populate_from_memory(self, buf: &[u8]) -> KalkotoResult<Self> {
+        self.populate_from_reader(buf)
+    }
+
+    // Lecture d'un flux JSON-lines (sortie d'un autre programme, entrée standard...)
+    pub fn populate_from_reader<R: BufRead>(self, input: R) -> KalkotoResult<Self> {
+        let mut objets = vec![];
+
+        for (numero, ligne) in input.lines().enumerate() {
+            let ligne = ligne.map_err(MenageListAdapterError::IO)?;
+            if ligne.trim().is_empty() {
+                continue;
+            }
+
+            match serde_json::from_str::<Value>(&ligne) {
+                Ok(Value::Object(mut objet)) => {
+                    objet.retain(|nom, _| {
+                        colonne_lue(&self.colonnes, &self.colonne_identifiant, nom)
+                    });
+                    objets.push((numero + 1, objet))
+                }
+                Ok(_) => {
+                    return Err(From::from(MenageListAdapterError::FileFormat(format!(
+                        "La ligne {} n'est pas un objet JSON",
+                        numero + 1
+                    ))))
+                }
+                Err(e) => {
+                    return Err(From::from(MenageListAdapterError::FileFormat(format!(
+                        "La ligne {} n'est pas du JSON valide : {e}",
+                        numero + 1
+                    ))))
+                }
+            }
+        }
+
+        Ok(Self {
+            liste_menages: Some(menages_from_objets(
+                objets,
+                self.colonne_identifiant.as_deref(),
+            )?),
+            ..self
+        })
+    }
+}
+
+// Une clé absente d'un objet est une valeur manquante du ménage, traitée à la validation
+fn menages_from_objets(
+    objets: Vec<(usize, Map<String, Value>)>,
+    colonne_identifiant: Option<&str>,
+) -> KalkotoResult<Vec<Menage>> {
+    let mut noms_colonnes: Vec<&String> = vec![];
+    for (_, objet) in &objets {
+        for nom in objet.keys() {
+            if !noms_colonnes.contains(&nom) {
+                noms_colonnes.push(nom);
+            }
+        }
+    }
+
+    let mut liste_menages: Vec<Menage> = (1..=objets.len())
+        .map(|index| Menage::new(index as i32))
+        .collect();
+
+    for nom in noms_colonnes {
+        let conversion = match colonne_identifiant == Some(nom.as_str()) {
+            true => identifiant_from_json,
+            false => caracteristique_from_json,
+        };
+        let valeurs = objets
+            .iter()
+            .map(|(ligne, objet)| {
+                conversion(objet.get(nom).unwrap_or(&Value::Null)).map_err(|cause| {
+                    MenageListAdapterError::Conversion {
+                        colonne: nom.clone(),
+                        ligne: *ligne,
+                        cause,
+                    }
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for (menage, valeur) in liste_menages.iter_mut().zip(reels_si_melange(valeurs)) {
+            menage.caracteristiques.insert(nom.clone(), valeur);
+        }
+    }
+
+    Ok(liste_menages)
+}
+
+fn caracteristique_from_json(valeur: &Value) -> Result<Caracteristique, String> {
+    match valeur {
+        Value::Null => Ok(Caracteristique::Manquant),
+        Value::Bool(booleen) => Ok(Caracteristique::Booleen(*booleen)),
+        Value::Number(nombre) => match (nombre.as_i64(), nombre.as_f64()) {
+            (Some(entier), _) => i32::try_from(entier)
+                .map(Caracteristique::Entier)
+                .map_err(|_| format!("{entier} dépasse la capacité d'un entier 32 bits")),
+            (None, Some(reel)) => Ok(Caracteristique::Numeric(reel)),
+            (None, None) => Err(format!("{nombre} n'est pas un nombre représentable")),
+        },
+        Value::String(texte) => Ok(Caracteristique::Textuel(texte.clone())),
+        Value::Array(_) | Value::Object(_) => {
+            Err("une liste ou un objet JSON n'est pas une caractéristique".into())
+        }
+    }
+}
+
+// Les identifiants numériques sont gardés en texte, sans passer par les entiers 32 bits
+fn identifiant_from_json(valeur: &Value) -> Result<Caracteristique, String> {
+    match valeur {
+        Value::Null => Ok(Caracteristique::Manquant),
+        Value::Number(nombre) => Ok(Caracteristique::Textuel(nombre.to_string())),
+        Value::String(texte) => Ok(Caracteristique::Textuel(texte.clone())),
+        Value::Bool(_) | Value::Array(_) | Value::Object(_) => {
+            Err("un identifiant doit être un nombre ou un texte".into())
+        }
+    }
+}
+
+// JSON ne distingue pas 1000 de 1000.0 selon la façon dont il est produit : une colonne
+// mêlant entiers et réels est entièrement lue en réels
+fn reels_si_melange(valeurs: Vec<Caracteristique>) -> Vec<Caracteristique> {
+    let melange = valeurs
+        .iter()
+        .any(|valeur| matches!(valeur, Caracteristique::Numeric(_)))
+        && valeurs
+            .iter()
+            .any(|valeur| matches!(valeur, Caracteristique::Entier(_)));
+
+    match melange {
+        true => valeurs
+            .into_iter()
+            .map(|valeur| match valeur {
+                Caracteristique::Entier(entier) => Caracteristique::Numeric(entier as f64),
+                valeur => valeur,
+            })
+            .collect(),
+        false => valeurs,
+    }
+}
+
+impl MenageListCreator for JsonLinesInputAdapter {
+    fn create_valid_menage_input(
+        self,
+        empty_menage_input: MenageInputBuilder<EmptyList>,
+    ) -> KalkotoResult<MenageInput> {
+        match self.liste_menages {
+            Some(liste_menages) => empty_menage_input
+                .with_schema(self.schema)
+                .with_colonne_identifiant(self.colonne_identifiant)
+                .with_colonnes(self.colonnes)
+                .from_unvalidated_liste_menage(&liste_menages)
+                .validate_liste_menage()?
+                .build_valide_menage_input(),
+            None => Err(KalkotoError::ListMenageError(
+                MenageListAdapterError::Uninitialized,
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entities::menage_input::{TraitementManquant, ValeursManquantes};
+
+    #[test]
+    fn ok_jsonl_cle_absente_et_reels() -> KalkotoResult<()> {
+        let input = r#"{"Age": 80, "GIR": 1, "Revenu": 1000}
+
+{"Age": 70, "Revenu": 2000.5, "Commune": "Paris"}
+"#;
+
+        let menage_input = JsonLinesInputAdapter::new()
+            .populate_from_memory(input.as_bytes())?
+            .create_valid_menage_input(MenageInputBuilder::new().with_valeurs_manquantes(
+                ValeursManquantes::pour_toutes(TraitementManquant::Transmettre),
+            ))?;
+
+        let menages = menage_input.liste_menage_valide;
+        assert_eq!(2, menages.len());
+        assert_eq!(
+            Caracteristique::Numeric(1000.0),
+            menages[0].caracteristiques["Revenu"]
+        );
+        assert_eq!(
+            Caracteristique::Manquant,
+            menages[1].caracteristiques["GIR"]
+        );
+        assert_eq!(
+            Caracteristique::Manquant,
+            menages[0].caracteristiques["Commune"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn ok_jsonl_identifiant_au_dela_de_32_bits() -> KalkotoResult<()> {
+        let input = r#"{"id": 2019000000001, "Age": 80}
+{"id": "B12", "Age": 70}
+"#;
+
+        let menage_input = JsonLinesInputAdapter::new()
+            .with_colonne_identifiant("id")
+            .populate_from_memory(input.as_bytes())?
+            .create_valid_menage_input(MenageInputBuilder::new())?;
+
+        assert_eq!(
+            vec!["2019000000001", "B12"],
+            menage_input
+                .liste_menage_valide
+                .iter()
+                .map(Menage::cle)
+                .collect::<Vec<_>>()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn err_jsonl_ligne_invalide() -> KalkotoResult<()> {
+        let input = "{\"Age\": 80}\n[80]\n";
+
+        let result = JsonLinesInputAdapter::new().populate_from_memory(input.as_bytes());
+
+        let wanted = true;
+        let result = matches!(
+            result,
+            Err(KalkotoError::ListMenageError(MenageListAdapterError::FileFormat(ref message)))
+                if message.contains("ligne 2")
+        );
+        assert_eq!(wanted, result);
+
+        Ok(())
+    }
+}
@@ -1,8 +1,15 @@
 use arrow::array::ArrowNumericType;
-use pyo3::exceptions::PySyntaxWarning;
 
 use crate::adapters::input_adapters::arrow_input_adapter::ArrowInputAdapter;
 use crate::adapters::input_adapters::csv_input_adapter::CsvInputAdapter;
+use crate::adapters::input_adapters::generateur_input_adapter::GenerateurInputAdapter;
+use crate::adapters::input_adapters::jsonl_input_adapter::JsonLinesInputAdapter;
+#[cfg(feature = "parquet")]
+use crate::adapters::input_adapters::parquet_input_adapter::ParquetInputAdapter;
+#[cfg(feature = "sqlite")]
+use crate::adapters::input_adapters::sqlite_input_adapter::SqliteInputAdapter;
+#[cfg(feature = "tableur")]
+use crate::adapters::input_adapters::tableur_input_adapter::TableurInputAdapter;
 use crate::entities::menage::*;
 use crate::entities::menage_input::*;
 use crate::entities::policy::*;
@@ -14,11 +21,27 @@ use std::{collections::HashSet, error::Error, fmt::Debug};
 
 pub mod arrow_input_adapter;
 pub mod csv_input_adapter;
+pub mod generateur_input_adapter;
+pub mod jsonl_input_adapter;
+#[cfg(feature = "parquet")]
+pub mod parquet_input_adapter;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_input_adapter;
+#[cfg(feature = "tableur")]
+pub mod tableur_input_adapter;
 pub mod toml_input_adapter;
 
 pub enum MenageAdapter {
     CSV(CsvInputAdapter),
     Arrow(ArrowInputAdapter),
+    Generateur(GenerateurInputAdapter),
+    JsonLines(JsonLinesInputAdapter),
+    #[cfg(feature = "parquet")]
+    Parquet(ParquetInputAdapter),
+    #[cfg(feature = "sqlite")]
+    Sqlite(SqliteInputAdapter),
+    #[cfg(feature = "tableur")]
+    Tableur(TableurInputAdapter),
 }
 
 #[derive(thiserror::Error)]
@@ -36,9 +59,39 @@ pub enum MenageListAdapterError {
         conseil: String,
     },
 
+    #[error("La colonne {colonne} ne peut pas être lue à la ligne {ligne} : {cause}")]
+    Conversion {
+        colonne: String,
+        ligne: usize,
+        cause: String,
+    },
+
+    #[error("Valeur manquante pour la caractéristique {colonne} du ménage {menage}.\nConseil : renseigner la valeur ou choisir un traitement des valeurs manquantes pour cette caractéristique")]
+    ValeurManquante { colonne: String, menage: i32 },
+
+    #[error("La caractéristique {colonne} du ménage {menage} ne respecte pas le schéma (attendu : {attendu}) : {cause}")]
+    Schema {
+        colonne: String,
+        menage: i32,
+        attendu: String,
+        cause: String,
+    },
+
     #[error("Erreur à la lecture du dataframe Arrow")]
     Arrow(#[from] arrow::error::ArrowError),
 
+    #[cfg(feature = "parquet")]
+    #[error("Erreur à la lecture du fichier Parquet")]
+    Parquet(#[from] parquet::errors::ParquetError),
+
+    #[cfg(feature = "sqlite")]
+    #[error("Erreur à la lecture de la base SQLite")]
+    Sqlite(#[from] rusqlite::Error),
+
+    #[cfg(feature = "tableur")]
+    #[error("Erreur à la lecture du classeur")]
+    Tableur(#[from] calamine::Error),
+
     #[error("Validation impossible : le fichier ménages n'a pas encore été initialisé !")]
     Uninitialized,
 }
@@ -53,6 +106,19 @@ impl Debug for MenageListAdapterError {
     }
 }
 
+// Une colonne est lue si aucune projection n'est demandée, si elle fait partie de la projection
+// ou si elle identifie les ménages
+pub(crate) fn colonne_lue(
+    colonnes: &Option<HashSet<String>>,
+    colonne_identifiant: &Option<String>,
+    nom: &str,
+) -> bool {
+    colonnes
+        .as_ref()
+        .is_none_or(|colonnes| colonnes.contains(nom))
+        || colonne_identifiant.as_deref() == Some(nom)
+}
+
 // Trait commun à tous les adapteurs de création d'une liste de ménages dont toutes
 // les caractéristiques ont été vérifiées
 pub trait MenageListCreator {
@@ -74,6 +140,24 @@ impl MenageListCreator for MenageAdapter {
             Self::Arrow(arrow_input_adapter) => {
                 arrow_input_adapter.create_valid_menage_input(empty_menage_input)
             }
+            Self::Generateur(generateur_input_adapter) => {
+                generateur_input_adapter.create_valid_menage_input(empty_menage_input)
+            }
+            Self::JsonLines(jsonl_input_adapter) => {
+                jsonl_input_adapter.create_valid_menage_input(empty_menage_input)
+            }
+            #[cfg(feature = "parquet")]
+            Self::Parquet(parquet_input_adapter) => {
+                parquet_input_adapter.create_valid_menage_input(empty_menage_input)
+            }
+            #[cfg(feature = "sqlite")]
+            Self::Sqlite(sqlite_input_adapter) => {
+                sqlite_input_adapter.create_valid_menage_input(empty_menage_input)
+            }
+            #[cfg(feature = "tableur")]
+            Self::Tableur(tableur_input_adapter) => {
+                tableur_input_adapter.create_valid_menage_input(empty_menage_input)
+            }
         }
     }
 }
@@ -98,6 +182,9 @@ pub enum PolicyAdapterError {
     #[error("Champ(s) manquant(s) ou invalide(s): {0}")]
     Generic(String),
 
+    #[error("Formule invalide pour la composante {composante} : {message}")]
+    Formula { composante: String, message: String },
+
     #[error("Problème à la création de l'input modélisé")]
     Trait,
 }
@@ -158,6 +245,12 @@ mod tests {
         let wanted = MenageInput {
             set_caracteristiques_valide: valide_caracteristiques,
             liste_menage_valide: valide_menage_list.clone(),
+            colonne_identifiant: None,
+            colonne_poids: None,
+            filtre: None,
+            menages_ecartes: 0,
+            cles_menages_ecartes: HashSet::new(),
+            set_caracteristiques_individus: HashSet::new(),
         };
 
         let result = MenageInputBuilder::<EmptyList>::new()
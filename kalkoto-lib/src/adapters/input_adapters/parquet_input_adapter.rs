@@ -0,0 +1,225 @@
+use crate::adapters::input_adapters::arrow_input_adapter::{
+    create_menage_input_from_columns, extract_columns_from_batches,
+};
+use crate::adapters::input_adapters::{colonne_lue, MenageListAdapterError, MenageListCreator};
+use crate::entities::menage::Caracteristique;
+use crate::entities::menage_input::{EmptyList, MenageInput, MenageInputBuilder};
+use crate::entities::schema_menages::SchemaMenages;
+use crate::{KalkotoError, KalkotoResult};
+use arrow::array::RecordBatchReader;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::ProjectionMask;
+use std::collections::HashSet;
+use std::ffi::OsStr;
+use std::{fs::File, path::Path};
+
+#[derive(Default, Debug)]
+pub struct ParquetInputAdapter {
+    dataframe: Option<Vec<(String, Vec<Caracteristique>)>>,
+    ncol: usize,
+    nrow: usize,
+    schema: Option<SchemaMenages>,
+    colonne_identifiant: Option<String>,
+    colonnes: Option<HashSet<String>>,
+}
+
+impl ParquetInputAdapter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Les colonnes déclarées dans le schéma sont converties dans leur type
+    pub fn with_schema(self, schema: SchemaMenages) -> Self {
+        Self {
+            schema: Some(schema),
+            ..self
+        }
+    }
+
+    // Colonne dont les valeurs, uniques, identifient les ménages dans les résultats.
+    // Elle ne fait pas partie des caractéristiques
+    pub fn with_colonne_identifiant(self, colonne_identifiant: &str) -> Self {
+        Self {
+            colonne_identifiant: Some(colonne_identifiant.to_string()),
+            ..self
+        }
+    }
+
+    // Seules ces colonnes (et la colonne identifiant) sont lues : celles dont dépendent les
+    // politiques et celles reprises dans les résultats
+    pub fn with_colonnes(self, colonnes: HashSet<String>) -> Self {
+        Self {
+            colonnes: Some(colonnes),
+            ..self
+        }
+    }
+
+    // Lecture de tous les groupes de lignes du fichier, batch par batch
+    pub fn populate_from_path<P>(self, path: P) -> KalkotoResult<Self>
+    where
+        P: AsRef<Path>,
+    {
+        match path.as_ref().extension().and_then(OsStr::to_str) {
+            Some("parquet") => (),
+            _ => {
+                return Err(From::from(MenageListAdapterError::FileFormat(
+                    "Le fichier indiqué n'est pas un fichier Parquet".into(),
+                )))
+            }
+        }
+
+        let file = File::open(path).map_err(MenageListAdapterError::IO)?;
+
+        let lue = |nom: &str| colonne_lue(&self.colonnes, &self.colonne_identifiant, nom);
+
+        // Les colonnes hors de la projection ne sont pas lues dans le fichier
+        let reader = ParquetRecordBatchReaderBuilder::try_new(file)
+            .and_then(|builder| {
+                let projection = ProjectionMask::roots(
+                    builder.parquet_schema(),
+                    builder
+                        .schema()
+                        .fields()
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, field)| lue(field.name()))
+                        .map(|(position, _)| position),
+                );
+                builder.with_projection(projection).build()
+            })
+            .map_err(MenageListAdapterError::Parquet)?;
+
+        let schema = reader.schema();
+        let (dataframe, nrow) = extract_columns_from_batches(
+            &schema,
+            reader,
+            lue,
+            self.colonne_identifiant.as_deref(),
+        )?;
+
+        Ok(Self {
+            ncol: dataframe.len(),
+            dataframe: Some(dataframe),
+            nrow,
+            schema: self.schema,
+            colonne_identifiant: self.colonne_identifiant,
+            colonnes: self.colonnes,
+        })
+    }
+}
+
+impl MenageListCreator for ParquetInputAdapter {
+    fn create_valid_menage_input(
+        self,
+        empty_menage_input: MenageInputBuilder<EmptyList>,
+    ) -> KalkotoResult<MenageInput> {
+        match self.dataframe {
+            Some(dataframe) => create_menage_input_from_columns(
+                &dataframe,
+                self.ncol,
+                self.nrow,
+                empty_menage_input
+                    .with_schema(self.schema)
+                    .with_colonne_identifiant(self.colonne_identifiant)
+                    .with_colonnes(self.colonnes),
+            ),
+            _ => Err(KalkotoError::ListMenageError(
+                MenageListAdapterError::Uninitialized,
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Float64Array, Int64Array, RecordBatch, StringArray};
+    use arrow::datatypes::{DataType, Field, Schema};
+    use parquet::arrow::ArrowWriter;
+    use parquet::file::properties::WriterProperties;
+    use std::sync::Arc;
+    use tempdir::TempDir;
+
+    fn write_test_parquet(path: &Path, ages: Vec<i64>) -> KalkotoResult<()> {
+        let nrow = ages.len();
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("Age", DataType::Int64, false),
+            Field::new("Revenu", DataType::Float64, false),
+            Field::new("Commune", DataType::Utf8, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int64Array::from(ages)),
+                Arc::new(Float64Array::from(vec![1000.5; nrow])),
+                Arc::new(StringArray::from(vec!["Paris"; nrow])),
+            ],
+        )
+        .map_err(MenageListAdapterError::Arrow)?;
+
+        // Un groupe de lignes par ménage, pour vérifier qu'ils sont tous lus
+        let properties = WriterProperties::builder()
+            .set_max_row_group_size(1)
+            .build();
+        let file = File::create(path).map_err(MenageListAdapterError::IO)?;
+        let mut writer = ArrowWriter::try_new(file, schema, Some(properties))
+            .map_err(MenageListAdapterError::Parquet)?;
+        writer
+            .write(&batch)
+            .map_err(MenageListAdapterError::Parquet)?;
+        writer.close().map_err(MenageListAdapterError::Parquet)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn ok_parquet_all_row_groups() -> KalkotoResult<()> {
+        let tmp_dir = TempDir::new("test-input").map_err(MenageListAdapterError::IO)?;
+        let path = tmp_dir.path().join("menages.parquet");
+        write_test_parquet(&path, vec![80, 70, 60])?;
+
+        let menage_input = ParquetInputAdapter::new()
+            .populate_from_path(&path)?
+            .create_valid_menage_input(MenageInputBuilder::<EmptyList>::new())?;
+
+        let wanted = vec![
+            Caracteristique::Entier(80),
+            Caracteristique::Entier(70),
+            Caracteristique::Entier(60),
+        ];
+        let result: Vec<Caracteristique> = menage_input
+            .liste_menage_valide
+            .iter()
+            .map(|menage| menage.caracteristiques["Age"].clone())
+            .collect();
+        assert_eq!(wanted, result);
+        assert_eq!(
+            Some(&Caracteristique::Textuel("Paris".into())),
+            menage_input.liste_menage_valide[2]
+                .caracteristiques
+                .get("Commune")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn err_parquet_int64_overflow() -> KalkotoResult<()> {
+        let tmp_dir = TempDir::new("test-input").map_err(MenageListAdapterError::IO)?;
+        let path = tmp_dir.path().join("menages.parquet");
+        write_test_parquet(&path, vec![80, i64::MAX])?;
+
+        let result = ParquetInputAdapter::new().populate_from_path(&path);
+
+        let wanted = true;
+        let result = matches!(
+            result,
+            Err(KalkotoError::ListMenageError(
+                MenageListAdapterError::Conversion { ref colonne, ligne: 2, .. }
+            )) if colonne == "Age"
+        );
+        assert_eq!(wanted, result);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,317 @@
+use crate::adapters::input_adapters::{colonne_lue, MenageListAdapterError, MenageListCreator};
+use crate::entities::menage::{Caracteristique, Menage};
+use crate::entities::menage_input::{EmptyList, MenageInput, MenageInputBuilder};
+use crate::entities::schema_menages::SchemaMenages;
+use crate::{KalkotoError, KalkotoResult};
+use rusqlite::types::ValueRef;
+use rusqlite::{Connection, OpenFlags};
+use std::collections::HashSet;
+use std::path::Path;
+
+#[derive(Default, Debug)]
+pub struct SqliteInputAdapter {
+    liste_menages: Option<Vec<Menage>>,
+    schema: Option<SchemaMenages>,
+    colonne_identifiant: Option<String>,
+    colonnes: Option<HashSet<String>>,
+}
+
+// Affinité d'une colonne SQLite, déduite de son type déclaré selon les règles de SQLite.
+// Les colonnes calculées par la requête n'ont pas de type déclaré
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Affinite {
+    Entier,
+    Reel,
+    Texte,
+    Numerique,
+    Aucune,
+}
+
+impl Affinite {
+    fn from_type_declare(type_declare: Option<&str>) -> Self {
+        let Some(type_declare) = type_declare.map(str::to_uppercase) else {
+            return Affinite::Aucune;
+        };
+        let contient = |motifs: &[&str]| motifs.iter().any(|motif| type_declare.contains(motif));
+
+        if contient(&["INT"]) {
+            Affinite::Entier
+        } else if contient(&["CHAR", "CLOB", "TEXT"]) {
+            Affinite::Texte
+        } else if type_declare.is_empty() || contient(&["BLOB"]) {
+            Affinite::Aucune
+        } else if contient(&["REAL", "FLOA", "DOUB"]) {
+            Affinite::Reel
+        } else {
+            Affinite::Numerique
+        }
+    }
+
+    // Les entiers d'une colonne REAL restent des réels, pour que tous les ménages aient le
+    // même type de caractéristique
+    fn caracteristique(&self, valeur: ValueRef) -> Result<Caracteristique, String> {
+        match (self, valeur) {
+            (_, ValueRef::Null) => Ok(Caracteristique::Manquant),
+            (Affinite::Reel, ValueRef::Integer(entier)) => {
+                Ok(Caracteristique::Numeric(entier as f64))
+            }
+            (Affinite::Texte, ValueRef::Integer(entier)) => {
+                Ok(Caracteristique::Textuel(entier.to_string()))
+            }
+            (_, ValueRef::Integer(entier)) => i32::try_from(entier)
+                .map(Caracteristique::Entier)
+                .map_err(|_| format!("{entier} dépasse la capacité d'un entier 32 bits")),
+            (Affinite::Texte, ValueRef::Real(reel)) => {
+                Ok(Caracteristique::Textuel(reel.to_string()))
+            }
+            (_, ValueRef::Real(reel)) => Ok(Caracteristique::Numeric(reel)),
+            (_, ValueRef::Text(texte)) => std::str::from_utf8(texte)
+                .map(|texte| Caracteristique::Textuel(texte.to_string()))
+                .map_err(|e| e.to_string()),
+            (_, ValueRef::Blob(_)) => {
+                Err("une valeur binaire (BLOB) n'est pas une caractéristique".into())
+            }
+        }
+    }
+}
+
+impl SqliteInputAdapter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Les colonnes déclarées dans le schéma sont converties dans leur type
+    pub fn with_schema(self, schema: SchemaMenages) -> Self {
+        Self {
+            schema: Some(schema),
+            ..self
+        }
+    }
+
+    // Colonne dont les valeurs, uniques, identifient les ménages dans les résultats.
+    // Elle ne fait pas partie des caractéristiques
+    pub fn with_colonne_identifiant(self, colonne_identifiant: &str) -> Self {
+        Self {
+            colonne_identifiant: Some(colonne_identifiant.to_string()),
+            ..self
+        }
+    }
+
+    // Seules ces colonnes (et la colonne identifiant) sont lues : celles dont dépendent les
+    // politiques et celles reprises dans les résultats
+    pub fn with_colonnes(self, colonnes: HashSet<String>) -> Self {
+        Self {
+            colonnes: Some(colonnes),
+            ..self
+        }
+    }
+
+    // requete est une requête SELECT, ou le nom d'une table dont toutes les lignes sont lues.
+    // La base est ouverte en lecture seule
+    pub fn populate_from_path<P>(self, path: P, requete: &str) -> KalkotoResult<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let connexion = Connection::open_with_flags(
+            path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )
+        .map_err(MenageListAdapterError::Sqlite)?;
+
+        self.populate_from_connection(&connexion, requete)
+    }
+
+    pub fn populate_from_connection(
+        self,
+        connexion: &Connection,
+        requete: &str,
+    ) -> KalkotoResult<Self> {
+        let requete = match requete.chars().all(|c| c.is_alphanumeric() || c == '_') {
+            true => format!("SELECT * FROM \"{requete}\""),
+            false => requete.to_string(),
+        };
+
+        let mut statement = connexion
+            .prepare(&requete)
+            .map_err(MenageListAdapterError::Sqlite)?;
+
+        // Les colonnes hors de la projection ne sont pas converties, et la colonne identifiant
+        // est lue comme du texte pour ne pas réduire ses entiers à 32 bits
+        let colonnes: Vec<(usize, String, Affinite)> = statement
+            .columns()
+            .iter()
+            .enumerate()
+            .filter(|(_, colonne)| {
+                colonne_lue(&self.colonnes, &self.colonne_identifiant, colonne.name())
+            })
+            .map(|(position, colonne)| {
+                (
+                    position,
+                    colonne.name().to_string(),
+                    match self.colonne_identifiant.as_deref() == Some(colonne.name()) {
+                        true => Affinite::Texte,
+                        false => Affinite::from_type_declare(colonne.decl_type()),
+                    },
+                )
+            })
+            .collect();
+
+        let mut rows = statement
+            .query([])
+            .map_err(MenageListAdapterError::Sqlite)?;
+        let mut liste_menages = vec![];
+
+        while let Some(row) = rows.next().map_err(MenageListAdapterError::Sqlite)? {
+            let ligne = liste_menages.len() + 1;
+            let erreur = |colonne: &str, cause: String| MenageListAdapterError::Conversion {
+                colonne: colonne.to_string(),
+                ligne,
+                cause,
+            };
+
+            let mut menage = Menage::new(ligne as i32);
+
+            for (position, nom, affinite) in &colonnes {
+                let valeur = row
+                    .get_ref(*position)
+                    .map_err(MenageListAdapterError::Sqlite)?;
+                let caracteristique = affinite
+                    .caracteristique(valeur)
+                    .map_err(|cause| erreur(nom, cause))?;
+
+                menage.caracteristiques.insert(nom.clone(), caracteristique);
+            }
+
+            liste_menages.push(menage);
+        }
+
+        Ok(Self {
+            liste_menages: Some(liste_menages),
+            ..self
+        })
+    }
+}
+
+impl MenageListCreator for SqliteInputAdapter {
+    fn create_valid_menage_input(
+        self,
+        empty_menage_input: MenageInputBuilder<EmptyList>,
+    ) -> KalkotoResult<MenageInput> {
+        match self.liste_menages {
+            Some(liste_menages) => empty_menage_input
+                .with_schema(self.schema)
+                .with_colonne_identifiant(self.colonne_identifiant)
+                .with_colonnes(self.colonnes)
+                .from_unvalidated_liste_menage(&liste_menages)
+                .validate_liste_menage()?
+                .build_valide_menage_input(),
+            None => Err(KalkotoError::ListMenageError(
+                MenageListAdapterError::Uninitialized,
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base_de_test() -> rusqlite::Result<Connection> {
+        let connexion = Connection::open_in_memory()?;
+        connexion.execute_batch(
+            "CREATE TABLE cas_types (id INTEGER, Age INT, Revenu REAL, CodePostal VARCHAR(5));
+             INSERT INTO cas_types VALUES (12, 80, 1000, '01500');
+             INSERT INTO cas_types VALUES (7, 70, 2000.5, NULL);",
+        )?;
+        Ok(connexion)
+    }
+
+    #[test]
+    fn ok_sqlite_table_colonne_identifiant() -> KalkotoResult<()> {
+        let connexion = base_de_test().map_err(MenageListAdapterError::Sqlite)?;
+
+        let menage_input = SqliteInputAdapter::new()
+            .with_colonne_identifiant("id")
+            .populate_from_connection(&connexion, "cas_types")?
+            .create_valid_menage_input(MenageInputBuilder::new().with_valeurs_manquantes(
+                crate::entities::menage_input::ValeursManquantes::pour_toutes(
+                    crate::entities::menage_input::TraitementManquant::Transmettre,
+                ),
+            ))?;
+
+        let wanted: HashSet<String> = ["Age", "Revenu", "CodePostal"].map(String::from).into();
+        assert_eq!(wanted, menage_input.set_caracteristiques_valide);
+
+        let menages = menage_input.liste_menage_valide;
+        assert_eq!(
+            vec!["12", "7"],
+            menages.iter().map(Menage::cle).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            Caracteristique::Numeric(1000.0),
+            menages[0].caracteristiques["Revenu"]
+        );
+        assert_eq!(
+            Caracteristique::Textuel("01500".into()),
+            menages[0].caracteristiques["CodePostal"]
+        );
+        assert_eq!(
+            Caracteristique::Manquant,
+            menages[1].caracteristiques["CodePostal"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn ok_sqlite_identifiant_au_dela_de_32_bits() -> KalkotoResult<()> {
+        let connexion = Connection::open_in_memory().map_err(MenageListAdapterError::Sqlite)?;
+        connexion
+            .execute_batch(
+                "CREATE TABLE menages (id INTEGER, Age INT);
+                 INSERT INTO menages VALUES (2019000000001, 80);
+                 INSERT INTO menages VALUES (2019000000002, 70);",
+            )
+            .map_err(MenageListAdapterError::Sqlite)?;
+
+        let menage_input = SqliteInputAdapter::new()
+            .with_colonne_identifiant("id")
+            .populate_from_connection(&connexion, "menages")?
+            .create_valid_menage_input(MenageInputBuilder::new())?;
+
+        assert_eq!(
+            vec!["2019000000001", "2019000000002"],
+            menage_input
+                .liste_menage_valide
+                .iter()
+                .map(Menage::cle)
+                .collect::<Vec<_>>()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn err_sqlite_identifiant_en_double() -> KalkotoResult<()> {
+        let connexion = base_de_test().map_err(MenageListAdapterError::Sqlite)?;
+
+        let result = SqliteInputAdapter::new()
+            .with_colonne_identifiant("Dossier")
+            .populate_from_connection(
+                &connexion,
+                "SELECT 'A' AS Dossier, Age FROM cas_types WHERE Age > 60",
+            )?
+            .create_valid_menage_input(MenageInputBuilder::new());
+
+        let wanted = true;
+        let result = matches!(
+            result,
+            Err(KalkotoError::ListMenageError(
+                MenageListAdapterError::Validation { fault_index: 2, .. }
+            ))
+        );
+        assert_eq!(wanted, result);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,401 @@
+use crate::adapters::input_adapters::{colonne_lue, MenageListAdapterError, MenageListCreator};
+use crate::entities::menage::{Caracteristique, Menage};
+use crate::entities::menage_input::{EmptyList, MenageInput, MenageInputBuilder};
+use crate::entities::schema_menages::SchemaMenages;
+use crate::{KalkotoError, KalkotoResult};
+use calamine::{open_workbook_auto, open_workbook_auto_from_rs, Data, Range, Reader, Sheets};
+use chrono::NaiveTime;
+use std::collections::HashSet;
+use std::ffi::OsStr;
+use std::io::{Cursor, Read, Seek};
+use std::path::Path;
+
+// Classeur Excel (.xlsx, .xlsm, .xlsb, .xls) ou OpenDocument (.ods) : une feuille contient un
+// ménage par ligne, sous une ligne d'en-tête qui nomme les caractéristiques
+#[derive(Default, Debug)]
+pub struct TableurInputAdapter {
+    liste_menages: Option<Vec<Menage>>,
+    feuille: Option<String>,
+    lignes_avant_entete: u32,
+    schema: Option<SchemaMenages>,
+    colonne_identifiant: Option<String>,
+    colonnes: Option<HashSet<String>>,
+}
+
+impl TableurInputAdapter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Feuille lue, la première du classeur par défaut
+    pub fn with_feuille(self, feuille: &str) -> Self {
+        Self {
+            feuille: Some(feuille.to_string()),
+            ..self
+        }
+    }
+
+    // Numéro (à partir de 1) de la ligne d'en-tête : les lignes précédentes (titre, notes) sont
+    // ignorées
+    pub fn with_ligne_entete(self, ligne_entete: u32) -> Self {
+        Self {
+            lignes_avant_entete: ligne_entete.saturating_sub(1),
+            ..self
+        }
+    }
+
+    // Les colonnes déclarées dans le schéma sont converties dans leur type
+    pub fn with_schema(self, schema: SchemaMenages) -> Self {
+        Self {
+            schema: Some(schema),
+            ..self
+        }
+    }
+
+    // Colonne dont les valeurs, uniques, identifient les ménages dans les résultats.
+    // Elle ne fait pas partie des caractéristiques
+    pub fn with_colonne_identifiant(self, colonne_identifiant: &str) -> Self {
+        Self {
+            colonne_identifiant: Some(colonne_identifiant.to_string()),
+            ..self
+        }
+    }
+
+    // Seules ces colonnes (et la colonne identifiant) sont lues : celles dont dépendent les
+    // politiques et celles reprises dans les résultats
+    pub fn with_colonnes(self, colonnes: HashSet<String>) -> Self {
+        Self {
+            colonnes: Some(colonnes),
+            ..self
+        }
+    }
+
+    pub fn populate_from_path<P>(self, path: P) -> KalkotoResult<Self>
+    where
+        P: AsRef<Path>,
+    {
+        match path.as_ref().extension().and_then(OsStr::to_str) {
+            Some("xlsx" | "xlsm" | "xlsb" | "xls" | "ods") => (),
+            _ => {
+                return Err(From::from(MenageListAdapterError::FileFormat(
+                    "Le fichier indiqué n'est pas un classeur Excel ou OpenDocument".into(),
+                )))
+            }
+        }
+
+        let classeur = open_workbook_auto(path).map_err(MenageListAdapterError::Tableur)?;
+        self.populate_from_classeur(classeur)
+    }
+
+    // Lecture d'un classeur déjà chargé en mémoire (application web notamment)
+    pub fn populate_from_memory(self, buf: &[u8]) -> KalkotoResult<Self> {
+        let classeur = open_workbook_auto_from_rs(Cursor::new(buf))
+            .map_err(MenageListAdapterError::Tableur)?;
+        self.populate_from_classeur(classeur)
+    }
+
+    fn populate_from_classeur<RS: Read + Seek>(
+        self,
+        mut classeur: Sheets<RS>,
+    ) -> KalkotoResult<Self> {
+        let feuilles = classeur.sheet_names();
+        let feuille = match &self.feuille {
+            Some(feuille) if feuilles.contains(feuille) => feuille.clone(),
+            Some(feuille) => {
+                return Err(From::from(MenageListAdapterError::FileFormat(format!(
+                    "La feuille {feuille} est absente du classeur, qui contient : {}",
+                    feuilles.join(", ")
+                ))))
+            }
+            None => feuilles.first().cloned().ok_or_else(|| {
+                MenageListAdapterError::FileFormat("Le classeur ne contient aucune feuille".into())
+            })?,
+        };
+
+        let cellules = classeur
+            .worksheet_range(&feuille)
+            .map_err(MenageListAdapterError::Tableur)?;
+
+        Ok(Self {
+            liste_menages: Some(self.lit_feuille(&feuille, &cellules)?),
+            ..self
+        })
+    }
+
+    fn lit_feuille(&self, feuille: &str, cellules: &Range<Data>) -> KalkotoResult<Vec<Menage>> {
+        let erreur_entete = || {
+            MenageListAdapterError::FileFormat(format!(
+                "La ligne d'en-tête {} de la feuille {feuille} est vide",
+                self.lignes_avant_entete + 1
+            ))
+        };
+
+        // Les positions de la plage de cellules sont relatives à sa première cellule non vide
+        let (premiere_ligne, _) = cellules.start().ok_or_else(erreur_entete)?;
+        let entete = self
+            .lignes_avant_entete
+            .checked_sub(premiere_ligne)
+            .ok_or_else(erreur_entete)?;
+        let mut lignes = cellules.rows().skip(entete as usize);
+
+        let noms_colonnes: Vec<String> = lignes
+            .next()
+            .ok_or_else(erreur_entete)?
+            .iter()
+            .map(|cellule| cellule.to_string().trim().to_string())
+            .collect();
+
+        let mut noms_vus = HashSet::new();
+        if let Some(doublon) = noms_colonnes
+            .iter()
+            .find(|nom| !nom.is_empty() && !noms_vus.insert(*nom))
+        {
+            return Err(From::from(MenageListAdapterError::FileFormat(format!(
+                "La colonne {doublon} apparaît plusieurs fois dans l'en-tête"
+            ))));
+        }
+
+        let lues: Vec<bool> = noms_colonnes
+            .iter()
+            .map(|nom| colonne_lue(&self.colonnes, &self.colonne_identifiant, nom))
+            .collect();
+
+        // Les lignes entièrement vides (mise en forme en fin de feuille) sont ignorées
+        let mut colonnes: Vec<Vec<Caracteristique>> = vec![vec![]; noms_colonnes.len()];
+        let mut nombre_menages: usize = 0;
+        for ligne in lignes.filter(|ligne| ligne.iter().any(|cellule| *cellule != Data::Empty)) {
+            nombre_menages += 1;
+            for (position, (cellule, nom)) in ligne.iter().zip(&noms_colonnes).enumerate() {
+                if !lues[position] {
+                    continue;
+                }
+                let conversion = match self.colonne_identifiant.as_ref() == Some(nom) {
+                    true => identifiant_from_cellule,
+                    false => caracteristique_from_cellule,
+                };
+                let caracteristique =
+                    conversion(cellule).map_err(|cause| MenageListAdapterError::Conversion {
+                        colonne: nom.clone(),
+                        ligne: colonnes[position].len() + 1,
+                        cause,
+                    })?;
+                colonnes[position].push(caracteristique);
+            }
+        }
+
+        let mut liste_menages: Vec<Menage> = (1..=nombre_menages)
+            .map(|index| Menage::new(index as i32))
+            .collect();
+
+        for (nom, valeurs) in noms_colonnes.iter().zip(colonnes) {
+            if nom.is_empty() {
+                match valeurs
+                    .iter()
+                    .all(|valeur| *valeur == Caracteristique::Manquant)
+                {
+                    true => continue,
+                    false => {
+                        return Err(From::from(MenageListAdapterError::FileFormat(
+                            "Une colonne contient des valeurs mais n'a pas de nom dans l'en-tête"
+                                .into(),
+                        )))
+                    }
+                }
+            }
+
+            for (menage, valeur) in liste_menages.iter_mut().zip(entiers_si_possible(valeurs)) {
+                menage.caracteristiques.insert(nom.clone(), valeur);
+            }
+        }
+
+        Ok(liste_menages)
+    }
+}
+
+// Les dates à minuit sont lues comme des dates, les autres au format ISO 8601 (AAAA-MM-JJ HH:MM:SS)
+fn caracteristique_from_cellule(cellule: &Data) -> Result<Caracteristique, String> {
+    match cellule {
+        Data::Empty => Ok(Caracteristique::Manquant),
+        Data::String(texte) if texte.trim().is_empty() => Ok(Caracteristique::Manquant),
+        Data::String(texte) => Ok(Caracteristique::Textuel(texte.clone())),
+        Data::Int(entier) => i32::try_from(*entier)
+            .map(Caracteristique::Entier)
+            .map_err(|_| format!("{entier} dépasse la capacité d'un entier 32 bits")),
+        Data::Float(reel) => Ok(Caracteristique::Numeric(*reel)),
+        Data::Bool(booleen) => Ok(Caracteristique::Booleen(*booleen)),
+        Data::DateTime(date) => date
+            .as_datetime()
+            .map(|date| match date.time() == NaiveTime::MIN {
+                true => Caracteristique::Date(date.date()),
+                false => Caracteristique::Textuel(date.to_string()),
+            })
+            .ok_or_else(|| "date hors des bornes représentables".to_string()),
+        Data::DateTimeIso(texte) | Data::DurationIso(texte) => {
+            Ok(Caracteristique::Textuel(texte.clone()))
+        }
+        Data::Error(erreur) => Err(format!("cellule en erreur ({erreur})")),
+    }
+}
+
+// Les identifiants numériques sont gardés en texte, sans passer par les entiers 32 bits
+fn identifiant_from_cellule(cellule: &Data) -> Result<Caracteristique, String> {
+    match cellule {
+        Data::Int(entier) => Ok(Caracteristique::Textuel(entier.to_string())),
+        Data::Float(reel) => Ok(Caracteristique::Textuel(reel.to_string())),
+        cellule => caracteristique_from_cellule(cellule),
+    }
+}
+
+// Les tableurs stockent tous les nombres en virgule flottante : une colonne dont tous les
+// nombres sont entiers est lue comme une colonne d'entiers
+fn entiers_si_possible(valeurs: Vec<Caracteristique>) -> Vec<Caracteristique> {
+    let entiere = |valeur: &Caracteristique| match valeur {
+        Caracteristique::Numeric(reel) => {
+            reel.fract() == 0.0 && *reel >= i32::MIN as f64 && *reel <= i32::MAX as f64
+        }
+        Caracteristique::Entier(_) | Caracteristique::Manquant => true,
+        Caracteristique::Textuel(_) | Caracteristique::Booleen(_) | Caracteristique::Date(_) => {
+            false
+        }
+    };
+
+    match valeurs.iter().all(entiere) {
+        true => valeurs
+            .into_iter()
+            .map(|valeur| match valeur {
+                Caracteristique::Numeric(reel) => Caracteristique::Entier(reel as i32),
+                valeur => valeur,
+            })
+            .collect(),
+        false => valeurs,
+    }
+}
+
+impl MenageListCreator for TableurInputAdapter {
+    fn create_valid_menage_input(
+        self,
+        empty_menage_input: MenageInputBuilder<EmptyList>,
+    ) -> KalkotoResult<MenageInput> {
+        match self.liste_menages {
+            Some(liste_menages) => empty_menage_input
+                .with_schema(self.schema)
+                .with_colonne_identifiant(self.colonne_identifiant)
+                .with_colonnes(self.colonnes)
+                .from_unvalidated_liste_menage(&liste_menages)
+                .validate_liste_menage()?
+                .build_valide_menage_input(),
+            None => Err(KalkotoError::ListMenageError(
+                MenageListAdapterError::Uninitialized,
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entities::menage_input::{TraitementManquant, ValeursManquantes};
+    use chrono::NaiveDate;
+    use rust_xlsxwriter::{ExcelDateTime, Format, Workbook, XlsxError};
+    use tempdir::TempDir;
+
+    fn ecrit_classeur(path: &Path) -> Result<(), XlsxError> {
+        let mut workbook = Workbook::new();
+        workbook.add_worksheet().set_name("Notes")?;
+
+        let feuille = workbook.add_worksheet().set_name("Ménages")?;
+        feuille.write_string(0, 0, "Cas-types APA 2024")?;
+        for (colonne, nom) in ["Age", "Revenu", "Commune", "Entree", "Locataire"]
+            .iter()
+            .enumerate()
+        {
+            feuille.write_string(1, colonne as u16, *nom)?;
+        }
+        feuille.write_number(2, 0, 80)?;
+        feuille.write_number(2, 1, 1000.5)?;
+        feuille.write_string(2, 2, "01500")?;
+        feuille.write_datetime_with_format(
+            2,
+            3,
+            ExcelDateTime::from_ymd(2024, 1, 15)?,
+            &Format::new().set_num_format("yyyy-mm-dd"),
+        )?;
+        feuille.write_boolean(2, 4, true)?;
+        feuille.write_number(3, 0, 70)?;
+        feuille.write_number(3, 1, 2000)?;
+        feuille.write_datetime_with_format(
+            3,
+            3,
+            ExcelDateTime::from_ymd(2023, 12, 1)?,
+            &Format::new().set_num_format("yyyy-mm-dd"),
+        )?;
+        feuille.write_boolean(3, 4, false)?;
+
+        workbook.save(path)
+    }
+
+    #[test]
+    fn ok_tableur_feuille_et_ligne_entete() -> KalkotoResult<()> {
+        let tmp_dir = TempDir::new("test-input").map_err(MenageListAdapterError::IO)?;
+        let path = tmp_dir.path().join("cas_types.xlsx");
+        ecrit_classeur(&path).expect("écriture du classeur de test");
+
+        let menage_input = TableurInputAdapter::new()
+            .with_feuille("Ménages")
+            .with_ligne_entete(2)
+            .populate_from_path(&path)?
+            .create_valid_menage_input(MenageInputBuilder::new().with_valeurs_manquantes(
+                ValeursManquantes::pour_toutes(TraitementManquant::Transmettre),
+            ))?;
+
+        let menages = menage_input.liste_menage_valide;
+        assert_eq!(2, menages.len());
+        assert_eq!(
+            Caracteristique::Entier(80),
+            menages[0].caracteristiques["Age"]
+        );
+        assert_eq!(
+            Caracteristique::Numeric(2000.0),
+            menages[1].caracteristiques["Revenu"]
+        );
+        assert_eq!(
+            Caracteristique::Textuel("01500".into()),
+            menages[0].caracteristiques["Commune"]
+        );
+        assert_eq!(
+            Caracteristique::Manquant,
+            menages[1].caracteristiques["Commune"]
+        );
+        assert_eq!(
+            Caracteristique::Date(NaiveDate::from_ymd_opt(2024, 1, 15).unwrap()),
+            menages[0].caracteristiques["Entree"]
+        );
+        assert_eq!(
+            Caracteristique::Booleen(false),
+            menages[1].caracteristiques["Locataire"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn err_tableur_feuille_absente() -> KalkotoResult<()> {
+        let tmp_dir = TempDir::new("test-input").map_err(MenageListAdapterError::IO)?;
+        let path = tmp_dir.path().join("cas_types.xlsx");
+        ecrit_classeur(&path).expect("écriture du classeur de test");
+
+        let result = TableurInputAdapter::new()
+            .with_feuille("Menages 2025")
+            .populate_from_path(&path);
+
+        let wanted = true;
+        let result = matches!(
+            result,
+            Err(KalkotoError::ListMenageError(MenageListAdapterError::FileFormat(ref message)))
+                if message.contains("Notes, Ménages")
+        );
+        assert_eq!(wanted, result);
+
+        Ok(())
+    }
+}
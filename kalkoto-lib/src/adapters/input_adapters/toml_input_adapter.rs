@@ -1,6 +1,9 @@
 use crate::adapters::input_adapters::{PolicyAdapterError, PolicyCreator};
-use crate::entities::policy::{Composante, Parameters, Policy};
+use crate::entities::evaluator::{MOTEUR_FORMULA, MOTEUR_PYTHON, MOTEUR_RHAI};
+use crate::entities::formula::ContexteVerification;
+use crate::entities::policy::{Composante, Niveau, Parameters, Policy};
 use crate::entities::policy_input::PolicyInput;
+use crate::entities::python_environnement::EnvironnementPython;
 use crate::{KalkotoError, KalkotoResult};
 use rayon::slice::ParallelSlice;
 use std::collections::{HashMap, HashSet};
@@ -18,6 +21,8 @@ pub struct TomlInputAdapter {
     policy_name: Option<String>,
     policy_intitule: Option<String>,
     policy_composantes: Option<Vec<Composante>>,
+    policy_path: Option<String>,
+    policy_environnement_python: EnvironnementPython,
 }
 
 impl TomlInputAdapter {
@@ -26,6 +31,14 @@ impl TomlInputAdapter {
     }
 
     pub fn populate_from_buf(self, buf: &[u8]) -> KalkotoResult<(String, String, Vec<Composante>)> {
+        let (policy_name, policy_intitule, policy_composantes, _) = self.lit_policy(buf)?;
+        Ok((policy_name, policy_intitule, policy_composantes))
+    }
+
+    fn lit_policy(
+        &self,
+        buf: &[u8],
+    ) -> KalkotoResult<(String, String, Vec<Composante>, EnvironnementPython)> {
         let s = std::str::from_utf8(buf).map_err(PolicyAdapterError::from)?;
 
         let policy_table: Table = match s.parse::<Table>() {
@@ -33,15 +46,18 @@ impl TomlInputAdapter {
             Err(e) => return Err(From::from(PolicyAdapterError::Interpret(e))),
         };
 
-        let clefs_autorisees: HashSet<_> = ["name", "intitule_long", "composante"]
+        let clefs_obligatoires: HashSet<_> = ["name", "intitule_long", "composante"]
             .iter()
             .cloned()
             .collect();
+        let clefs_facultatives: HashSet<_> = ["python"].iter().cloned().collect();
 
-        let toutes_autorisees = policy_table
-            .keys()
-            .all(|k| clefs_autorisees.contains(&k.as_str()));
-        let no_missing = clefs_autorisees.len() == policy_table.len();
+        let toutes_autorisees = policy_table.keys().all(|k| {
+            clefs_obligatoires.contains(&k.as_str()) || clefs_facultatives.contains(&k.as_str())
+        });
+        let no_missing = clefs_obligatoires
+            .iter()
+            .all(|clef| policy_table.contains_key(*clef));
 
         let valid_keys = toutes_autorisees && no_missing;
 
@@ -74,7 +90,20 @@ impl TomlInputAdapter {
                     .try_into()
                     .map_err(PolicyAdapterError::from)?;
 
-                Ok((policy_name, policy_intitule, policy_composantes))
+                let policy_environnement_python: EnvironnementPython = policy_table
+                    .get("python")
+                    .cloned()
+                    .map(|python| python.try_into())
+                    .transpose()
+                    .map_err(PolicyAdapterError::from)?
+                    .unwrap_or_default();
+
+                Ok((
+                    policy_name,
+                    policy_intitule,
+                    policy_composantes,
+                    policy_environnement_python,
+                ))
             }
             false => Err(From::from(PolicyAdapterError::Generic(
                 "Le fichier d'input ne contient pas les clefs nécessaires".into(),
@@ -82,10 +111,45 @@ impl TomlInputAdapter {
         }
     }
 
+    // Caractéristiques des ménages dont dépendent les composantes lues, pour ne charger
+    // que ces colonnes du fichier ménages
+    pub fn caracteristiques_menages(&self) -> HashSet<String> {
+        self.policy_composantes
+            .iter()
+            .flatten()
+            .flat_map(|composante| composante.caracteristiques_dependencies.iter().cloned())
+            .collect()
+    }
+
+    // Caractéristiques des individus lues par les composantes, pour la table des individus
+    pub fn caracteristiques_individus(&self) -> HashSet<String> {
+        self.policy_composantes
+            .iter()
+            .flatten()
+            .flat_map(|composante| composante.caracteristiques_individus.iter().cloned())
+            .collect()
+    }
+
+    // Lecture d'un TOML déjà chargé en mémoire (application web notamment)
+    pub fn populate_from_memory(self, buf: &[u8]) -> KalkotoResult<Self> {
+        let (policy_name, policy_intitule, policy_composantes, policy_environnement_python) =
+            self.lit_policy(buf)?;
+
+        Ok(Self {
+            policy_name: Some(policy_name),
+            policy_intitule: Some(policy_intitule),
+            policy_composantes: Some(policy_composantes),
+            policy_path: None,
+            policy_environnement_python,
+        })
+    }
+
     pub fn populate_from_path<P>(self, path: P, buf_string: &mut String) -> KalkotoResult<Self>
     where
         P: AsRef<Path>,
     {
+        let policy_path = path.as_ref().display().to_string();
+
         match path.as_ref().extension().and_then(OsStr::to_str) {
             Some("toml") => (),
             _ => {
@@ -108,13 +172,15 @@ impl TomlInputAdapter {
 
         let input_slice = buf_string.as_bytes();
 
-        let (policy_name, policy_intitule, policy_composantes) =
-            self.populate_from_buf(input_slice)?;
+        let (policy_name, policy_intitule, policy_composantes, policy_environnement_python) =
+            self.lit_policy(input_slice)?;
 
         Ok(Self {
             policy_name: Some(policy_name),
             policy_intitule: Some(policy_intitule),
             policy_composantes: Some(policy_composantes),
+            policy_environnement_python: policy_environnement_python.relatif_a(Some(&policy_path)),
+            policy_path: Some(policy_path),
         })
     }
 }
@@ -132,6 +198,7 @@ impl PolicyCreator for TomlInputAdapter {
                 let mut policy_parameters_intitules = HashMap::new();
                 let mut policy_parameters_values = HashMap::new();
                 let mut policy_caracteristiques = HashSet::new();
+                let mut policy_caracteristiques_individus = HashSet::new();
 
                 for composante in composantes.iter() {
                     let temp_dict_names: HashMap<String, String> = composante
@@ -158,6 +225,63 @@ impl PolicyCreator for TomlInputAdapter {
                         .cloned()
                         .collect();
                     policy_caracteristiques.extend(temp_set);
+                    policy_caracteristiques_individus
+                        .extend(composante.caracteristiques_individus.iter().cloned());
+                }
+
+                let mut variables_precedentes = HashSet::new();
+                let mut variables_individus_precedentes = HashSet::new();
+
+                for composante in composantes.iter() {
+                    if composante.niveau == Niveau::Individu
+                        && composante.engine() != MOTEUR_FORMULA
+                    {
+                        return Err(From::from(PolicyAdapterError::Generic(format!(
+                            "la composante {} de niveau individu doit être calculée par une formula",
+                            composante.name
+                        ))));
+                    }
+
+                    match (
+                        composante.engine(),
+                        &composante.function,
+                        &composante.formula,
+                    ) {
+                        (MOTEUR_PYTHON | MOTEUR_RHAI, Some(_), None) => (),
+                        (MOTEUR_FORMULA, None, Some(formula)) => formula
+                            .verifie(&ContexteVerification {
+                                caracteristiques: &composante.caracteristiques_dependencies,
+                                parametres: &policy_parameters_values,
+                                variables: &variables_precedentes,
+                                caracteristiques_individus: &composante.caracteristiques_individus,
+                                variables_individus: &variables_individus_precedentes,
+                                individu: composante.niveau == Niveau::Individu,
+                            })
+                            .map_err(|message| PolicyAdapterError::Formula {
+                                composante: composante.name.to_owned(),
+                                message,
+                            })?,
+                        (MOTEUR_PYTHON | MOTEUR_RHAI | MOTEUR_FORMULA, _, _) => {
+                            return Err(From::from(PolicyAdapterError::Generic(format!(
+                                "la composante {} doit définir soit une function, soit une formula",
+                                composante.name
+                            ))))
+                        }
+                        // Moteurs enregistrés par le programme hôte : le calcul n'est pas décrit dans le TOML
+                        (_, None, None) => (),
+                        (engine, _, _) => {
+                            return Err(From::from(PolicyAdapterError::Generic(format!(
+                                "la composante {} calculée par le moteur {} ne doit définir ni function, ni formula",
+                                composante.name, engine
+                            ))))
+                        }
+                    }
+                    match composante.niveau {
+                        Niveau::Menage => variables_precedentes.insert(composante.name.to_owned()),
+                        Niveau::Individu => {
+                            variables_individus_precedentes.insert(composante.name.to_owned())
+                        }
+                    };
                 }
 
                 let policy = Policy {
@@ -167,7 +291,11 @@ impl PolicyCreator for TomlInputAdapter {
                     parameters_intitules: policy_parameters_intitules.clone(),
                     parameters_values: policy_parameters_values.clone(),
                     caracteristiques_menages: policy_caracteristiques.clone(),
+                    caracteristiques_individus: policy_caracteristiques_individus,
                     python_functions: None,
+                    fichier_policy: self.policy_path,
+                    python_source_map: None,
+                    environnement_python: self.policy_environnement_python,
                 };
 
                 let policy = policy.populate_python_functions()?;
@@ -184,6 +312,8 @@ impl PolicyCreator for TomlInputAdapter {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::entities::evaluator::Evaluateurs;
+    use crate::entities::menage::{Caracteristique, Individu, Menage};
     use std::fs;
     use tempdir::TempDir;
 
@@ -361,4 +491,228 @@ def plan_cons(Variables, ParamsDict, MenageCarac):
 
         Ok(())
     }
+
+    static FORMULA_TOML_BYTES: &[u8] = r#"
+name = "APA domicile"
+
+intitule_long = "Aide personnalisée à domicile"
+
+[[composante]]
+name = "plan_notif"
+intitule_long = "Plan notifié"
+parameters.names = ["tau_1","tau_2"]
+parameters.intitules_long = ["Taux GIR 1","Taux GIR 2"]
+parameters.values = [0.15,0.3]
+caracteristiques_dependencies = ["Age","GIR"]
+logical_order = 1
+formula = """
+ParamsDict["tau_1"] * MenageCarac["Age"] if MenageCarac["GIR"] == 1 else ParamsDict["tau_2"] * MenageCarac["Age"]
+"""
+
+[[composante]]
+name = "plan_cons"
+intitule_long = "Plan effectivement consommé"
+parameters.names = ["taux_ss_conso"]
+parameters.intitules_long = ["Taux de sous-consommation du plan notifié"]
+parameters.values = [0.8]
+caracteristiques_dependencies = []
+logical_order = 2
+formula = 'Variables["plan_notif"]*ParamsDict["taux_ss_conso"]'
+"#
+    .as_bytes();
+
+    #[test]
+    fn ok_toml_formula_policy_simulate() -> KalkotoResult<()> {
+        let policy_input = TomlInputAdapter::new()
+            .populate_from_memory(FORMULA_TOML_BYTES)?
+            .create_valid_policy_input()?;
+
+        let mut menage = Menage::new(1);
+        menage
+            .caracteristiques
+            .insert("Age".into(), Caracteristique::Entier(80));
+        menage
+            .caracteristiques
+            .insert("GIR".into(), Caracteristique::Entier(2));
+
+        let results = policy_input.valid_policy.simulate_all_menages(&[menage])?;
+
+        let wanted = HashMap::from([
+            ("plan_notif".to_string(), 24.0),
+            ("plan_cons".to_string(), 19.200000000000003),
+        ]);
+        assert_eq!(vec![wanted], results);
+
+        Ok(())
+    }
+
+    #[test]
+    fn err_toml_formula_unknown_variable() -> KalkotoResult<()> {
+        let toml = r#"
+name = "APA domicile"
+intitule_long = "Aide personnalisée à domicile"
+
+[[composante]]
+name = "plan_notif"
+intitule_long = "Plan notifié"
+parameters.names = []
+parameters.intitules_long = []
+parameters.values = []
+caracteristiques_dependencies = ["Age"]
+logical_order = 1
+formula = 'MenageCarac["Age"]'
+
+[[composante]]
+name = "plan_cons"
+intitule_long = "Plan effectivement consommé"
+parameters.names = []
+parameters.intitules_long = []
+parameters.values = []
+caracteristiques_dependencies = []
+logical_order = 2
+formula = 'Variables["plan_total"]'
+"#;
+
+        let result = TomlInputAdapter::new()
+            .populate_from_memory(toml.as_bytes())?
+            .create_valid_policy_input();
+
+        let wanted = true;
+        let result = matches!(
+            result,
+            Err(KalkotoError::PolicyError(
+                PolicyAdapterError::Formula { .. }
+            ))
+        );
+        assert_eq!(wanted, result);
+
+        Ok(())
+    }
+
+    #[test]
+    fn err_toml_formula_syntax() {
+        let toml = r#"
+name = "APA domicile"
+intitule_long = "Aide personnalisée à domicile"
+
+[[composante]]
+name = "plan_notif"
+intitule_long = "Plan notifié"
+parameters.names = ["tau_1"]
+parameters.intitules_long = ["Taux GIR 1"]
+parameters.values = [0.15]
+caracteristiques_dependencies = ["Age"]
+logical_order = 1
+formula = 'ParamsDict["tau_1"] * MenageCarac["Age"'
+"#;
+
+        let wanted = true;
+        let result = TomlInputAdapter::new()
+            .populate_from_buf(toml.as_bytes())
+            .is_err();
+        assert_eq!(wanted, result);
+    }
+
+    static INDIVIDUS_TOML_BYTES: &[u8] = r#"
+name = "Prime d'activité"
+
+intitule_long = "Prime d'activité calculée par individu"
+
+[[composante]]
+name = "bonification"
+intitule_long = "Bonification individuelle"
+parameters.names = ["taux_bonif"]
+parameters.intitules_long = ["Taux de bonification des revenus d'activité"]
+parameters.values = [0.1]
+caracteristiques_dependencies = []
+caracteristiques_individus = ["Salaire"]
+niveau = "individu"
+logical_order = 1
+formula = 'IndividuCarac["Salaire"] * ParamsDict["taux_bonif"]'
+
+[[composante]]
+name = "prime"
+intitule_long = "Prime du foyer"
+parameters.names = ["forfait_enfant"]
+parameters.intitules_long = ["Forfait par enfant"]
+parameters.values = [50.0]
+caracteristiques_dependencies = []
+caracteristiques_individus = ["Age"]
+logical_order = 2
+formula = 'somme_individus(IndividuVariables["bonification"]) + ParamsDict["forfait_enfant"] * nombre_individus(IndividuCarac["Age"] < 18)'
+"#
+    .as_bytes();
+
+    #[test]
+    fn ok_toml_composantes_individus_simulate() -> KalkotoResult<()> {
+        let policy = TomlInputAdapter::new()
+            .populate_from_memory(INDIVIDUS_TOML_BYTES)?
+            .create_valid_policy_input()?
+            .valid_policy;
+
+        let mut menage = Menage::new(1);
+        menage.individus = [(1, 40, 1500), (2, 12, 0), (3, 8, 0)]
+            .into_iter()
+            .map(|(index, age, salaire)| Individu {
+                index,
+                caracteristiques: HashMap::from([
+                    ("Age".to_string(), Caracteristique::Entier(age)),
+                    ("Salaire".to_string(), Caracteristique::Entier(salaire)),
+                ]),
+            })
+            .collect();
+
+        let (results, results_individus, _) = policy.simulate_menages_et_individus(
+            &[menage],
+            &HashSet::new(),
+            &Evaluateurs::default(),
+            0,
+            None,
+        )?;
+
+        assert_eq!(250.0, results[0]["prime"]);
+        let bonifications: Vec<f64> = results_individus[0]
+            .iter()
+            .map(|variables| variables["bonification"])
+            .collect();
+        assert_eq!(vec![150.0, 0.0, 0.0], bonifications);
+
+        Ok(())
+    }
+
+    #[test]
+    fn err_toml_composante_individu_hors_formula() -> KalkotoResult<()> {
+        let toml = r#"
+name = "Prime d'activité"
+intitule_long = "Prime d'activité calculée par individu"
+
+[[composante]]
+name = "bonification"
+intitule_long = "Bonification individuelle"
+parameters.names = []
+parameters.intitules_long = []
+parameters.values = []
+caracteristiques_dependencies = []
+niveau = "individu"
+logical_order = 1
+function = """
+def bonification(Variables, ParamsDict, MenageCarac):
+    return 0.0
+"""
+"#;
+
+        let result = TomlInputAdapter::new()
+            .populate_from_memory(toml.as_bytes())?
+            .create_valid_policy_input();
+
+        let wanted = true;
+        let result = matches!(
+            result,
+            Err(KalkotoError::PolicyError(PolicyAdapterError::Generic(ref cause)))
+                if cause.contains("bonification")
+        );
+        assert_eq!(wanted, result);
+
+        Ok(())
+    }
 }
@@ -1,7 +1,11 @@
 use crate::{
-    adapters::output_adapters::{OutputAdapterError, OutputWriter},
+    adapters::output_adapters::{
+        csv_output_adapter::COLONNE_INDIVIDU, OutputAdapterError, OutputWriter,
+    },
     entities::{
         menage::{Caracteristique, Menage},
+        menage_input::MenageInput,
+        policy::{Policy, ResultatsIndividus},
         simulator::{
             SimulationError, SimulatorBuilder, ValidBaselineInput, ValidMenageInput,
             ValidVarianteInput,
@@ -9,8 +13,10 @@ use crate::{
     },
     KalkotoError, KalkotoResult,
 };
-use arrow::array::{Array, ArrayRef, Float64Array, Int32Array, RecordBatch, StringArray};
-use arrow::datatypes::{DataType, Field, Schema};
+use arrow::array::{
+    Array, ArrayRef, BooleanArray, Date32Array, Float64Array, Int32Array, RecordBatch, StringArray,
+};
+use arrow::datatypes::{DataType, Date32Type, Field, Schema};
 use arrow_ipc::writer::FileWriter;
 use itertools::Itertools;
 use std::{collections::HashMap, fs::File, path::Path, sync::Arc, thread};
@@ -40,6 +46,36 @@ impl ArrowOutputAdapter {
             output_prefix: Some(prefix.clone()),
         }
     }
+
+    // Résultats de niveau individu, dans {prefix}-{nom_simulation}-individus.arrow
+    fn export_individus_results<V: Default + AllowedValue + Copy + 'static>(
+        &self,
+        nom_simulation: &str,
+        simulated_menage_input: &MenageInput,
+        policy: &Policy,
+        results_individus: &Option<ResultatsIndividus<V>>,
+        seed: u64,
+    ) -> KalkotoResult<()> {
+        let Some(results_individus) = results_individus else {
+            return Ok(());
+        };
+        if !policy.a_composantes_individus() {
+            return Ok(());
+        }
+
+        let output_path = match &self.output_prefix {
+            Some(output_prefix) => format!("{}-{}-individus.arrow", output_prefix, nom_simulation),
+            _ => format!("{}-individus.arrow", nom_simulation),
+        };
+
+        let final_record = add_run_metadata(
+            create_record_batch_individus(simulated_menage_input, results_individus)?,
+            seed,
+            simulated_menage_input,
+        )?;
+
+        write_final_record(&final_record, output_path)
+    }
 }
 
 impl OutputWriter for ArrowOutputAdapter {
@@ -54,17 +90,34 @@ impl OutputWriter for ArrowOutputAdapter {
                     _ => String::from("baseline-results.arrow"),
                 };
 
-                let record_menage = create_record_batch_from_menage_list(
-                    &simulated.menage_input.0.liste_menage_valide,
+                let record_menage = add_colonne_identifiant(
+                    add_colonne_poids(
+                        create_record_batch_from_menage_list(
+                            &simulated.menage_input.0.liste_menage_valide,
+                        )?,
+                        &simulated.menage_input.0,
+                    )?,
+                    &simulated.menage_input.0,
                 )?;
 
                 let record_baseline_results =
                     create_record_batch_from_list_dict_results(results_baseline)?;
 
-                let final_record =
-                    create_final_record_batch(&record_menage, &record_baseline_results)?;
+                let final_record = add_run_metadata(
+                    create_final_record_batch(&record_menage, &record_baseline_results)?,
+                    simulated.seed,
+                    &simulated.menage_input.0,
+                )?;
+
+                write_final_record(&final_record, output_path)?;
 
-                write_final_record(&final_record, output_path)
+                self.export_individus_results(
+                    "baseline",
+                    &simulated.menage_input.0,
+                    &simulated.policy_baseline.0.valid_policy,
+                    &simulated.results_individus_baseline,
+                    simulated.seed,
+                )
             }
             None => Err(KalkotoError::from(OutputAdapterError::Custom(
                 "La simulation n'a pas encore été réalisée !".into(),
@@ -94,15 +147,24 @@ impl OutputWriter for ArrowOutputAdapter {
                             _ => String::from("variante-results.arrow"),
                         };
 
-                        let record_menage = create_record_batch_from_menage_list(
-                            &simulated.menage_input.0.liste_menage_valide,
+                        let record_menage = add_colonne_identifiant(
+                            add_colonne_poids(
+                                create_record_batch_from_menage_list(
+                                    &simulated.menage_input.0.liste_menage_valide,
+                                )?,
+                                &simulated.menage_input.0,
+                            )?,
+                            &simulated.menage_input.0,
                         )?;
 
                         let record_variante_results =
                             create_record_batch_from_list_dict_results(results_variante)?;
 
-                        let final_record =
-                            create_final_record_batch(&record_menage, &record_variante_results)?;
+                        let final_record = add_run_metadata(
+                            create_final_record_batch(&record_menage, &record_variante_results)?,
+                            simulated.seed,
+                            &simulated.menage_input.0,
+                        )?;
 
                         write_final_record(&final_record, output_path)
                     }
@@ -126,15 +188,24 @@ impl OutputWriter for ArrowOutputAdapter {
                         _ => String::from("diff-results.arrow"),
                     };
 
-                    let record_menage = create_record_batch_from_menage_list(
-                        &simulated.menage_input.0.liste_menage_valide,
+                    let record_menage = add_colonne_identifiant(
+                        add_colonne_poids(
+                            create_record_batch_from_menage_list(
+                                &simulated.menage_input.0.liste_menage_valide,
+                            )?,
+                            &simulated.menage_input.0,
+                        )?,
+                        &simulated.menage_input.0,
                     )?;
 
                     let record_diff_results =
                         create_record_batch_from_list_dict_results(results_diff)?;
 
-                    let final_record =
-                        create_final_record_batch(&record_menage, &record_diff_results)?;
+                    let final_record = add_run_metadata(
+                        create_final_record_batch(&record_menage, &record_diff_results)?,
+                        simulated.seed,
+                        &simulated.menage_input.0,
+                    )?;
 
                     write_final_record(&final_record, output_path)
                 }
@@ -150,7 +221,21 @@ impl OutputWriter for ArrowOutputAdapter {
             handle.join().unwrap()?
         }
 
-        Ok(())
+        let policy_variante = &simulated.policy_variante.0.valid_policy;
+        output_adapter.export_individus_results(
+            "variante",
+            &simulated.menage_input.0,
+            policy_variante,
+            &simulated.results_individus_variante,
+            simulated.seed,
+        )?;
+        output_adapter.export_individus_results(
+            "diff",
+            &simulated.menage_input.0,
+            policy_variante,
+            &simulated.results_individus_diff,
+            simulated.seed,
+        )
     }
 }
 
@@ -161,7 +246,12 @@ fn create_field_from_caracteristique(
     match caracteristique {
         Caracteristique::Entier(_) => Field::new(nom_caracteristique, DataType::Int32, true),
         Caracteristique::Numeric(_) => Field::new(nom_caracteristique, DataType::Float64, true),
-        Caracteristique::Textuel(_) => Field::new(nom_caracteristique, DataType::Utf8, true),
+        Caracteristique::Booleen(_) => Field::new(nom_caracteristique, DataType::Boolean, true),
+        Caracteristique::Date(_) => Field::new(nom_caracteristique, DataType::Date32, true),
+        // Colonne entièrement manquante : le type d'origine n'est plus connu
+        Caracteristique::Textuel(_) | Caracteristique::Manquant => {
+            Field::new(nom_caracteristique, DataType::Utf8, true)
+        }
     }
 }
 
@@ -252,65 +342,118 @@ fn extract_values_from_menage_list(
         .collect();
     let values = values?;
 
-    values
-        .clone()
-        .into_iter()
-        .map(|colonne| {
-            let mut binding = colonne.iter().peekable();
-            let caracteristique = binding.peek().ok_or_else(|| {
-                KalkotoError::from(OutputAdapterError::Custom("Première colonne vide !".into()))
-            });
-            match caracteristique {
-                Ok(Caracteristique::Entier(_)) => {
-                    let values: Vec<i32> = colonne
+    // Le type de chaque colonne est celui du ménage de référence, les valeurs manquantes
+    // devenant des valeurs nulles
+    caracteristiques_names
+        .iter()
+        .zip(values)
+        .map(|(name, colonne)| {
+            let incompatible = || {
+                KalkotoError::from(OutputAdapterError::Custom(format!(
+                    "Les valeurs de la caractéristique {} n'ont pas toutes le même type",
+                    name
+                )))
+            };
+            match menage_blueprint.caracteristiques.get(*name) {
+                Some(Caracteristique::Entier(_)) => {
+                    let values = colonne
                         .into_iter()
                         .map(|c| match c {
-                            Caracteristique::Entier(i) => i,
-                            _ => unreachable!(),
+                            Caracteristique::Entier(i) => Ok(Some(i)),
+                            Caracteristique::Manquant => Ok(None),
+                            _ => Err(incompatible()),
                         })
-                        .collect();
+                        .collect::<KalkotoResult<Vec<Option<i32>>>>()?;
                     Ok(Arc::new(Int32Array::from(values)) as Arc<dyn Array>)
                 }
-                Ok(Caracteristique::Numeric(_)) => {
-                    let values: Vec<f64> = colonne
+                Some(Caracteristique::Numeric(_)) => {
+                    let values = colonne
                         .into_iter()
                         .map(|c| match c {
-                            Caracteristique::Numeric(i) => i,
-                            _ => unreachable!(),
+                            Caracteristique::Numeric(x) => Ok(Some(x)),
+                            Caracteristique::Manquant => Ok(None),
+                            _ => Err(incompatible()),
                         })
-                        .collect();
+                        .collect::<KalkotoResult<Vec<Option<f64>>>>()?;
                     Ok(Arc::new(Float64Array::from(values)) as Arc<dyn Array>)
                 }
-                Ok(Caracteristique::Textuel(_)) => {
-                    let values: Vec<String> = colonne
+                Some(Caracteristique::Booleen(_)) => {
+                    let values = colonne
                         .into_iter()
                         .map(|c| match c {
-                            Caracteristique::Textuel(i) => i.clone(),
-                            _ => unreachable!(),
+                            Caracteristique::Booleen(b) => Ok(Some(b)),
+                            Caracteristique::Manquant => Ok(None),
+                            _ => Err(incompatible()),
                         })
-                        .collect();
+                        .collect::<KalkotoResult<Vec<Option<bool>>>>()?;
+                    Ok(Arc::new(BooleanArray::from(values)) as Arc<dyn Array>)
+                }
+                Some(Caracteristique::Date(_)) => {
+                    let values = colonne
+                        .into_iter()
+                        .map(|c| match c {
+                            Caracteristique::Date(date) => {
+                                Ok(Some(Date32Type::from_naive_date(date)))
+                            }
+                            Caracteristique::Manquant => Ok(None),
+                            _ => Err(incompatible()),
+                        })
+                        .collect::<KalkotoResult<Vec<Option<i32>>>>()?;
+                    Ok(Arc::new(Date32Array::from(values)) as Arc<dyn Array>)
+                }
+                Some(Caracteristique::Textuel(_) | Caracteristique::Manquant) => {
+                    let values = colonne
+                        .into_iter()
+                        .map(|c| match c {
+                            Caracteristique::Textuel(texte) => Ok(Some(texte)),
+                            Caracteristique::Manquant => Ok(None),
+                            _ => Err(incompatible()),
+                        })
+                        .collect::<KalkotoResult<Vec<Option<String>>>>()?;
                     Ok(Arc::new(StringArray::from(values)) as Arc<dyn Array>)
                 }
-                _ => Err(From::from(OutputAdapterError::Custom("Les types des caractéristiques des ménages ne sont pas pris en charge (entier, double ou string)".into()))),
+                None => Err(From::from(OutputAdapterError::Custom(format!(
+                    "Caractéristique {} manquante",
+                    name
+                )))),
             }
         })
         .collect()
 }
 
+// Ménage dont chaque caractéristique manquante est remplacée par la première valeur
+// renseignée de la colonne, pour déterminer le type des colonnes
+fn menage_representatif(menage_list: &[Menage]) -> Option<Menage> {
+    let mut representatif = menage_list.first()?.clone();
+
+    for (nom, caracteristique) in representatif.caracteristiques.iter_mut() {
+        if *caracteristique == Caracteristique::Manquant {
+            if let Some(renseignee) = menage_list
+                .iter()
+                .filter_map(|menage| menage.caracteristiques.get(nom))
+                .find(|valeur| **valeur != Caracteristique::Manquant)
+            {
+                *caracteristique = renseignee.clone();
+            }
+        }
+    }
+
+    Some(representatif)
+}
+
 fn create_record_batch_from_menage_list(menage_list: &[Menage]) -> KalkotoResult<RecordBatch> {
     match validate_menage_list(menage_list)? {
         true => {
-            let mut peekable = menage_list.iter().peekable();
-            let first_valid_menage = peekable.peek().ok_or_else(|| {
+            let first_valid_menage = menage_representatif(menage_list).ok_or_else(|| {
                 KalkotoError::from(OutputAdapterError::Custom(
                     "Aucun ménage dans le fichier d'input".into(),
                 ))
             })?;
 
             //Extraction du schema
-            let schema = extract_schema_from_menage(first_valid_menage)?;
+            let schema = extract_schema_from_menage(&first_valid_menage)?;
 
-            let arrays_data = extract_values_from_menage_list(first_valid_menage, menage_list)?;
+            let arrays_data = extract_values_from_menage_list(&first_valid_menage, menage_list)?;
 
             RecordBatch::try_new(Arc::new(schema), arrays_data)
                 .map_err(|e| KalkotoError::from(OutputAdapterError::Arrow(e)))
@@ -460,6 +603,165 @@ pub fn create_final_record_batch(
     }
 }
 
+// Les ménages identifiés par une colonne de l'input sont exportés avec cette colonne en tête,
+// pour joindre les résultats aux données d'origine
+pub fn add_colonne_identifiant(
+    menage_record: RecordBatch,
+    menage_input: &MenageInput,
+) -> KalkotoResult<RecordBatch> {
+    let Some(colonne_identifiant) = &menage_input.colonne_identifiant else {
+        return Ok(menage_record);
+    };
+
+    let identifiants: ArrayRef = Arc::new(StringArray::from_iter_values(
+        menage_input.liste_menage_valide.iter().map(Menage::cle),
+    ));
+
+    let fields: Vec<Arc<Field>> = std::iter::once(Arc::new(Field::new(
+        colonne_identifiant,
+        DataType::Utf8,
+        false,
+    )))
+    .chain(menage_record.schema().fields().iter().cloned())
+    .collect();
+    let columns: Vec<ArrayRef> = std::iter::once(identifiants)
+        .chain(menage_record.columns().iter().cloned())
+        .collect();
+
+    Ok(RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)
+        .map_err(OutputAdapterError::Arrow)?)
+}
+
+// Le poids de sondage des ménages est exporté en tête de leurs caractéristiques, juste après
+// la colonne identifiant
+pub fn add_colonne_poids(
+    menage_record: RecordBatch,
+    menage_input: &MenageInput,
+) -> KalkotoResult<RecordBatch> {
+    let Some((field, poids)) = colonne_poids(&menage_input.liste_menage_valide, menage_input)
+    else {
+        return Ok(menage_record);
+    };
+
+    let fields: Vec<Arc<Field>> = std::iter::once(field)
+        .chain(menage_record.schema().fields().iter().cloned())
+        .collect();
+    let columns: Vec<ArrayRef> = std::iter::once(poids)
+        .chain(menage_record.columns().iter().cloned())
+        .collect();
+
+    Ok(RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)
+        .map_err(OutputAdapterError::Arrow)?)
+}
+
+fn colonne_poids(menages: &[Menage], menage_input: &MenageInput) -> Option<(Arc<Field>, ArrayRef)> {
+    let colonne_poids = menage_input.colonne_poids.as_deref()?;
+    let poids: ArrayRef = Arc::new(Float64Array::from_iter_values(
+        menages.iter().map(Menage::ponderation),
+    ));
+    Some((
+        Arc::new(Field::new(colonne_poids, DataType::Float64, false)),
+        poids,
+    ))
+}
+
+// Une ligne par individu : la clé de son ménage, l'index de sa ligne dans la table des
+// individus, ses caractéristiques et les composantes de niveau individu
+fn create_record_batch_individus<V: Default + AllowedValue + Copy + 'static>(
+    menage_input: &MenageInput,
+    results_individus: &[Vec<HashMap<String, V>>],
+) -> KalkotoResult<RecordBatch> {
+    let individus: Vec<Menage> = menage_input
+        .liste_menage_valide
+        .iter()
+        .flat_map(|menage| {
+            menage.individus.iter().map(|individu| Menage {
+                index: individu.index,
+                identifiant: Some(menage.cle()),
+                poids: menage.poids,
+                caracteristiques: individu.caracteristiques.clone(),
+                individus: vec![],
+            })
+        })
+        .collect();
+    let record_individus = create_record_batch_from_menage_list(&individus)?;
+
+    let cles: ArrayRef = Arc::new(StringArray::from_iter_values(
+        individus.iter().map(Menage::cle),
+    ));
+    let index: ArrayRef = Arc::new(Int32Array::from_iter_values(
+        individus.iter().map(|individu| individu.index),
+    ));
+    let (field_poids, poids) = colonne_poids(&individus, menage_input).unzip();
+    let fields: Vec<Arc<Field>> = std::iter::once(Arc::new(Field::new(
+        menage_input
+            .colonne_identifiant
+            .as_deref()
+            .unwrap_or("Index"),
+        DataType::Utf8,
+        false,
+    )))
+    .chain(field_poids)
+    .chain([Arc::new(Field::new(
+        COLONNE_INDIVIDU,
+        DataType::Int32,
+        false,
+    ))])
+    .chain(record_individus.schema().fields().iter().cloned())
+    .collect();
+    let columns: Vec<ArrayRef> = std::iter::once(cles)
+        .chain(poids)
+        .chain([index])
+        .chain(record_individus.columns().iter().cloned())
+        .collect();
+    let record_individus = RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)
+        .map_err(OutputAdapterError::Arrow)?;
+
+    let results_individus: Vec<HashMap<String, V>> =
+        results_individus.iter().flatten().cloned().collect();
+    create_final_record_batch(
+        &record_individus,
+        &create_record_batch_from_list_dict_results(&results_individus)?,
+    )
+}
+
+// La graine des tirages aléatoires, le filtre et les poids des ménages sont enregistrés dans les
+// métadonnées du schéma, pour pouvoir reproduire la simulation
+pub fn add_run_metadata(
+    final_record: RecordBatch,
+    seed: u64,
+    menage_input: &MenageInput,
+) -> KalkotoResult<RecordBatch> {
+    let mut metadata = HashMap::from([("seed".to_string(), seed.to_string())]);
+    if let Some(filtre) = &menage_input.filtre {
+        metadata.insert("filtre".to_string(), filtre.to_string());
+        metadata.insert(
+            "menages_gardes".to_string(),
+            menage_input.liste_menage_valide.len().to_string(),
+        );
+        metadata.insert(
+            "menages_ecartes".to_string(),
+            menage_input.menages_ecartes.to_string(),
+        );
+    }
+    if let Some(colonne_poids) = &menage_input.colonne_poids {
+        metadata.insert("colonne_poids".to_string(), colonne_poids.clone());
+        metadata.insert(
+            "effectif_pondere".to_string(),
+            menage_input.effectif_pondere().to_string(),
+        );
+    }
+    let schema = final_record
+        .schema()
+        .as_ref()
+        .clone()
+        .with_metadata(metadata);
+
+    Ok(final_record
+        .with_schema(Arc::new(schema))
+        .map_err(OutputAdapterError::Arrow)?)
+}
+
 pub fn write_final_record<P>(final_record: &RecordBatch, output_path: P) -> KalkotoResult<()>
 where
     P: AsRef<Path>,
@@ -482,6 +784,9 @@ where
 mod tests {
 
     use super::*;
+    use crate::entities::menage::Individu;
+    use arrow::array::AsArray;
+    use chrono::NaiveDate;
 
     #[test]
     fn test_menage_list_valid_for_valide_liste() -> KalkotoResult<()> {
@@ -630,4 +935,152 @@ mod tests {
         assert_eq!(wanted, result);
         Ok(())
     }
+
+    #[test]
+    fn ok_valeurs_manquantes_arrow_aller_retour() -> KalkotoResult<()> {
+        use crate::adapters::input_adapters::arrow_input_adapter::extract_values_from_arrow;
+
+        let revenus = [
+            Caracteristique::Manquant,
+            Caracteristique::Numeric(400.45f64),
+            Caracteristique::Manquant,
+        ];
+        let menage_list: Vec<Menage> = revenus
+            .iter()
+            .enumerate()
+            .map(|(index, revenu)| {
+                let mut menage = Menage::new(index as i32 + 1);
+                menage
+                    .caracteristiques
+                    .insert(String::from("Revenu"), revenu.clone());
+                menage
+            })
+            .collect();
+
+        let record_batch = create_record_batch_from_menage_list(&menage_list)?;
+        let colonne = record_batch
+            .column_by_name("Revenu")
+            .expect("colonne Revenu exportée")
+            .clone();
+
+        assert_eq!(&DataType::Float64, colonne.data_type());
+        assert_eq!(2, colonne.null_count());
+        assert_eq!(
+            revenus.to_vec(),
+            extract_values_from_arrow("Revenu", colonne, 1)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn ok_colonne_identifiant_en_tete() -> KalkotoResult<()> {
+        let liste_menage_valide: Vec<Menage> = ["0012", "B7"]
+            .iter()
+            .enumerate()
+            .map(|(index, identifiant)| {
+                let mut menage = Menage::new(index as i32 + 1);
+                menage.identifiant = Some(identifiant.to_string());
+                menage
+                    .caracteristiques
+                    .insert(String::from("Age"), Caracteristique::Entier(80));
+                menage
+            })
+            .collect();
+        let menage_input = MenageInput {
+            liste_menage_valide,
+            colonne_identifiant: Some("Dossier".into()),
+            ..MenageInput::default()
+        };
+
+        let record_batch = add_colonne_identifiant(
+            create_record_batch_from_menage_list(&menage_input.liste_menage_valide)?,
+            &menage_input,
+        )?;
+
+        assert_eq!("Dossier", record_batch.schema().field(0).name());
+        assert_eq!(
+            &(Arc::new(StringArray::from(vec!["0012", "B7"])) as ArrayRef),
+            record_batch.column(0)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn ok_individus_cle_du_menage() -> KalkotoResult<()> {
+        let mut menage = Menage::new(1);
+        menage.identifiant = Some("B7".into());
+        menage.individus = [(1, 40), (2, 12)]
+            .into_iter()
+            .map(|(index, age)| Individu {
+                index,
+                caracteristiques: HashMap::from([(
+                    "Age".to_string(),
+                    Caracteristique::Entier(age),
+                )]),
+            })
+            .collect();
+        let menage_input = MenageInput {
+            liste_menage_valide: vec![menage],
+            colonne_identifiant: Some("Dossier".into()),
+            ..MenageInput::default()
+        };
+        let results_individus = vec![vec![
+            HashMap::from([("bonification".to_string(), 150.0)]),
+            HashMap::from([("bonification".to_string(), 0.0)]),
+        ]];
+
+        let record_batch = create_record_batch_individus(&menage_input, &results_individus)?;
+
+        assert_eq!(2, record_batch.num_rows());
+        assert_eq!("Dossier", record_batch.schema().field(0).name());
+        assert_eq!(COLONNE_INDIVIDU, record_batch.schema().field(1).name());
+        assert_eq!(
+            &(Arc::new(StringArray::from(vec!["B7", "B7"])) as ArrayRef),
+            record_batch.column(0)
+        );
+        assert!(record_batch
+            .schema()
+            .column_with_name("bonification")
+            .is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn ok_colonnes_booleen_et_date() -> KalkotoResult<()> {
+        let naissances = [
+            NaiveDate::from_ymd_opt(1950, 7, 14),
+            NaiveDate::from_ymd_opt(1948, 2, 1),
+        ];
+        let menages: Vec<Menage> = [Some(true), None]
+            .into_iter()
+            .zip(naissances)
+            .enumerate()
+            .map(|(index, (locataire, naissance))| {
+                let mut menage = Menage::new(index as i32 + 1);
+                menage.caracteristiques.insert(
+                    "Locataire".into(),
+                    locataire.map_or(Caracteristique::Manquant, Caracteristique::Booleen),
+                );
+                menage.caracteristiques.insert(
+                    "Naissance".into(),
+                    Caracteristique::Date(naissance.unwrap()),
+                );
+                menage
+            })
+            .collect();
+
+        let record_batch = create_record_batch_from_menage_list(&menages)?;
+
+        assert_eq!(
+            &(Arc::new(BooleanArray::from(vec![Some(true), None])) as ArrayRef),
+            record_batch.column(0)
+        );
+        assert_eq!(
+            &DataType::Date32,
+            record_batch.schema().field(1).data_type()
+        );
+        let dates = record_batch.column(1).as_primitive::<Date32Type>();
+        assert_eq!(naissances[1], dates.value_as_date(1));
+        Ok(())
+    }
 }
@@ -1,9 +1,21 @@
-use std::{collections::HashMap, sync::Arc, thread};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io,
+    sync::Arc,
+    thread,
+};
 
 use crate::{
     adapters::output_adapters::{OutputAdapterError, OutputWriter},
-    entities::simulator::{
-        SimulationError, SimulatorBuilder, ValidBaselineInput, ValidMenageInput, ValidVarianteInput,
+    entities::{
+        menage::Menage,
+        menage_input::MenageInput,
+        policy::{Niveau, Policy, ResultatsIndividus},
+        simulator::{
+            SimulationError, SimulatorBuilder, ValidBaselineInput, ValidMenageInput,
+            ValidVarianteInput,
+        },
     },
     KalkotoError, KalkotoResult,
 };
@@ -34,99 +46,8 @@ impl CSVOutputAdapter {
             _ => String::from("variante-results.csv"),
         };
 
-        if let Some(variante_results) = &simulated.results_variante {
-            let text_results_dict: Vec<HashMap<&str, String>> = variante_results
-                .iter()
-                .map(|menage_results| {
-                    menage_results
-                        .iter()
-                        .map(|(k, v)| (k.as_str(), format!("{}", v)))
-                        .collect()
-                })
-                .collect();
-
-            let mut text_caract_dict: Vec<HashMap<&str, String>> = simulated
-                .menage_input
-                .0
-                .liste_menage_valide
-                .iter()
-                .map(|menage| {
-                    menage
-                        .caracteristiques
-                        .iter()
-                        .map(|(k, v)| (k.as_str(), format!("{}", v)))
-                        .collect()
-                })
-                .collect();
-
-            let joined_menages_variante_results: Vec<HashMap<&str, String>> = text_caract_dict
-                .into_iter()
-                .zip(text_results_dict)
-                .map(|(mut caract_dict, results_dict)| {
-                    caract_dict.extend(results_dict);
-                    caract_dict
-                })
-                .collect();
-
-            let mut wtr_var = WriterBuilder::new()
-                .delimiter(b';')
-                .from_path(output_path_var)
-                .map_err(OutputAdapterError::from)?;
-
-            let mut headers = simulated
-                .policy_variante
-                .0
-                .valid_policy
-                .composantes_ordonnees
-                .iter()
-                .map(|composante| composante.name.as_str())
-                .chain(
-                    simulated
-                        .menage_input
-                        .0
-                        .set_caracteristiques_valide
-                        .iter()
-                        .map(|caracteristique_name| caracteristique_name.as_str()),
-                )
-                .collect::<Vec<&str>>();
-
-            headers.sort_unstable();
-
-            headers.insert(0, "Index");
-
-            wtr_var.write_record(&headers);
-
-            headers.remove(0);
-
-            for (index, results_menage_variante) in
-                joined_menages_variante_results.into_iter().enumerate()
-            {
-                let mut vec_results_menage_variante = vec![];
-                for name in headers.iter() {
-                    let out_variante_result = results_menage_variante
-                        .get(name)
-                        .ok_or(SimulationError::ResultsError(format!(
-                            "Problème de cohérence des composantes lors de l'export. Erreur à la composante : {}",
-                            name
-                        )))?
-                        ;
-
-                    vec_results_menage_variante.push(out_variante_result);
-                }
-
-                let index_mod = (index + 1).to_string();
-                vec_results_menage_variante.insert(0, &index_mod);
-
-                wtr_var.write_record(&vec_results_menage_variante);
-            }
-
-            wtr_var.flush().map_err(OutputAdapterError::from)?;
-            return Ok(());
-        }
-
-        Err(KalkotoError::SimError(SimulationError::ResultsError(
-            "Pas possible d'exporter : les résultats n'ont pas encore été calculés".to_string(),
-        )))
+        let file = File::create(&output_path_var).map_err(OutputAdapterError::from)?;
+        write_variante_results(&simulated, file)
     }
 
     fn export_diff_results(
@@ -138,102 +59,384 @@ impl CSVOutputAdapter {
             _ => String::from("diff-results.csv"),
         };
 
-        if let Some(diff_results) = &simulated.results_diff {
-            let text_results_dict: Vec<HashMap<&str, String>> = diff_results
-                .iter()
-                .map(|menage_results| {
-                    menage_results
-                        .iter()
-                        .map(|(k, v)| match (k, v) {
-                            (k, Some(v)) => (k.as_str(), format!("{}", v)),
-                            (k, None) => (k.as_str(), String::default()),
-                        })
-                        .collect()
-                })
-                .collect();
-
-            let mut text_caract_dict: Vec<HashMap<&str, String>> = simulated
-                .menage_input
-                .0
-                .liste_menage_valide
-                .iter()
-                .map(|menage| {
-                    menage
-                        .caracteristiques
-                        .iter()
-                        .map(|(k, v)| (k.as_str(), format!("{}", v)))
-                        .collect()
-                })
-                .collect();
-
-            let joined_menages_diff_results: Vec<HashMap<&str, String>> = text_caract_dict
+        let file = File::create(&output_path_diff).map_err(OutputAdapterError::from)?;
+        write_diff_results(&simulated, file)
+    }
+
+    // Résultats de niveau individu, dans {prefix}-{nom_simulation}-individus.csv
+    fn export_individus_results<V>(
+        &self,
+        nom_simulation: &str,
+        menage_input: &MenageInput,
+        policy: &Policy,
+        results_individus: &Option<ResultatsIndividus<V>>,
+        formate: fn(&V) -> String,
+    ) -> KalkotoResult<()> {
+        let Some(results_individus) = results_individus else {
+            return Ok(());
+        };
+        if !policy.a_composantes_individus() {
+            return Ok(());
+        }
+
+        let output_path = match &self.output_prefix {
+            Some(output_prefix) => format!("{}-{}-individus.csv", output_prefix, nom_simulation),
+            _ => format!("{}-individus.csv", nom_simulation),
+        };
+
+        let file = File::create(&output_path).map_err(OutputAdapterError::from)?;
+        write_individus_results(menage_input, policy, results_individus, formate, file)
+    }
+}
+
+// Colonnes en tête des résultats : la clé des ménages (la colonne identifiant, ou à défaut
+// leur index), puis leur poids si une colonne de poids est choisie
+fn colonnes_tete(menage_input: &MenageInput) -> Vec<&str> {
+    std::iter::once(
+        menage_input
+            .colonne_identifiant
+            .as_deref()
+            .unwrap_or("Index"),
+    )
+    .chain(menage_input.colonne_poids.as_deref())
+    .collect()
+}
+
+fn valeurs_tete(menage_input: &MenageInput, menage: &Menage) -> Vec<String> {
+    std::iter::once(menage.cle())
+        .chain(
+            menage_input
+                .colonne_poids
+                .as_ref()
+                .map(|_| menage.ponderation().to_string()),
+        )
+        .collect()
+}
+
+// Colonne des résultats de niveau individu donnant l'index de l'individu
+pub const COLONNE_INDIVIDU: &str = "Individu";
+
+// Noms triés des colonnes de résultats d'un niveau : caractéristiques et composantes
+fn noms_colonnes<'a>(
+    policy: &'a Policy,
+    niveau: Niveau,
+    caracteristiques: &'a HashSet<String>,
+) -> Vec<&'a str> {
+    let mut noms = policy
+        .composantes_ordonnees
+        .iter()
+        .filter(|composante| composante.niveau == niveau)
+        .map(|composante| composante.name.as_str())
+        .chain(caracteristiques.iter().map(String::as_str))
+        .collect::<Vec<&str>>();
+    noms.sort_unstable();
+    noms
+}
+
+// Une ligne par individu : la clé de son ménage, l'index de sa ligne dans la table des
+// individus, ses caractéristiques et les composantes de niveau individu
+pub fn write_individus_results<V, W: io::Write>(
+    menage_input: &MenageInput,
+    policy: &Policy,
+    results_individus: &[Vec<HashMap<String, V>>],
+    formate: fn(&V) -> String,
+    writer: W,
+) -> KalkotoResult<()> {
+    let headers = noms_colonnes(
+        policy,
+        Niveau::Individu,
+        &menage_input.set_caracteristiques_individus,
+    );
+    let mut wtr = WriterBuilder::new().delimiter(b';').from_writer(writer);
+    wtr.write_record(
+        colonnes_tete(menage_input)
+            .into_iter()
+            .chain([COLONNE_INDIVIDU])
+            .chain(headers.iter().copied()),
+    )
+    .map_err(OutputAdapterError::from)?;
+
+    for (menage, results_menage) in menage_input
+        .liste_menage_valide
+        .iter()
+        .zip(results_individus)
+    {
+        for (individu, results_individu) in menage.individus.iter().zip(results_menage) {
+            let mut ligne = valeurs_tete(menage_input, menage);
+            ligne.push(individu.index.to_string());
+            for name in headers.iter() {
+                let valeur = match (individu.caracteristiques.get(*name), results_individu.get(*name)) {
+                    (Some(caracteristique), _) => caracteristique.to_string(),
+                    (None, Some(valeur)) => formate(valeur),
+                    (None, None) => {
+                        return Err(From::from(SimulationError::ResultsError(format!(
+                            "Problème de cohérence des composantes lors de l'export. Erreur à la composante : {}",
+                            name
+                        ))))
+                    }
+                };
+                ligne.push(valeur);
+            }
+            wtr.write_record(&ligne).map_err(OutputAdapterError::from)?;
+        }
+    }
+
+    wtr.flush().map_err(OutputAdapterError::from)?;
+    Ok(())
+}
+
+// Écriture des résultats de la baseline au format CSV, dans un fichier ou en mémoire
+pub fn write_baseline_results<E, W: io::Write>(
+    simulated: &SimulatorBuilder<ValidMenageInput, ValidBaselineInput, E>,
+    writer: W,
+) -> KalkotoResult<()> {
+    if let Some(baseline_results) = &simulated.results_baseline {
+        let text_results_dict: Vec<HashMap<&str, String>> = baseline_results
+            .iter()
+            .map(|menage_results| {
+                menage_results
+                    .iter()
+                    .map(|(k, v)| (k.as_str(), format!("{}", v)))
+                    .collect()
+            })
+            .collect();
+
+        let mut text_caract_dict: Vec<HashMap<&str, String>> = simulated
+            .menage_input
+            .0
+            .liste_menage_valide
+            .iter()
+            .map(|menage| {
+                menage
+                    .caracteristiques
+                    .iter()
+                    .map(|(k, v)| (k.as_str(), format!("{}", v)))
+                    .collect()
+            })
+            .collect();
+
+        let joined_menages_baseline_results: Vec<HashMap<&str, String>> = text_caract_dict
+            .into_iter()
+            .zip(text_results_dict)
+            .map(|(mut caract_dict, results_dict)| {
+                caract_dict.extend(results_dict);
+                caract_dict
+            })
+            .collect();
+
+        let mut wtr = WriterBuilder::new().delimiter(b';').from_writer(writer);
+
+        let headers = noms_colonnes(
+            &simulated.policy_baseline.0.valid_policy,
+            Niveau::Menage,
+            &simulated.menage_input.0.set_caracteristiques_valide,
+        );
+
+        wtr.write_record(
+            colonnes_tete(&simulated.menage_input.0)
                 .into_iter()
-                .zip(text_results_dict)
-                .map(|(mut caract_dict, results_dict)| {
-                    caract_dict.extend(results_dict);
-                    caract_dict
-                })
-                .collect();
-
-            let mut wtr_diff = WriterBuilder::new()
-                .delimiter(b';')
-                .from_path(output_path_diff)
-                .map_err(OutputAdapterError::from)?;
-
-            let mut headers = simulated
-                .policy_variante
-                .0
-                .valid_policy
-                .composantes_ordonnees
-                .iter()
-                .map(|composante| composante.name.as_str())
-                .chain(
-                    simulated
-                        .menage_input
-                        .0
-                        .set_caracteristiques_valide
-                        .iter()
-                        .map(|caracteristique_name| caracteristique_name.as_str()),
-                )
-                .collect::<Vec<&str>>();
-
-            headers.sort_unstable();
-
-            headers.insert(0, "Index");
-
-            wtr_diff.write_record(&headers);
-
-            headers.remove(0);
-
-            for (index, results_menage_diff) in joined_menages_diff_results.iter().enumerate() {
-                let mut vec_results_menage_diff = vec![];
-                for name in headers.iter() {
-                    let out_diff_result = results_menage_diff
+                .chain(headers.iter().copied()),
+        );
+
+        for (index, results_menage) in joined_menages_baseline_results.iter().enumerate() {
+            let mut vec_results_menage = vec![];
+            for name in headers.iter() {
+                vec_results_menage.push(
+                    results_menage
                         .get(name)
                         .ok_or_else(|| {
-                            SimulationError::ResultsError(format!(
-                            "Problème de cohérence des composantes lors de l'export. Erreur à la composante : {}",
-                            name
-                        ))
-                        })?;
+                            SimulationError::ResultsError(
+                                format!("Problème de cohérence des composantes lors de l'export. Erreur à la composante : {}",name)
+                            )
+                        })?);
+            }
+
+            let tete = valeurs_tete(
+                &simulated.menage_input.0,
+                &simulated.menage_input.0.liste_menage_valide[index],
+            );
+            wtr.write_record(tete.iter().chain(vec_results_menage));
+        }
 
-                    vec_results_menage_diff.push(out_diff_result);
-                }
+        wtr.flush().map_err(OutputAdapterError::from)?;
+        return Ok(());
+    }
 
-                let index_mod = (index + 1).to_string();
-                vec_results_menage_diff.insert(0, &index_mod);
+    Err(KalkotoError::SimError(SimulationError::ResultsError(
+        "Pas possible d'exporter : les résultats n'ont pas encore été calculés".to_string(),
+    )))
+}
 
-                wtr_diff.write_record(&vec_results_menage_diff);
+// Écriture des résultats de la variante au format CSV, dans un fichier ou en mémoire
+pub fn write_variante_results<W: io::Write>(
+    simulated: &SimulatorBuilder<ValidMenageInput, ValidBaselineInput, ValidVarianteInput>,
+    writer: W,
+) -> KalkotoResult<()> {
+    if let Some(variante_results) = &simulated.results_variante {
+        let text_results_dict: Vec<HashMap<&str, String>> = variante_results
+            .iter()
+            .map(|menage_results| {
+                menage_results
+                    .iter()
+                    .map(|(k, v)| (k.as_str(), format!("{}", v)))
+                    .collect()
+            })
+            .collect();
+
+        let mut text_caract_dict: Vec<HashMap<&str, String>> = simulated
+            .menage_input
+            .0
+            .liste_menage_valide
+            .iter()
+            .map(|menage| {
+                menage
+                    .caracteristiques
+                    .iter()
+                    .map(|(k, v)| (k.as_str(), format!("{}", v)))
+                    .collect()
+            })
+            .collect();
+
+        let joined_menages_variante_results: Vec<HashMap<&str, String>> = text_caract_dict
+            .into_iter()
+            .zip(text_results_dict)
+            .map(|(mut caract_dict, results_dict)| {
+                caract_dict.extend(results_dict);
+                caract_dict
+            })
+            .collect();
+
+        let mut wtr_var = WriterBuilder::new().delimiter(b';').from_writer(writer);
+
+        let headers = noms_colonnes(
+            &simulated.policy_variante.0.valid_policy,
+            Niveau::Menage,
+            &simulated.menage_input.0.set_caracteristiques_valide,
+        );
+
+        wtr_var.write_record(
+            colonnes_tete(&simulated.menage_input.0)
+                .into_iter()
+                .chain(headers.iter().copied()),
+        );
+
+        for (index, results_menage_variante) in
+            joined_menages_variante_results.into_iter().enumerate()
+        {
+            let mut vec_results_menage_variante = vec![];
+            for name in headers.iter() {
+                let out_variante_result = results_menage_variante
+                    .get(name)
+                    .ok_or(SimulationError::ResultsError(format!(
+                        "Problème de cohérence des composantes lors de l'export. Erreur à la composante : {}",
+                        name
+                    )))?
+                    ;
+
+                vec_results_menage_variante.push(out_variante_result);
             }
 
-            wtr_diff.flush().map_err(OutputAdapterError::from)?;
-            return Ok(());
+            let tete = valeurs_tete(
+                &simulated.menage_input.0,
+                &simulated.menage_input.0.liste_menage_valide[index],
+            );
+            wtr_var.write_record(tete.iter().chain(vec_results_menage_variante));
+        }
+
+        wtr_var.flush().map_err(OutputAdapterError::from)?;
+        return Ok(());
+    }
+
+    Err(KalkotoError::SimError(SimulationError::ResultsError(
+        "Pas possible d'exporter : les résultats n'ont pas encore été calculés".to_string(),
+    )))
+}
+
+// Écriture des écarts variante - baseline au format CSV, dans un fichier ou en mémoire
+pub fn write_diff_results<W: io::Write>(
+    simulated: &SimulatorBuilder<ValidMenageInput, ValidBaselineInput, ValidVarianteInput>,
+    writer: W,
+) -> KalkotoResult<()> {
+    if let Some(diff_results) = &simulated.results_diff {
+        let text_results_dict: Vec<HashMap<&str, String>> = diff_results
+            .iter()
+            .map(|menage_results| {
+                menage_results
+                    .iter()
+                    .map(|(k, v)| match (k, v) {
+                        (k, Some(v)) => (k.as_str(), format!("{}", v)),
+                        (k, None) => (k.as_str(), String::default()),
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let mut text_caract_dict: Vec<HashMap<&str, String>> = simulated
+            .menage_input
+            .0
+            .liste_menage_valide
+            .iter()
+            .map(|menage| {
+                menage
+                    .caracteristiques
+                    .iter()
+                    .map(|(k, v)| (k.as_str(), format!("{}", v)))
+                    .collect()
+            })
+            .collect();
+
+        let joined_menages_diff_results: Vec<HashMap<&str, String>> = text_caract_dict
+            .into_iter()
+            .zip(text_results_dict)
+            .map(|(mut caract_dict, results_dict)| {
+                caract_dict.extend(results_dict);
+                caract_dict
+            })
+            .collect();
+
+        let mut wtr_diff = WriterBuilder::new().delimiter(b';').from_writer(writer);
+
+        let headers = noms_colonnes(
+            &simulated.policy_variante.0.valid_policy,
+            Niveau::Menage,
+            &simulated.menage_input.0.set_caracteristiques_valide,
+        );
+
+        wtr_diff.write_record(
+            colonnes_tete(&simulated.menage_input.0)
+                .into_iter()
+                .chain(headers.iter().copied()),
+        );
+
+        for (index, results_menage_diff) in joined_menages_diff_results.iter().enumerate() {
+            let mut vec_results_menage_diff = vec![];
+            for name in headers.iter() {
+                let out_diff_result = results_menage_diff
+                    .get(name)
+                    .ok_or_else(|| {
+                        SimulationError::ResultsError(format!(
+                        "Problème de cohérence des composantes lors de l'export. Erreur à la composante : {}",
+                        name
+                    ))
+                    })?;
+
+                vec_results_menage_diff.push(out_diff_result);
+            }
+
+            let tete = valeurs_tete(
+                &simulated.menage_input.0,
+                &simulated.menage_input.0.liste_menage_valide[index],
+            );
+            wtr_diff.write_record(tete.iter().chain(vec_results_menage_diff));
         }
 
-        Err(KalkotoError::SimError(SimulationError::ResultsError(
-            "Pas possible d'exporter : les résultats n'ont pas encore été calculés".to_string(),
-        )))
+        wtr_diff.flush().map_err(OutputAdapterError::from)?;
+        return Ok(());
     }
+
+    Err(KalkotoError::SimError(SimulationError::ResultsError(
+        "Pas possible d'exporter : les résultats n'ont pas encore été calculés".to_string(),
+    )))
 }
 
 impl OutputWriter for CSVOutputAdapter {
@@ -246,95 +449,39 @@ impl OutputWriter for CSVOutputAdapter {
             _ => String::from("baseline-results.csv"),
         };
 
-        if let Some(baseline_results) = &simulated.results_baseline {
-            let text_results_dict: Vec<HashMap<&str, String>> = baseline_results
-                .iter()
-                .map(|menage_results| {
-                    menage_results
-                        .iter()
-                        .map(|(k, v)| (k.as_str(), format!("{}", v)))
-                        .collect()
-                })
-                .collect();
-
-            let mut text_caract_dict: Vec<HashMap<&str, String>> = simulated
-                .menage_input
-                .0
-                .liste_menage_valide
-                .iter()
-                .map(|menage| {
-                    menage
-                        .caracteristiques
-                        .iter()
-                        .map(|(k, v)| (k.as_str(), format!("{}", v)))
-                        .collect()
-                })
-                .collect();
-
-            let joined_menages_baseline_results: Vec<HashMap<&str, String>> = text_caract_dict
-                .into_iter()
-                .zip(text_results_dict)
-                .map(|(mut caract_dict, results_dict)| {
-                    caract_dict.extend(results_dict);
-                    caract_dict
-                })
-                .collect();
-
-            let mut wtr = WriterBuilder::new()
-                .delimiter(b';')
-                .from_path(output_path)
-                .map_err(OutputAdapterError::from)?;
-
-            let mut headers = simulated
-                .policy_baseline
-                .0
-                .valid_policy
-                .composantes_ordonnees
-                .iter()
-                .map(|composante| composante.name.as_str())
-                .chain(
-                    simulated
-                        .menage_input
-                        .0
-                        .set_caracteristiques_valide
-                        .iter()
-                        .map(|caracteristique_name| caracteristique_name.as_str()),
-                )
-                .collect::<Vec<&str>>();
-
-            headers.sort_unstable();
-
-            headers.insert(0, "Index");
-
-            wtr.write_record(&headers);
-
-            headers.remove(0);
-
-            for (index, results_menage) in joined_menages_baseline_results.iter().enumerate() {
-                let mut vec_results_menage = vec![];
-                for name in headers.iter() {
-                    vec_results_menage.push(
-                        results_menage
-                            .get(name)
-                            .ok_or_else(|| {
-                                SimulationError::ResultsError(
-                                    format!("Problème de cohérence des composantes lors de l'export. Erreur à la composante : {}",name)
-                                )
-                            })?);
-                }
-
-                let index_mod = (index + 1).to_string();
-                vec_results_menage.insert(0, &index_mod);
-                wtr.write_record(&vec_results_menage);
-            }
-
-            wtr.flush().map_err(OutputAdapterError::from)?;
-            return Ok(());
+        let file = File::create(&output_path).map_err(OutputAdapterError::from)?;
+        write_baseline_results(simulated, file)?;
+
+        self.export_individus_results(
+            "baseline",
+            &simulated.menage_input.0,
+            &simulated.policy_baseline.0.valid_policy,
+            &simulated.results_individus_baseline,
+            f64::to_string,
+        )?;
+
+        // Le CSV n'ayant pas de métadonnées, la graine et le filtre des ménages sont
+        // enregistrés à côté des résultats
+        let output_path_metadata = match &self.output_prefix {
+            Some(output_prefix) => format!("{}-metadata.json", output_prefix),
+            _ => String::from("metadata.json"),
+        };
+        let mut metadata = serde_json::json!({ "seed": simulated.seed });
+        let menage_input = &simulated.menage_input.0;
+        if let Some(filtre) = &menage_input.filtre {
+            metadata["filtre"] = serde_json::json!(filtre.to_string());
+            metadata["menages_gardes"] = serde_json::json!(menage_input.liste_menage_valide.len());
+            metadata["menages_ecartes"] = serde_json::json!(menage_input.menages_ecartes);
+        }
+        if let Some(colonne_poids) = &menage_input.colonne_poids {
+            metadata["colonne_poids"] = serde_json::json!(colonne_poids);
+            metadata["effectif_pondere"] = serde_json::json!(menage_input.effectif_pondere());
         }
+        let file = File::create(&output_path_metadata).map_err(OutputAdapterError::from)?;
+        serde_json::to_writer_pretty(file, &metadata)
+            .map_err(|e| OutputAdapterError::Custom(e.to_string()))?;
 
-        Err(KalkotoError::SimError(SimulationError::ResultsError(
-            "Pas possible d'exporter : les résultats n'ont pas encore été calculés".to_string(),
-        )))
+        Ok(())
     }
 
     fn export_variante_and_diff_results(
@@ -358,6 +505,20 @@ impl OutputWriter for CSVOutputAdapter {
             handle.join().unwrap()?
         }
 
-        Ok(())
+        let policy_variante = &simulated.policy_variante.0.valid_policy;
+        output_adapter.export_individus_results(
+            "variante",
+            &simulated.menage_input.0,
+            policy_variante,
+            &simulated.results_individus_variante,
+            f64::to_string,
+        )?;
+        output_adapter.export_individus_results(
+            "diff",
+            &simulated.menage_input.0,
+            policy_variante,
+            &simulated.results_individus_diff,
+            |diff| diff.map(|diff| diff.to_string()).unwrap_or_default(),
+        )
     }
 }
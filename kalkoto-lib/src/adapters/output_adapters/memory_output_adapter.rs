@@ -0,0 +1,234 @@
+use crate::{
+    adapters::output_adapters::{
+        csv_output_adapter::{write_baseline_results, write_diff_results, write_variante_results},
+        OutputAdapterError, OutputWriter,
+    },
+    entities::simulator::{
+        SimulatorBuilder, ValidBaselineInput, ValidMenageInput, ValidVarianteInput,
+    },
+    KalkotoResult,
+};
+use std::sync::{Arc, Mutex};
+
+// Résultats exportés au format CSV et conservés en mémoire
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MemoryResults {
+    pub baseline: Option<String>,
+    pub variante: Option<String>,
+    pub diff: Option<String>,
+    pub seed: Option<u64>,
+}
+
+// Adapteur d'export sans système de fichiers (application web notamment). Les clones
+// partagent les mêmes résultats : garder un clone pour les lire après l'export de la variante
+#[derive(Default, Clone)]
+pub struct MemoryOutputAdapter {
+    resultats: Arc<Mutex<MemoryResults>>,
+}
+
+impl MemoryOutputAdapter {
+    pub fn new() -> Self {
+        MemoryOutputAdapter::default()
+    }
+
+    pub fn resultats(&self) -> MemoryResults {
+        self.resultats
+            .lock()
+            .map(|resultats| resultats.clone())
+            .unwrap_or_default()
+    }
+
+    fn enregistre<F>(&self, ecriture: F) -> KalkotoResult<String>
+    where
+        F: FnOnce(&mut Vec<u8>) -> KalkotoResult<()>,
+    {
+        let mut buffer = vec![];
+        ecriture(&mut buffer)?;
+
+        String::from_utf8(buffer).map_err(|e| {
+            From::from(OutputAdapterError::Custom(format!(
+                "Résultats CSV non valides en UTF-8 : {}",
+                e
+            )))
+        })
+    }
+
+    fn resultats_mut(&self) -> KalkotoResult<std::sync::MutexGuard<'_, MemoryResults>> {
+        self.resultats.lock().map_err(|_| {
+            From::from(OutputAdapterError::Custom(
+                "Les résultats en mémoire ne sont plus accessibles".into(),
+            ))
+        })
+    }
+}
+
+impl OutputWriter for MemoryOutputAdapter {
+    fn export_baseline_results<E>(
+        &self,
+        simulated: &SimulatorBuilder<ValidMenageInput, ValidBaselineInput, E>,
+    ) -> KalkotoResult<()> {
+        let baseline = self.enregistre(|buffer| write_baseline_results(simulated, buffer))?;
+        let mut resultats = self.resultats_mut()?;
+        resultats.baseline = Some(baseline);
+        resultats.seed = Some(simulated.seed);
+        Ok(())
+    }
+
+    fn export_variante_and_diff_results(
+        self,
+        simulated: SimulatorBuilder<ValidMenageInput, ValidBaselineInput, ValidVarianteInput>,
+    ) -> KalkotoResult<()> {
+        let variante = self.enregistre(|buffer| write_variante_results(&simulated, buffer))?;
+        let diff = self.enregistre(|buffer| write_diff_results(&simulated, buffer))?;
+
+        let mut resultats = self.resultats_mut()?;
+        resultats.variante = Some(variante);
+        resultats.diff = Some(diff);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::input_adapters::csv_input_adapter::CsvInputAdapter;
+    use crate::adapters::input_adapters::toml_input_adapter::TomlInputAdapter;
+    use crate::entities::menage_input::{TraitementManquant, ValeursManquantes};
+
+    static MENAGES_CSV_BYTES: &[u8] = "Age;GIR\n80;1\n70;2\n".as_bytes();
+
+    static POLICY_TOML: &str = r#"
+name = "APA domicile"
+
+intitule_long = "Aide personnalisée à domicile"
+
+[[composante]]
+name = "plan_notif"
+intitule_long = "Plan notifié"
+parameters.names = ["tau"]
+parameters.intitules_long = ["Taux"]
+parameters.values = [0.5]
+caracteristiques_dependencies = ["Age", "GIR"]
+logical_order = 1
+formula = 'ParamsDict["tau"] * MenageCarac["Age"] / MenageCarac["GIR"]'
+"#;
+
+    #[test]
+    fn ok_simulation_exported_in_memory() -> KalkotoResult<()> {
+        static POLICY_VARIANTE_TOML: &str = r#"
+name = "APA domicile"
+
+intitule_long = "Aide personnalisée à domicile"
+
+[[composante]]
+name = "plan_notif"
+intitule_long = "Plan notifié"
+parameters.names = ["tau"]
+parameters.intitules_long = ["Taux"]
+parameters.values = [1.0]
+caracteristiques_dependencies = ["Age", "GIR"]
+logical_order = 1
+formula = 'ParamsDict["tau"] * MenageCarac["Age"] / MenageCarac["GIR"]'
+"#;
+
+        let mut simulation = SimulatorBuilder::new()
+            .add_menage_input(CsvInputAdapter::new().populate_from_memory(MENAGES_CSV_BYTES)?)?
+            .add_valid_baseline_policy(
+                TomlInputAdapter::new().populate_from_memory(POLICY_TOML.as_bytes())?,
+            )?;
+        simulation.simulate_baseline_policy()?;
+
+        let output_adapter = MemoryOutputAdapter::new();
+        simulation.export_baseline(&output_adapter)?;
+
+        let mut simulation = simulation.add_valid_variante_policy(
+            TomlInputAdapter::new().populate_from_memory(POLICY_VARIANTE_TOML.as_bytes())?,
+        )?;
+        simulation.simulate_variante_policy()?;
+        simulation.export_variante_and_diff(output_adapter.clone())?;
+
+        let wanted = MemoryResults {
+            baseline: Some("Index;Age;GIR;plan_notif\n1;80;1;40\n2;70;2;17.5\n".into()),
+            variante: Some("Index;Age;GIR;plan_notif\n1;80;1;80\n2;70;2;35\n".into()),
+            diff: Some("Index;Age;GIR;plan_notif\n1;80;1;40\n2;70;2;17.5\n".into()),
+            seed: Some(0),
+        };
+        assert_eq!(wanted, output_adapter.resultats());
+
+        Ok(())
+    }
+
+    #[test]
+    fn ok_valeur_manquante_exportee_vide() -> KalkotoResult<()> {
+        static CSV_BYTES: &[u8] = "Age;GIR;Commune\n80;1;\n70;2;Paris\n".as_bytes();
+
+        let mut simulation = SimulatorBuilder::new()
+            .add_valeurs_manquantes(ValeursManquantes::pour_toutes(
+                TraitementManquant::Transmettre,
+            ))
+            .add_menage_input(CsvInputAdapter::new().populate_from_memory(CSV_BYTES)?)?
+            .add_valid_baseline_policy(
+                TomlInputAdapter::new().populate_from_memory(POLICY_TOML.as_bytes())?,
+            )?;
+        simulation.simulate_baseline_policy()?;
+
+        let output_adapter = MemoryOutputAdapter::new();
+        simulation.export_baseline(&output_adapter)?;
+
+        let wanted =
+            Some("Index;Age;Commune;GIR;plan_notif\n1;80;;1;40\n2;70;Paris;2;17.5\n".into());
+        assert_eq!(wanted, output_adapter.resultats().baseline);
+
+        Ok(())
+    }
+
+    #[test]
+    fn ok_colonne_identifiant_cle_des_resultats() -> KalkotoResult<()> {
+        static CSV_BYTES: &[u8] = "Dossier;Age;GIR\n0012;80;1\nB7;70;2\n".as_bytes();
+
+        let mut simulation = SimulatorBuilder::new()
+            .add_menage_input(
+                CsvInputAdapter::new()
+                    .with_colonne_identifiant("Dossier")
+                    .populate_from_memory(CSV_BYTES)?,
+            )?
+            .add_valid_baseline_policy(
+                TomlInputAdapter::new().populate_from_memory(POLICY_TOML.as_bytes())?,
+            )?;
+        simulation.simulate_baseline_policy()?;
+
+        let output_adapter = MemoryOutputAdapter::new();
+        simulation.export_baseline(&output_adapter)?;
+
+        let wanted = Some("Dossier;Age;GIR;plan_notif\n0012;80;1;40\nB7;70;2;17.5\n".into());
+        assert_eq!(wanted, output_adapter.resultats().baseline);
+
+        Ok(())
+    }
+
+    #[test]
+    fn ok_colonne_poids_reprise_dans_les_resultats() -> KalkotoResult<()> {
+        static CSV_BYTES: &[u8] = "Dossier;Age;GIR;Poids\nA1;80;1;150.5\nA2;70;2;300\n".as_bytes();
+
+        let mut simulation = SimulatorBuilder::new()
+            .add_colonne_poids("Poids")
+            .add_menage_input(
+                CsvInputAdapter::new()
+                    .with_colonne_identifiant("Dossier")
+                    .populate_from_memory(CSV_BYTES)?,
+            )?
+            .add_valid_baseline_policy(
+                TomlInputAdapter::new().populate_from_memory(POLICY_TOML.as_bytes())?,
+            )?;
+        simulation.simulate_baseline_policy()?;
+
+        let output_adapter = MemoryOutputAdapter::new();
+        simulation.export_baseline(&output_adapter)?;
+
+        let wanted =
+            Some("Dossier;Poids;Age;GIR;plan_notif\nA1;150.5;80;1;40\nA2;300;70;2;17.5\n".into());
+        assert_eq!(wanted, output_adapter.resultats().baseline);
+
+        Ok(())
+    }
+}
@@ -1,6 +1,7 @@
 use crate::{
     adapters::output_adapters::{
         arrow_output_adapter::ArrowOutputAdapter, csv_output_adapter::CSVOutputAdapter,
+        memory_output_adapter::MemoryOutputAdapter,
     },
     entities::simulator::{
         SimulatorBuilder, ValidBaselineInput, ValidMenageInput, ValidVarianteInput,
@@ -22,10 +23,12 @@ pub trait OutputWriter {
 
 pub mod arrow_output_adapter;
 pub mod csv_output_adapter;
+pub mod memory_output_adapter;
 
 pub enum OutputAdapter {
     CSV(CSVOutputAdapter),
     Arrow(ArrowOutputAdapter),
+    Memory(MemoryOutputAdapter),
 }
 
 impl OutputWriter for OutputAdapter {
@@ -38,6 +41,9 @@ impl OutputWriter for OutputAdapter {
             Self::Arrow(arrow_output_adapter) => {
                 arrow_output_adapter.export_baseline_results(simulated)
             }
+            Self::Memory(memory_output_adapter) => {
+                memory_output_adapter.export_baseline_results(simulated)
+            }
         }
     }
 
@@ -52,6 +58,9 @@ impl OutputWriter for OutputAdapter {
             Self::Arrow(arrow_output_adapter) => {
                 arrow_output_adapter.export_variante_and_diff_results(simulated)
             }
+            Self::Memory(memory_output_adapter) => {
+                memory_output_adapter.export_variante_and_diff_results(simulated)
+            }
         }
     }
 }
@@ -0,0 +1,70 @@
+use std::cell::Cell;
+
+// Générateur pseudo-aléatoire propre à un couple (ménage, composante). Son état initial ne
+// dépend que de la graine de la simulation, de la clé du ménage (son identifiant, ou à défaut
+// son index) et du nom de la composante : avec une colonne identifiant, les tirages ne dépendent
+// donc ni de l'ordre ou du filtrage des ménages, ni du découpage des calculs.
+// L'algorithme (SplitMix64, état dérivé par FNV-1a) est fixé pour rester reproductible
+// d'une version de Rust à l'autre, contrairement au hasher de la bibliothèque standard
+#[derive(Debug, Clone)]
+pub struct Aleatoire {
+    etat: Cell<u64>,
+}
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+impl Aleatoire {
+    pub fn new(seed: u64, menage: &str, composante: &str) -> Self {
+        // L'octet 0xff, absent de l'UTF-8, sépare la clé du ménage du nom de la composante
+        let etat = seed
+            .to_le_bytes()
+            .iter()
+            .chain(menage.as_bytes())
+            .chain(&[0xff])
+            .chain(composante.as_bytes())
+            .fold(FNV_OFFSET, |hash, octet| {
+                (hash ^ *octet as u64).wrapping_mul(FNV_PRIME)
+            });
+
+        Self {
+            etat: Cell::new(etat),
+        }
+    }
+
+    pub fn next_u64(&self) -> u64 {
+        let etat = self.etat.get().wrapping_add(0x9e37_79b9_7f4a_7c15);
+        self.etat.set(etat);
+
+        let mut z = etat;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // Tirage uniforme dans [0, 1)
+    pub fn uniforme(&self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ok_tirages_reproductibles() {
+        let premier = Aleatoire::new(42, "7", "non_recours");
+        let second = Aleatoire::new(42, "7", "non_recours");
+        let autre_menage = Aleatoire::new(42, "8", "non_recours");
+
+        let tirages: Vec<f64> = (0..3).map(|_| premier.uniforme()).collect();
+
+        assert_eq!(
+            tirages,
+            (0..3).map(|_| second.uniforme()).collect::<Vec<f64>>()
+        );
+        assert_ne!(tirages[0], autre_menage.uniforme());
+        assert!(tirages.iter().all(|tirage| (0.0..1.0).contains(tirage)));
+    }
+}
@@ -0,0 +1,564 @@
+use crate::entities::aleatoire::Aleatoire;
+use crate::entities::formula::{ContexteEvaluation, Formula, Source};
+use crate::entities::menage::{Caracteristique, Menage};
+use crate::entities::policy::{Composante, Policy};
+#[cfg(feature = "python")]
+use crate::entities::python_evaluator::PythonEvaluator;
+#[cfg(feature = "rhai")]
+use crate::entities::rhai_evaluator::RhaiEvaluator;
+use crate::entities::simulator::SimulationError;
+use crate::entities::trace::{ComposanteTrace, Lecture, MenageTrace};
+use crate::{KalkotoError, KalkotoResult};
+use itertools::Itertools;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+pub const MOTEUR_PYTHON: &str = "python";
+pub const MOTEUR_FORMULA: &str = "formula";
+pub const MOTEUR_RHAI: &str = "rhai";
+pub const MOTEUR_RUST: &str = "rust";
+
+// Données communes à toutes les composantes d'une simulation
+#[derive(Clone, Copy)]
+pub struct ContexteSimulation<'a> {
+    pub policy: &'a Policy,
+    pub menages: &'a [Menage],
+    pub menages_traces: &'a HashSet<String>,
+    // Graine des tirages aléatoires, voir Aleatoire
+    pub seed: u64,
+}
+
+// Moteur de calcul des composantes. Une session est ouverte une seule fois par simulation
+// pour chaque moteur utilisé, puis appelée pour chacune de ses composantes dans l'ordre logique
+pub trait ComposanteEvaluator: Send + Sync {
+    fn session<'a>(
+        &'a self,
+        contexte: ContexteSimulation<'a>,
+    ) -> KalkotoResult<Box<dyn SessionEvaluation + 'a>>;
+}
+
+pub trait SessionEvaluation {
+    // Calcule la composante pour tous les ménages et ajoute sa valeur à leurs variables
+    fn evaluate(
+        &mut self,
+        composante: &Composante,
+        variables: &mut [HashMap<String, f64>],
+        traces: &mut [Option<MenageTrace>],
+    ) -> KalkotoResult<()>;
+
+    // Composante de niveau ménage pouvant agréger les composantes de niveau individu déjà
+    // calculées. Les moteurs qui ne lisent pas les individus gardent cette implémentation
+    fn evaluate_avec_individus(
+        &mut self,
+        composante: &Composante,
+        variables: &mut [HashMap<String, f64>],
+        _variables_individus: &[Vec<HashMap<String, f64>>],
+        traces: &mut [Option<MenageTrace>],
+    ) -> KalkotoResult<()> {
+        self.evaluate(composante, variables, traces)
+    }
+
+    // Calcule une composante de niveau individu pour chaque individu de chaque ménage et ajoute
+    // sa valeur aux variables de l'individu
+    fn evaluate_individus(
+        &mut self,
+        composante: &Composante,
+        _variables: &[HashMap<String, f64>],
+        _variables_individus: &mut [Vec<HashMap<String, f64>>],
+    ) -> KalkotoResult<()> {
+        Err(From::from(SimulationError::EvaluatorError(format!(
+            "Le moteur {} ne calcule pas les composantes de niveau individu ({})",
+            composante.engine(),
+            composante.name
+        ))))
+    }
+}
+
+// Ensemble des moteurs disponibles, indexés par le nom utilisé dans le champ engine du TOML
+#[derive(Clone)]
+pub struct Evaluateurs(HashMap<String, Arc<dyn ComposanteEvaluator>>);
+
+impl Default for Evaluateurs {
+    fn default() -> Self {
+        let mut evaluateurs = Evaluateurs(HashMap::new());
+        #[cfg(feature = "python")]
+        evaluateurs.register(MOTEUR_PYTHON, PythonEvaluator::default());
+        evaluateurs.register(MOTEUR_FORMULA, FormulaEvaluator);
+        #[cfg(feature = "rhai")]
+        evaluateurs.register(MOTEUR_RHAI, RhaiEvaluator);
+        evaluateurs
+    }
+}
+
+impl Evaluateurs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register<E: ComposanteEvaluator + 'static>(&mut self, nom: &str, evaluateur: E) {
+        self.0.insert(nom.to_owned(), Arc::new(evaluateur));
+    }
+
+    pub fn get(&self, nom: &str) -> KalkotoResult<&Arc<dyn ComposanteEvaluator>> {
+        self.0.get(nom).ok_or_else(|| {
+            let conseil = match nom {
+                MOTEUR_PYTHON => " (kalkoto-lib est compilé sans la feature python)",
+                MOTEUR_RHAI => " (kalkoto-lib est compilé sans la feature rhai)",
+                _ => "",
+            };
+            KalkotoError::from(SimulationError::EvaluatorError(format!(
+                "Aucun moteur de calcul \"{}\" n'est enregistré{}",
+                nom, conseil
+            )))
+        })
+    }
+}
+
+// Moteur des composantes définies par une formule (voir entities::formula)
+pub struct FormulaEvaluator;
+
+struct FormulaSession<'a> {
+    contexte: ContexteSimulation<'a>,
+}
+
+impl ComposanteEvaluator for FormulaEvaluator {
+    fn session<'a>(
+        &'a self,
+        contexte: ContexteSimulation<'a>,
+    ) -> KalkotoResult<Box<dyn SessionEvaluation + 'a>> {
+        Ok(Box::new(FormulaSession { contexte }))
+    }
+}
+
+fn formula(composante: &Composante) -> Result<&Formula, SimulationError> {
+    composante.formula.as_ref().ok_or_else(|| {
+        SimulationError::EvaluatorError(format!(
+            "La composante {} n'a pas de formula",
+            composante.name
+        ))
+    })
+}
+
+impl SessionEvaluation for FormulaSession<'_> {
+    fn evaluate(
+        &mut self,
+        composante: &Composante,
+        variables: &mut [HashMap<String, f64>],
+        traces: &mut [Option<MenageTrace>],
+    ) -> KalkotoResult<()> {
+        let variables_individus: Vec<Vec<HashMap<String, f64>>> = self
+            .contexte
+            .menages
+            .iter()
+            .map(|menage| vec![HashMap::new(); menage.individus.len()])
+            .collect();
+        self.evaluate_avec_individus(composante, variables, &variables_individus, traces)
+    }
+
+    fn evaluate_avec_individus(
+        &mut self,
+        composante: &Composante,
+        variables: &mut [HashMap<String, f64>],
+        variables_individus: &[Vec<HashMap<String, f64>>],
+        traces: &mut [Option<MenageTrace>],
+    ) -> KalkotoResult<()> {
+        let formula = formula(composante)?;
+        let parametres = &self.contexte.policy.parameters_values;
+
+        for (((menage, menage_variables), individus_variables), trace) in self
+            .contexte
+            .menages
+            .iter()
+            .zip(variables.iter_mut())
+            .zip(variables_individus)
+            .zip(traces.iter_mut())
+        {
+            let mut lectures = vec![];
+            let contexte = ContexteEvaluation {
+                caracteristiques: &menage.caracteristiques,
+                parametres,
+                variables: menage_variables,
+                aleatoire: &Aleatoire::new(self.contexte.seed, &menage.cle(), &composante.name),
+                individus: &menage.individus,
+                variables_individus: individus_variables,
+                individu: None,
+            };
+
+            let resultat = formula
+                .evalue(&contexte, &mut lectures)
+                .map_err(|message| SimulationError::FormulaError {
+                    composante: composante.name.to_owned(),
+                    message,
+                    menage: menage.to_string(),
+                })?;
+
+            if let Some(trace) = trace {
+                let lectures: Vec<(Source, String)> = lectures.into_iter().unique().collect();
+                let lectures_f64 = |source: Source, valeurs: &HashMap<String, f64>| {
+                    lectures
+                        .iter()
+                        .filter(|(source_lecture, _)| *source_lecture == source)
+                        .filter_map(|(_, nom)| {
+                            let valeur = *valeurs.get(nom)?;
+                            Some(Lecture {
+                                nom: nom.to_owned(),
+                                valeur,
+                            })
+                        })
+                        .collect()
+                };
+
+                trace.etapes.push(ComposanteTrace {
+                    composante: composante.name.to_owned(),
+                    intitule_long: composante.intitule_long.to_owned(),
+                    caracteristiques_lues: lectures
+                        .iter()
+                        .filter(|(source, _)| *source == Source::MenageCarac)
+                        .filter_map(|(_, nom)| {
+                            let valeur = menage.caracteristiques.get(nom)?.clone();
+                            Some(Lecture {
+                                nom: nom.to_owned(),
+                                valeur,
+                            })
+                        })
+                        .collect(),
+                    parametres_lus: lectures_f64(Source::ParamsDict, parametres),
+                    variables_lues: lectures_f64(Source::Variables, menage_variables),
+                    resultat,
+                    lectures_enregistrees: true,
+                });
+            }
+
+            menage_variables.insert(composante.name.to_owned(), resultat);
+        }
+
+        Ok(())
+    }
+
+    // Les tirages sont propres à chaque individu (clé de son ménage et rang dans le ménage) ;
+    // le détail du calcul des individus n'est pas tracé
+    fn evaluate_individus(
+        &mut self,
+        composante: &Composante,
+        variables: &[HashMap<String, f64>],
+        variables_individus: &mut [Vec<HashMap<String, f64>>],
+    ) -> KalkotoResult<()> {
+        let formula = formula(composante)?;
+        let parametres = &self.contexte.policy.parameters_values;
+
+        for ((menage, menage_variables), individus_variables) in self
+            .contexte
+            .menages
+            .iter()
+            .zip(variables)
+            .zip(variables_individus.iter_mut())
+        {
+            for (rang, (individu, individu_variables)) in menage
+                .individus
+                .iter()
+                .zip(individus_variables.iter_mut())
+                .enumerate()
+            {
+                let contexte = ContexteEvaluation {
+                    caracteristiques: &menage.caracteristiques,
+                    parametres,
+                    variables: menage_variables,
+                    aleatoire: &Aleatoire::new(
+                        self.contexte.seed,
+                        &menage.cle_individu(rang),
+                        &composante.name,
+                    ),
+                    individus: &[],
+                    variables_individus: &[],
+                    individu: Some((individu, individu_variables)),
+                };
+
+                let resultat = formula.evalue(&contexte, &mut vec![]).map_err(|message| {
+                    SimulationError::FormulaError {
+                        composante: composante.name.to_owned(),
+                        message: format!("individu {} : {}", individu.index, message),
+                        menage: menage.to_string(),
+                    }
+                })?;
+
+                individu_variables.insert(composante.name.to_owned(), resultat);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Fonction Rust calculant une composante, avec la même convention d'appel que les
+// fonctions Python : (Variables, ParamsDict, MenageCarac)
+pub type FonctionNative = dyn Fn(
+        &HashMap<String, f64>,
+        &HashMap<String, f64>,
+        &HashMap<String, Caracteristique>,
+    ) -> Result<f64, String>
+    + Send
+    + Sync;
+
+// Moteur des composantes codées en Rust par le programme hôte, enregistrées sous le nom
+// de la composante. Les traces ne contiennent que le résultat, les lectures n'étant pas observées
+#[derive(Default, Clone)]
+pub struct NativeEvaluator {
+    fonctions: HashMap<String, Arc<FonctionNative>>,
+}
+
+impl NativeEvaluator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register<F>(mut self, composante: &str, fonction: F) -> Self
+    where
+        F: Fn(
+                &HashMap<String, f64>,
+                &HashMap<String, f64>,
+                &HashMap<String, Caracteristique>,
+            ) -> Result<f64, String>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.fonctions
+            .insert(composante.to_owned(), Arc::new(fonction));
+        self
+    }
+}
+
+struct NativeSession<'a> {
+    evaluateur: &'a NativeEvaluator,
+    contexte: ContexteSimulation<'a>,
+}
+
+impl ComposanteEvaluator for NativeEvaluator {
+    fn session<'a>(
+        &'a self,
+        contexte: ContexteSimulation<'a>,
+    ) -> KalkotoResult<Box<dyn SessionEvaluation + 'a>> {
+        Ok(Box::new(NativeSession {
+            evaluateur: self,
+            contexte,
+        }))
+    }
+}
+
+impl SessionEvaluation for NativeSession<'_> {
+    fn evaluate(
+        &mut self,
+        composante: &Composante,
+        variables: &mut [HashMap<String, f64>],
+        traces: &mut [Option<MenageTrace>],
+    ) -> KalkotoResult<()> {
+        let fonction = self
+            .evaluateur
+            .fonctions
+            .get(&composante.name)
+            .ok_or_else(|| {
+                SimulationError::EvaluatorError(format!(
+                    "Aucune fonction Rust n'est enregistrée pour la composante {}",
+                    composante.name
+                ))
+            })?;
+        let parametres = &self.contexte.policy.parameters_values;
+
+        for ((menage, menage_variables), trace) in self
+            .contexte
+            .menages
+            .iter()
+            .zip(variables.iter_mut())
+            .zip(traces.iter_mut())
+        {
+            let resultat = fonction(menage_variables, parametres, &menage.caracteristiques)
+                .map_err(|message| SimulationError::NativeError {
+                    composante: composante.name.to_owned(),
+                    message,
+                    menage: menage.to_string(),
+                })?;
+
+            if let Some(trace) = trace {
+                trace.etapes.push(ComposanteTrace {
+                    composante: composante.name.to_owned(),
+                    intitule_long: composante.intitule_long.to_owned(),
+                    caracteristiques_lues: vec![],
+                    parametres_lus: vec![],
+                    variables_lues: vec![],
+                    resultat,
+                    lectures_enregistrees: false,
+                });
+            }
+
+            menage_variables.insert(composante.name.to_owned(), resultat);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::input_adapters::toml_input_adapter::TomlInputAdapter;
+
+    static MIXED_TOML_BYTES: &[u8] = r#"
+name = "APA domicile"
+
+intitule_long = "Aide personnalisée à domicile"
+
+[[composante]]
+name = "plan_notif"
+intitule_long = "Plan notifié"
+parameters.names = ["tau_1"]
+parameters.intitules_long = ["Taux GIR 1"]
+parameters.values = [0.5]
+caracteristiques_dependencies = ["Age"]
+logical_order = 1
+engine = "rust"
+
+[[composante]]
+name = "plan_cons"
+intitule_long = "Plan effectivement consommé"
+parameters.names = ["taux_ss_conso"]
+parameters.intitules_long = ["Taux de sous-consommation du plan notifié"]
+parameters.values = [0.8]
+caracteristiques_dependencies = []
+logical_order = 2
+formula = 'Variables["plan_notif"] * ParamsDict["taux_ss_conso"]'
+"#
+    .as_bytes();
+
+    fn create_test_policy() -> KalkotoResult<Policy> {
+        let (name, intitule_long, composantes) =
+            TomlInputAdapter::new().populate_from_buf(MIXED_TOML_BYTES)?;
+
+        Policy {
+            name,
+            intitule_long,
+            composantes_ordonnees: composantes,
+            parameters_intitules: HashMap::new(),
+            parameters_values: HashMap::from([
+                ("tau_1".to_string(), 0.5),
+                ("taux_ss_conso".to_string(), 0.8),
+            ]),
+            caracteristiques_menages: HashSet::new(),
+            caracteristiques_individus: HashSet::new(),
+            python_functions: None,
+            fichier_policy: None,
+            python_source_map: None,
+            environnement_python: Default::default(),
+        }
+        .populate_python_functions()
+    }
+
+    fn create_test_menage() -> Menage {
+        let mut menage = Menage::new(1);
+        menage
+            .caracteristiques
+            .insert("Age".into(), Caracteristique::Entier(80));
+        menage
+    }
+
+    #[test]
+    fn ok_native_and_formula_composantes() -> KalkotoResult<()> {
+        let policy = create_test_policy()?;
+
+        let mut evaluateurs = Evaluateurs::default();
+        evaluateurs.register(
+            MOTEUR_RUST,
+            NativeEvaluator::new().register("plan_notif", |_, params, caracteristiques| {
+                match caracteristiques.get("Age") {
+                    Some(Caracteristique::Entier(age)) => Ok(params["tau_1"] * *age as f64),
+                    _ => Err("Age doit être un entier".into()),
+                }
+            }),
+        );
+
+        let (results, _) = policy.simulate_all_menages_with_trace(
+            &[create_test_menage()],
+            &HashSet::new(),
+            &evaluateurs,
+        )?;
+
+        let wanted = HashMap::from([
+            ("plan_notif".to_string(), 40.0),
+            ("plan_cons".to_string(), 32.0),
+        ]);
+        assert_eq!(vec![wanted], results);
+
+        Ok(())
+    }
+
+    #[test]
+    fn ok_trace_native_lectures_non_enregistrees() -> KalkotoResult<()> {
+        let policy = create_test_policy()?;
+
+        let mut evaluateurs = Evaluateurs::default();
+        evaluateurs.register(
+            MOTEUR_RUST,
+            NativeEvaluator::new().register("plan_notif", |_, params, _| Ok(params["tau_1"])),
+        );
+
+        let (_, traces) = policy.simulate_all_menages_with_trace(
+            &[create_test_menage()],
+            &HashSet::from(["1".to_string()]),
+            &evaluateurs,
+        )?;
+
+        let lectures_enregistrees: Vec<bool> = traces[0]
+            .etapes
+            .iter()
+            .map(|etape| etape.lectures_enregistrees)
+            .collect();
+        assert_eq!(vec![false, true], lectures_enregistrees);
+        assert!(traces[0]
+            .to_string()
+            .contains("lectures non enregistrées par le moteur de cette composante"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn err_native_function_error() -> KalkotoResult<()> {
+        let policy = create_test_policy()?;
+
+        let mut evaluateurs = Evaluateurs::default();
+        evaluateurs.register(
+            MOTEUR_RUST,
+            NativeEvaluator::new().register("plan_notif", |_, _, _| Err("barème absent".into())),
+        );
+
+        let result = policy.simulate_all_menages_with_trace(
+            &[create_test_menage()],
+            &HashSet::new(),
+            &evaluateurs,
+        );
+
+        let wanted = true;
+        let result = matches!(
+            result,
+            Err(KalkotoError::SimError(SimulationError::NativeError { ref composante, ref message, .. }))
+                if composante == "plan_notif" && message == "barème absent"
+        );
+        assert_eq!(wanted, result);
+
+        Ok(())
+    }
+
+    #[test]
+    fn err_unregistered_engine() -> KalkotoResult<()> {
+        let policy = create_test_policy()?;
+
+        let result = policy.simulate_all_menages(&[create_test_menage()]);
+
+        let wanted = true;
+        let result = matches!(
+            result,
+            Err(KalkotoError::SimError(SimulationError::EvaluatorError(_)))
+        );
+        assert_eq!(wanted, result);
+
+        Ok(())
+    }
+}
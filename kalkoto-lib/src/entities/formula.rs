@@ -0,0 +1,1229 @@
+use crate::entities::aleatoire::Aleatoire;
+use crate::entities::menage::{Caracteristique, Individu};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+// Langage de formules des composantes, évalué en Rust sans passer par Python.
+// La syntaxe reprend celle des expressions Python :
+//   Variables["plan_notif"] * ParamsDict["taux_ss_conso"]
+//   min(MenageCarac["Revenu"], 3000) if MenageCarac["Age"] >= 60 and not MenageCarac["GIR"] == 6 else 0
+//   1 if alea() < ParamsDict["taux_recours"] else 0
+// Les composantes de niveau ménage agrègent les individus du ménage :
+//   somme_individus(IndividuCarac["Revenu"]) + ParamsDict["forfait"] * nombre_individus(IndividuCarac["Age"] < 18)
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Nombre(f64),
+    Texte(String),
+    Identifiant(String),
+    Plus,
+    Moins,
+    Fois,
+    Divise,
+    Egal,
+    Different,
+    Inferieur,
+    InferieurEgal,
+    Superieur,
+    SuperieurEgal,
+    ParentheseOuvrante,
+    ParentheseFermante,
+    CrochetOuvrant,
+    CrochetFermant,
+    Virgule,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Nombre(x) => write!(f, "{x}"),
+            Token::Texte(texte) => write!(f, "\"{texte}\""),
+            Token::Identifiant(nom) => write!(f, "{nom}"),
+            Token::Plus => write!(f, "+"),
+            Token::Moins => write!(f, "-"),
+            Token::Fois => write!(f, "*"),
+            Token::Divise => write!(f, "/"),
+            Token::Egal => write!(f, "=="),
+            Token::Different => write!(f, "!="),
+            Token::Inferieur => write!(f, "<"),
+            Token::InferieurEgal => write!(f, "<="),
+            Token::Superieur => write!(f, ">"),
+            Token::SuperieurEgal => write!(f, ">="),
+            Token::ParentheseOuvrante => write!(f, "("),
+            Token::ParentheseFermante => write!(f, ")"),
+            Token::CrochetOuvrant => write!(f, "["),
+            Token::CrochetFermant => write!(f, "]"),
+            Token::Virgule => write!(f, ","),
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut chars = source.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '0'..='9' | '.' => {
+                let mut nombre = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_ascii_digit() || c == '.' || c == '_' {
+                        nombre.push(c);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                let valeur = nombre
+                    .replace('_', "")
+                    .parse::<f64>()
+                    .map_err(|_| format!("nombre invalide : {}", nombre))?;
+                tokens.push(Token::Nombre(valeur));
+            }
+            '"' | '\'' => {
+                chars.next();
+                let mut texte = String::new();
+                loop {
+                    match chars.next() {
+                        Some(fin) if fin == c => break,
+                        Some(autre) => texte.push(autre),
+                        None => return Err(format!("chaîne non terminée : {}{}", c, texte)),
+                    }
+                }
+                tokens.push(Token::Texte(texte));
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut nom = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_alphanumeric() || c == '_' {
+                        nom.push(c);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                tokens.push(Token::Identifiant(nom));
+            }
+            _ => {
+                chars.next();
+                let token = match (c, chars.peek()) {
+                    ('=', Some('=')) => Token::Egal,
+                    ('!', Some('=')) => Token::Different,
+                    ('<', Some('=')) => Token::InferieurEgal,
+                    ('>', Some('=')) => Token::SuperieurEgal,
+                    ('<', _) => Token::Inferieur,
+                    ('>', _) => Token::Superieur,
+                    ('+', _) => Token::Plus,
+                    ('-', _) => Token::Moins,
+                    ('*', _) => Token::Fois,
+                    ('/', _) => Token::Divise,
+                    ('(', _) => Token::ParentheseOuvrante,
+                    (')', _) => Token::ParentheseFermante,
+                    ('[', _) => Token::CrochetOuvrant,
+                    (']', _) => Token::CrochetFermant,
+                    (',', _) => Token::Virgule,
+                    _ => return Err(format!("caractère inattendu : {}", c)),
+                };
+                if matches!(
+                    token,
+                    Token::Egal | Token::Different | Token::InferieurEgal | Token::SuperieurEgal
+                ) {
+                    chars.next();
+                }
+                tokens.push(token);
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OperateurArithmetique {
+    Addition,
+    Soustraction,
+    Multiplication,
+    Division,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OperateurComparaison {
+    Egal,
+    Different,
+    Inferieur,
+    InferieurEgal,
+    Superieur,
+    SuperieurEgal,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fonction {
+    Min,
+    Max,
+    Alea, //Tirage uniforme dans [0, 1), reproductible pour un ménage et une composante
+}
+
+// Agrégation d'une expression évaluée pour chaque individu du ménage. Sans individu, les
+// agrégations valent 0 (ou False pour existe_individu)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Agregation {
+    Somme,
+    Max,
+    Nombre, //Nombre d'individus vérifiant la condition
+    Existe, //Au moins un individu vérifie la condition
+}
+
+// Dictionnaire auquel fait référence une formule, avec la même convention que les fonctions Python
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Source {
+    MenageCarac,
+    ParamsDict,
+    Variables,
+    IndividuCarac,
+    IndividuVariables, //Composantes de niveau individu déjà calculées pour l'individu
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    Nombre(f64),
+    Texte(String),
+    Booleen(bool),
+    Reference(Source, String),
+    Oppose(Box<Expression>),
+    Non(Box<Expression>),
+    Arithmetique(OperateurArithmetique, Box<Expression>, Box<Expression>),
+    Comparaison(OperateurComparaison, Box<Expression>, Box<Expression>),
+    Et(Box<Expression>, Box<Expression>),
+    Ou(Box<Expression>, Box<Expression>),
+    Condition {
+        condition: Box<Expression>,
+        alors: Box<Expression>,
+        sinon: Box<Expression>,
+    },
+    Appel(Fonction, Vec<Expression>),
+    Agregation(Agregation, Box<Expression>),
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    filtre: bool, //Les noms seuls désignent des caractéristiques ménage (filtre des ménages)
+}
+
+const MOTS_CLEFS: [&str; 5] = ["if", "else", "and", "or", "not"];
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn peek_mot_clef(&self, mot_clef: &str) -> bool {
+        matches!(self.peek(), Some(Token::Identifiant(nom)) if nom == mot_clef)
+    }
+
+    fn attend(&mut self, attendu: Token) -> Result<(), String> {
+        match self.next() {
+            Some(token) if token == attendu => Ok(()),
+            Some(token) => Err(format!("'{}' attendu, '{}' trouvé", attendu, token)),
+            None => Err(format!("'{}' attendu en fin de formule", attendu)),
+        }
+    }
+
+    fn expression(&mut self) -> Result<Expression, String> {
+        let alors = self.ou()?;
+        if self.peek_mot_clef("if") {
+            self.next();
+            let condition = self.ou()?;
+            match self.next() {
+                Some(Token::Identifiant(nom)) if nom == "else" => (),
+                _ => return Err("'else' attendu après la condition d'un 'if'".into()),
+            }
+            let sinon = self.expression()?;
+            return Ok(Expression::Condition {
+                condition: Box::new(condition),
+                alors: Box::new(alors),
+                sinon: Box::new(sinon),
+            });
+        }
+        Ok(alors)
+    }
+
+    fn ou(&mut self) -> Result<Expression, String> {
+        let mut gauche = self.et()?;
+        while self.peek_mot_clef("or") {
+            self.next();
+            gauche = Expression::Ou(Box::new(gauche), Box::new(self.et()?));
+        }
+        Ok(gauche)
+    }
+
+    fn et(&mut self) -> Result<Expression, String> {
+        let mut gauche = self.non()?;
+        while self.peek_mot_clef("and") {
+            self.next();
+            gauche = Expression::Et(Box::new(gauche), Box::new(self.non()?));
+        }
+        Ok(gauche)
+    }
+
+    fn non(&mut self) -> Result<Expression, String> {
+        if self.peek_mot_clef("not") {
+            self.next();
+            return Ok(Expression::Non(Box::new(self.non()?)));
+        }
+        self.comparaison()
+    }
+
+    fn comparaison(&mut self) -> Result<Expression, String> {
+        let gauche = self.addition()?;
+        let operateur = match self.peek() {
+            Some(Token::Egal) => OperateurComparaison::Egal,
+            Some(Token::Different) => OperateurComparaison::Different,
+            Some(Token::Inferieur) => OperateurComparaison::Inferieur,
+            Some(Token::InferieurEgal) => OperateurComparaison::InferieurEgal,
+            Some(Token::Superieur) => OperateurComparaison::Superieur,
+            Some(Token::SuperieurEgal) => OperateurComparaison::SuperieurEgal,
+            _ => return Ok(gauche),
+        };
+        self.next();
+        let droite = self.addition()?;
+        Ok(Expression::Comparaison(
+            operateur,
+            Box::new(gauche),
+            Box::new(droite),
+        ))
+    }
+
+    fn addition(&mut self) -> Result<Expression, String> {
+        let mut gauche = self.multiplication()?;
+        loop {
+            let operateur = match self.peek() {
+                Some(Token::Plus) => OperateurArithmetique::Addition,
+                Some(Token::Moins) => OperateurArithmetique::Soustraction,
+                _ => return Ok(gauche),
+            };
+            self.next();
+            let droite = self.multiplication()?;
+            gauche = Expression::Arithmetique(operateur, Box::new(gauche), Box::new(droite));
+        }
+    }
+
+    fn multiplication(&mut self) -> Result<Expression, String> {
+        let mut gauche = self.unaire()?;
+        loop {
+            let operateur = match self.peek() {
+                Some(Token::Fois) => OperateurArithmetique::Multiplication,
+                Some(Token::Divise) => OperateurArithmetique::Division,
+                _ => return Ok(gauche),
+            };
+            self.next();
+            let droite = self.unaire()?;
+            gauche = Expression::Arithmetique(operateur, Box::new(gauche), Box::new(droite));
+        }
+    }
+
+    fn unaire(&mut self) -> Result<Expression, String> {
+        if let Some(Token::Moins) = self.peek() {
+            self.next();
+            return Ok(Expression::Oppose(Box::new(self.unaire()?)));
+        }
+        self.primaire()
+    }
+
+    fn primaire(&mut self) -> Result<Expression, String> {
+        match self.next() {
+            Some(Token::Nombre(x)) => Ok(Expression::Nombre(x)),
+            Some(Token::Texte(texte)) => Ok(Expression::Texte(texte)),
+            Some(Token::ParentheseOuvrante) => {
+                let expression = self.expression()?;
+                self.attend(Token::ParentheseFermante)?;
+                Ok(expression)
+            }
+            Some(Token::Identifiant(nom)) => match nom.as_str() {
+                "True" => Ok(Expression::Booleen(true)),
+                "False" => Ok(Expression::Booleen(false)),
+                "ParamsDict" | "Variables" | "alea" | "IndividuCarac" | "IndividuVariables"
+                | "somme_individus" | "max_individus" | "nombre_individus" | "existe_individu"
+                    if self.filtre =>
+                {
+                    Err(format!(
+                        "{} n'est pas utilisable dans un filtre de ménages",
+                        nom
+                    ))
+                }
+                "MenageCarac" => self.reference(Source::MenageCarac),
+                "ParamsDict" => self.reference(Source::ParamsDict),
+                "Variables" => self.reference(Source::Variables),
+                "IndividuCarac" => self.reference(Source::IndividuCarac),
+                "IndividuVariables" => self.reference(Source::IndividuVariables),
+                "somme_individus" => self.agregation(Agregation::Somme),
+                "max_individus" => self.agregation(Agregation::Max),
+                "nombre_individus" => self.agregation(Agregation::Nombre),
+                "existe_individu" => self.agregation(Agregation::Existe),
+                "min" => self.appel(Fonction::Min),
+                "max" => self.appel(Fonction::Max),
+                "alea" => self.appel(Fonction::Alea),
+                _ if self.filtre && !MOTS_CLEFS.contains(&nom.as_str()) => {
+                    Ok(Expression::Reference(Source::MenageCarac, nom))
+                }
+                _ => Err(format!("identifiant inconnu : {}", nom)),
+            },
+            Some(token) => Err(format!("'{}' inattendu", token)),
+            None => Err("formule incomplète".into()),
+        }
+    }
+
+    fn reference(&mut self, source: Source) -> Result<Expression, String> {
+        self.attend(Token::CrochetOuvrant)?;
+        let nom = match self.next() {
+            Some(Token::Texte(nom)) => nom,
+            _ => return Err(format!("nom entre guillemets attendu après {:?}[", source)),
+        };
+        self.attend(Token::CrochetFermant)?;
+        Ok(Expression::Reference(source, nom))
+    }
+
+    fn agregation(&mut self, agregation: Agregation) -> Result<Expression, String> {
+        self.attend(Token::ParentheseOuvrante)?;
+        let expression = self.expression()?;
+        self.attend(Token::ParentheseFermante)?;
+        Ok(Expression::Agregation(agregation, Box::new(expression)))
+    }
+
+    fn appel(&mut self, fonction: Fonction) -> Result<Expression, String> {
+        self.attend(Token::ParentheseOuvrante)?;
+        let mut arguments = vec![];
+        if let Some(Token::ParentheseFermante) = self.peek() {
+            self.next();
+            return Ok(Expression::Appel(fonction, arguments));
+        }
+        arguments.push(self.expression()?);
+        while let Some(Token::Virgule) = self.peek() {
+            self.next();
+            arguments.push(self.expression()?);
+        }
+        self.attend(Token::ParentheseFermante)?;
+        Ok(Expression::Appel(fonction, arguments))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TypeFormule {
+    Nombre,
+    Texte,
+    Booleen,
+    // Type d'une caractéristique ménage, connu seulement à l'évaluation
+    Caracteristique,
+}
+
+impl TypeFormule {
+    fn compatible(self, attendu: TypeFormule) -> bool {
+        self == attendu || self == TypeFormule::Caracteristique
+    }
+}
+
+// Références autorisées pour une composante : ses caractéristiques, les paramètres
+// de la politique et les variables des composantes précédentes. Les références aux individus
+// ne sont lisibles que si individu est vrai : composante de niveau individu, ou intérieur
+// d'une agrégation
+#[derive(Clone, Copy)]
+pub struct ContexteVerification<'a> {
+    pub caracteristiques: &'a [String],
+    pub parametres: &'a HashMap<String, f64>,
+    pub variables: &'a HashSet<String>,
+    pub caracteristiques_individus: &'a [String],
+    pub variables_individus: &'a HashSet<String>,
+    pub individu: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Valeur {
+    Nombre(f64),
+    Texte(String),
+    Booleen(bool),
+}
+
+impl fmt::Display for Valeur {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Valeur::Nombre(x) => write!(f, "{x}"),
+            Valeur::Texte(texte) => write!(f, "\"{texte}\""),
+            Valeur::Booleen(b) => write!(f, "{b}"),
+        }
+    }
+}
+
+impl TryFrom<&Caracteristique> for Valeur {
+    type Error = String;
+
+    fn try_from(caracteristique: &Caracteristique) -> Result<Valeur, String> {
+        match caracteristique {
+            Caracteristique::Entier(i) => Ok(Valeur::Nombre(*i as f64)),
+            Caracteristique::Numeric(x) => Ok(Valeur::Nombre(*x)),
+            Caracteristique::Textuel(texte) => Ok(Valeur::Texte(texte.clone())),
+            Caracteristique::Booleen(b) => Ok(Valeur::Booleen(*b)),
+            // Les dates sont lues au format AAAA-MM-JJ
+            Caracteristique::Date(_) => Ok(Valeur::Texte(caracteristique.to_string())),
+            Caracteristique::Manquant => Err("valeur manquante".into()),
+        }
+    }
+}
+
+// individus et variables_individus sont ceux du ménage, lus par les agrégations ; individu
+// est l'individu dont une composante de niveau individu (ou une agrégation) calcule la valeur
+#[derive(Clone, Copy)]
+pub struct ContexteEvaluation<'a> {
+    pub caracteristiques: &'a HashMap<String, Caracteristique>,
+    pub parametres: &'a HashMap<String, f64>,
+    pub variables: &'a HashMap<String, f64>,
+    pub aleatoire: &'a Aleatoire,
+    pub individus: &'a [Individu],
+    pub variables_individus: &'a [HashMap<String, f64>],
+    pub individu: Option<(&'a Individu, &'a HashMap<String, f64>)>,
+}
+
+impl Expression {
+    pub fn verifie(&self, contexte: &ContexteVerification) -> Result<TypeFormule, String> {
+        match self {
+            Expression::Nombre(_) => Ok(TypeFormule::Nombre),
+            Expression::Texte(_) => Ok(TypeFormule::Texte),
+            Expression::Booleen(_) => Ok(TypeFormule::Booleen),
+            Expression::Reference(Source::MenageCarac, nom) => {
+                match contexte.caracteristiques.contains(nom) {
+                    true => Ok(TypeFormule::Caracteristique),
+                    false => Err(format!(
+                        "la caractéristique {} n'est pas déclarée dans caracteristiques_dependencies",
+                        nom
+                    )),
+                }
+            }
+            Expression::Reference(Source::ParamsDict, nom) => {
+                match contexte.parametres.contains_key(nom) {
+                    true => Ok(TypeFormule::Nombre),
+                    false => Err(format!("le paramètre {} n'existe pas", nom)),
+                }
+            }
+            Expression::Reference(Source::Variables, nom) => {
+                match contexte.variables.contains(nom) {
+                    true => Ok(TypeFormule::Nombre),
+                    false => Err(format!(
+                        "la variable {} n'est calculée par aucune composante précédente",
+                        nom
+                    )),
+                }
+            }
+            Expression::Reference(Source::IndividuCarac | Source::IndividuVariables, _)
+                if !contexte.individu =>
+            {
+                Err("les individus ne sont lus que par une composante de niveau individu ou dans une agrégation (somme_individus...)".into())
+            }
+            Expression::Reference(Source::IndividuCarac, nom) => {
+                match contexte.caracteristiques_individus.contains(nom) {
+                    true => Ok(TypeFormule::Caracteristique),
+                    false => Err(format!(
+                        "la caractéristique {} n'est pas déclarée dans caracteristiques_individus",
+                        nom
+                    )),
+                }
+            }
+            Expression::Reference(Source::IndividuVariables, nom) => {
+                match contexte.variables_individus.contains(nom) {
+                    true => Ok(TypeFormule::Nombre),
+                    false => Err(format!(
+                        "la variable {} n'est calculée par aucune composante de niveau individu précédente",
+                        nom
+                    )),
+                }
+            }
+            Expression::Oppose(expression) => {
+                verifie_type(expression, contexte, TypeFormule::Nombre, "-")
+            }
+            Expression::Non(expression) => {
+                verifie_type(expression, contexte, TypeFormule::Booleen, "not")
+            }
+            Expression::Arithmetique(_, gauche, droite) => {
+                verifie_type(gauche, contexte, TypeFormule::Nombre, "un calcul")?;
+                verifie_type(droite, contexte, TypeFormule::Nombre, "un calcul")
+            }
+            Expression::Comparaison(operateur, gauche, droite) => {
+                let type_gauche = gauche.verifie(contexte)?;
+                let type_droite = droite.verifie(contexte)?;
+                let ordonne = !matches!(
+                    operateur,
+                    OperateurComparaison::Egal | OperateurComparaison::Different
+                );
+                if ordonne {
+                    verifie_type(gauche, contexte, TypeFormule::Nombre, "une comparaison")?;
+                    verifie_type(droite, contexte, TypeFormule::Nombre, "une comparaison")?;
+                } else if !(type_gauche.compatible(type_droite)
+                    || type_droite.compatible(type_gauche))
+                {
+                    return Err(format!(
+                        "comparaison entre des types différents ({:?} et {:?})",
+                        type_gauche, type_droite
+                    ));
+                }
+                Ok(TypeFormule::Booleen)
+            }
+            Expression::Et(gauche, droite) | Expression::Ou(gauche, droite) => {
+                verifie_type(gauche, contexte, TypeFormule::Booleen, "and/or")?;
+                verifie_type(droite, contexte, TypeFormule::Booleen, "and/or")
+            }
+            Expression::Condition {
+                condition,
+                alors,
+                sinon,
+            } => {
+                verifie_type(
+                    condition,
+                    contexte,
+                    TypeFormule::Booleen,
+                    "la condition d'un if",
+                )?;
+                let type_alors = alors.verifie(contexte)?;
+                let type_sinon = sinon.verifie(contexte)?;
+                match (type_alors, type_sinon) {
+                    (a, b) if a == b => Ok(a),
+                    (TypeFormule::Caracteristique, b) => Ok(b),
+                    (a, TypeFormule::Caracteristique) => Ok(a),
+                    (a, b) => Err(format!(
+                        "les deux branches d'un if doivent avoir le même type ({:?} et {:?})",
+                        a, b
+                    )),
+                }
+            }
+            Expression::Appel(fonction, arguments) => {
+                match (fonction, arguments.len()) {
+                    (Fonction::Alea, 0) | (Fonction::Min | Fonction::Max, 1..) => (),
+                    (Fonction::Alea, _) => return Err("alea() ne prend pas d'argument".into()),
+                    (_, _) => return Err(format!("{:?} attend au moins un argument", fonction)),
+                }
+                for argument in arguments {
+                    verifie_type(
+                        argument,
+                        contexte,
+                        TypeFormule::Nombre,
+                        &format!("{:?}", fonction),
+                    )?;
+                }
+                Ok(TypeFormule::Nombre)
+            }
+            Expression::Agregation(_, _) if contexte.individu => Err(
+                "les agrégations sur les individus ne sont possibles qu'au niveau ménage".into(),
+            ),
+            Expression::Agregation(agregation, expression) => {
+                let interieur = ContexteVerification {
+                    individu: true,
+                    ..*contexte
+                };
+                let operation = format!("{:?}", agregation);
+                match agregation {
+                    Agregation::Somme | Agregation::Max => {
+                        verifie_type(expression, &interieur, TypeFormule::Nombre, &operation)?;
+                        Ok(TypeFormule::Nombre)
+                    }
+                    Agregation::Nombre => {
+                        verifie_type(expression, &interieur, TypeFormule::Booleen, &operation)?;
+                        Ok(TypeFormule::Nombre)
+                    }
+                    Agregation::Existe => {
+                        verifie_type(expression, &interieur, TypeFormule::Booleen, &operation)
+                    }
+                }
+            }
+        }
+    }
+
+    // Évalue l'expression pour un ménage ; les références lues sont ajoutées à lectures
+    pub fn evalue(
+        &self,
+        contexte: &ContexteEvaluation,
+        lectures: &mut Vec<(Source, String)>,
+    ) -> Result<Valeur, String> {
+        match self {
+            Expression::Nombre(x) => Ok(Valeur::Nombre(*x)),
+            Expression::Texte(texte) => Ok(Valeur::Texte(texte.clone())),
+            Expression::Booleen(b) => Ok(Valeur::Booleen(*b)),
+            Expression::Reference(source, nom) => {
+                let valeur = match source {
+                    Source::MenageCarac => contexte
+                        .caracteristiques
+                        .get(nom)
+                        .map(Valeur::try_from)
+                        .transpose()
+                        .map_err(|e| format!("{:?}[\"{}\"] : {}", source, nom, e))?,
+                    Source::ParamsDict => contexte.parametres.get(nom).cloned().map(Valeur::Nombre),
+                    Source::Variables => contexte.variables.get(nom).cloned().map(Valeur::Nombre),
+                    Source::IndividuCarac => contexte
+                        .individu
+                        .and_then(|(individu, _)| individu.caracteristiques.get(nom))
+                        .map(Valeur::try_from)
+                        .transpose()
+                        .map_err(|e| format!("{:?}[\"{}\"] : {}", source, nom, e))?,
+                    Source::IndividuVariables => contexte
+                        .individu
+                        .and_then(|(_, variables)| variables.get(nom))
+                        .cloned()
+                        .map(Valeur::Nombre),
+                }
+                .ok_or_else(|| format!("{:?}[\"{}\"] introuvable", source, nom))?;
+                lectures.push((*source, nom.to_owned()));
+                Ok(valeur)
+            }
+            Expression::Oppose(expression) => {
+                Ok(Valeur::Nombre(-nombre(expression, contexte, lectures)?))
+            }
+            Expression::Non(expression) => {
+                Ok(Valeur::Booleen(!booleen(expression, contexte, lectures)?))
+            }
+            Expression::Arithmetique(operateur, gauche, droite) => {
+                let gauche = nombre(gauche, contexte, lectures)?;
+                let droite = nombre(droite, contexte, lectures)?;
+                let resultat = match operateur {
+                    OperateurArithmetique::Addition => gauche + droite,
+                    OperateurArithmetique::Soustraction => gauche - droite,
+                    OperateurArithmetique::Multiplication => gauche * droite,
+                    OperateurArithmetique::Division if droite == 0.0 => {
+                        return Err("division par zéro".into())
+                    }
+                    OperateurArithmetique::Division => gauche / droite,
+                };
+                Ok(Valeur::Nombre(resultat))
+            }
+            Expression::Comparaison(operateur, gauche, droite) => {
+                let gauche = gauche.evalue(contexte, lectures)?;
+                let droite = droite.evalue(contexte, lectures)?;
+                let resultat = match (operateur, &gauche, &droite) {
+                    (OperateurComparaison::Egal, _, _) => gauche == droite,
+                    (OperateurComparaison::Different, _, _) => gauche != droite,
+                    (operateur, Valeur::Nombre(a), Valeur::Nombre(b)) => match operateur {
+                        OperateurComparaison::Inferieur => a < b,
+                        OperateurComparaison::InferieurEgal => a <= b,
+                        OperateurComparaison::Superieur => a > b,
+                        _ => a >= b,
+                    },
+                    _ => {
+                        return Err(format!(
+                            "comparaison impossible entre {} et {}",
+                            gauche, droite
+                        ))
+                    }
+                };
+                Ok(Valeur::Booleen(resultat))
+            }
+            Expression::Et(gauche, droite) => Ok(Valeur::Booleen(
+                booleen(gauche, contexte, lectures)? && booleen(droite, contexte, lectures)?,
+            )),
+            Expression::Ou(gauche, droite) => Ok(Valeur::Booleen(
+                booleen(gauche, contexte, lectures)? || booleen(droite, contexte, lectures)?,
+            )),
+            Expression::Condition {
+                condition,
+                alors,
+                sinon,
+            } => match booleen(condition, contexte, lectures)? {
+                true => alors.evalue(contexte, lectures),
+                false => sinon.evalue(contexte, lectures),
+            },
+            Expression::Appel(fonction, arguments) => {
+                let valeurs = arguments
+                    .iter()
+                    .map(|argument| nombre(argument, contexte, lectures))
+                    .collect::<Result<Vec<f64>, String>>()?;
+                let resultat = match fonction {
+                    Fonction::Min => valeurs.into_iter().fold(f64::INFINITY, f64::min),
+                    Fonction::Max => valeurs.into_iter().fold(f64::NEG_INFINITY, f64::max),
+                    Fonction::Alea => contexte.aleatoire.uniforme(),
+                };
+                Ok(Valeur::Nombre(resultat))
+            }
+            Expression::Agregation(agregation, expression) => {
+                let individus = contexte.individus.iter().zip(contexte.variables_individus);
+                let contexte_individu = |(individu, variables)| ContexteEvaluation {
+                    individu: Some((individu, variables)),
+                    ..*contexte
+                };
+                let resultat = match agregation {
+                    Agregation::Somme | Agregation::Max => {
+                        let valeurs = individus
+                            .map(|individu| {
+                                nombre(expression, &contexte_individu(individu), lectures)
+                            })
+                            .collect::<Result<Vec<f64>, String>>()?;
+                        match agregation {
+                            Agregation::Somme => Valeur::Nombre(valeurs.into_iter().sum()),
+                            _ => Valeur::Nombre(
+                                valeurs.into_iter().reduce(f64::max).unwrap_or_default(),
+                            ),
+                        }
+                    }
+                    Agregation::Nombre => {
+                        let mut nombre_individus = 0.0;
+                        for individu in individus {
+                            if booleen(expression, &contexte_individu(individu), lectures)? {
+                                nombre_individus += 1.0;
+                            }
+                        }
+                        Valeur::Nombre(nombre_individus)
+                    }
+                    Agregation::Existe => {
+                        let mut existe = false;
+                        for individu in individus {
+                            if booleen(expression, &contexte_individu(individu), lectures)? {
+                                existe = true;
+                                break;
+                            }
+                        }
+                        Valeur::Booleen(existe)
+                    }
+                };
+                Ok(resultat)
+            }
+        }
+    }
+
+    // Ensemble des références lues par l'expression, quelles que soient les branches évaluées
+    pub fn references(&self, references: &mut HashSet<(Source, String)>) {
+        match self {
+            Expression::Nombre(_) | Expression::Texte(_) | Expression::Booleen(_) => (),
+            Expression::Reference(source, nom) => {
+                references.insert((*source, nom.to_owned()));
+            }
+            Expression::Oppose(expression)
+            | Expression::Non(expression)
+            | Expression::Agregation(_, expression) => expression.references(references),
+            Expression::Arithmetique(_, gauche, droite)
+            | Expression::Comparaison(_, gauche, droite)
+            | Expression::Et(gauche, droite)
+            | Expression::Ou(gauche, droite) => {
+                gauche.references(references);
+                droite.references(references);
+            }
+            Expression::Condition {
+                condition,
+                alors,
+                sinon,
+            } => {
+                condition.references(references);
+                alors.references(references);
+                sinon.references(references);
+            }
+            Expression::Appel(_, arguments) => arguments
+                .iter()
+                .for_each(|argument| argument.references(references)),
+        }
+    }
+}
+
+fn verifie_type(
+    expression: &Expression,
+    contexte: &ContexteVerification,
+    attendu: TypeFormule,
+    operation: &str,
+) -> Result<TypeFormule, String> {
+    let type_expression = expression.verifie(contexte)?;
+    match type_expression.compatible(attendu) {
+        true => Ok(attendu),
+        false => Err(format!(
+            "{:?} attendu pour {}, {:?} trouvé",
+            attendu, operation, type_expression
+        )),
+    }
+}
+
+fn nombre(
+    expression: &Expression,
+    contexte: &ContexteEvaluation,
+    lectures: &mut Vec<(Source, String)>,
+) -> Result<f64, String> {
+    match expression.evalue(contexte, lectures)? {
+        Valeur::Nombre(x) => Ok(x),
+        autre => Err(format!("nombre attendu, {} trouvé", autre)),
+    }
+}
+
+fn booleen(
+    expression: &Expression,
+    contexte: &ContexteEvaluation,
+    lectures: &mut Vec<(Source, String)>,
+) -> Result<bool, String> {
+    match expression.evalue(contexte, lectures)? {
+        Valeur::Booleen(b) => Ok(b),
+        autre => Err(format!("booléen attendu, {} trouvé", autre)),
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(try_from = "String")]
+pub struct Formula {
+    pub source: String,
+    pub expression: Expression,
+}
+
+fn parse(source: &str, nature: &str, filtre: bool) -> Result<Expression, String> {
+    let mut parser = Parser {
+        tokens: tokenize(source)?,
+        position: 0,
+        filtre,
+    };
+    let expression = parser
+        .expression()
+        .map_err(|e| format!("{} \"{}\" invalide : {}", nature, source.trim(), e))?;
+    if let Some(token) = parser.peek() {
+        return Err(format!(
+            "{} \"{}\" invalide : '{}' inattendu",
+            nature,
+            source.trim(),
+            token
+        ));
+    }
+    Ok(expression)
+}
+
+impl TryFrom<String> for Formula {
+    type Error = String;
+
+    fn try_from(source: String) -> Result<Self, Self::Error> {
+        let expression = parse(&source, "formule", false)?;
+        Ok(Formula { source, expression })
+    }
+}
+
+impl Formula {
+    // Vérification des références et des types à la lecture de la politique
+    pub fn verifie(&self, contexte: &ContexteVerification) -> Result<(), String> {
+        verifie_type(
+            &self.expression,
+            contexte,
+            TypeFormule::Nombre,
+            "le résultat",
+        )?;
+        Ok(())
+    }
+
+    pub fn evalue(
+        &self,
+        contexte: &ContexteEvaluation,
+        lectures: &mut Vec<(Source, String)>,
+    ) -> Result<f64, String> {
+        nombre(&self.expression, contexte, lectures)
+    }
+
+    pub fn references(&self) -> HashSet<(Source, String)> {
+        let mut references = HashSet::new();
+        self.expression.references(&mut references);
+        references
+    }
+}
+
+// Filtre des ménages appliqué à leur chargement : une expression booléenne sur leurs
+// caractéristiques, désignées par leur nom seul ou par MenageCarac["..."]
+//   Age >= 60 and GIR <= 4
+//   MenageCarac["Commune"] == "Paris" or not Revenu > 3000
+#[derive(Debug, Clone, PartialEq)]
+pub struct Filtre {
+    pub source: String,
+    pub expression: Expression,
+}
+
+impl TryFrom<String> for Filtre {
+    type Error = String;
+
+    fn try_from(source: String) -> Result<Self, Self::Error> {
+        let expression = parse(&source, "filtre", true)?;
+        Ok(Filtre { source, expression })
+    }
+}
+
+impl fmt::Display for Filtre {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source.trim())
+    }
+}
+
+impl Filtre {
+    // Caractéristiques lues par le filtre, à charger avec les ménages
+    pub fn caracteristiques(&self) -> HashSet<String> {
+        let mut references = HashSet::new();
+        self.expression.references(&mut references);
+        references.into_iter().map(|(_, nom)| nom).collect()
+    }
+
+    // Vérification des références et des types, une fois les caractéristiques des ménages connues
+    pub fn verifie(&self, caracteristiques: &[String]) -> Result<(), String> {
+        if let Some(absente) = self
+            .caracteristiques()
+            .into_iter()
+            .find(|nom| !caracteristiques.contains(nom))
+        {
+            return Err(format!(
+                "la caractéristique {} est absente des ménages",
+                absente
+            ));
+        }
+
+        let contexte = ContexteVerification {
+            caracteristiques,
+            parametres: &HashMap::new(),
+            variables: &HashSet::new(),
+            caracteristiques_individus: &[],
+            variables_individus: &HashSet::new(),
+            individu: false,
+        };
+        verifie_type(
+            &self.expression,
+            &contexte,
+            TypeFormule::Booleen,
+            "le filtre",
+        )?;
+        Ok(())
+    }
+
+    // Le ménage est-il gardé ? Le filtre ne fait pas de tirage : l'aléatoire n'est jamais lu
+    pub fn garde(
+        &self,
+        caracteristiques: &HashMap<String, Caracteristique>,
+    ) -> Result<bool, String> {
+        let contexte = ContexteEvaluation {
+            caracteristiques,
+            parametres: &HashMap::new(),
+            variables: &HashMap::new(),
+            aleatoire: &Aleatoire::new(0, "", "filtre"),
+            individus: &[],
+            variables_individus: &[],
+            individu: None,
+        };
+        booleen(&self.expression, &contexte, &mut vec![])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contexte_verification_test<'a>(
+        parametres: &'a HashMap<String, f64>,
+        variables: &'a HashSet<String>,
+        caracteristiques: &'a [String],
+    ) -> ContexteVerification<'a> {
+        ContexteVerification {
+            caracteristiques,
+            parametres,
+            variables,
+            caracteristiques_individus: &[],
+            variables_individus: variables,
+            individu: false,
+        }
+    }
+
+    #[test]
+    fn ok_parse_precedence() -> Result<(), String> {
+        let formula = Formula::try_from("1 + 2 * 3".to_string())?;
+
+        let wanted = Expression::Arithmetique(
+            OperateurArithmetique::Addition,
+            Box::new(Expression::Nombre(1.0)),
+            Box::new(Expression::Arithmetique(
+                OperateurArithmetique::Multiplication,
+                Box::new(Expression::Nombre(2.0)),
+                Box::new(Expression::Nombre(3.0)),
+            )),
+        );
+
+        assert_eq!(wanted, formula.expression);
+        Ok(())
+    }
+
+    #[test]
+    fn err_parse_unbalanced() {
+        let wanted = true;
+        let result = Formula::try_from("min(1, 2".to_string()).is_err()
+            && Formula::try_from("1 +".to_string()).is_err()
+            && Formula::try_from("Variables[plan]".to_string()).is_err();
+        assert_eq!(wanted, result);
+    }
+
+    #[test]
+    fn ok_evalue_formula() -> Result<(), String> {
+        let formula = Formula::try_from(
+            r#"min(Variables["plan_notif"] * ParamsDict["taux"], 100) if MenageCarac["Age"] >= 60 and MenageCarac["Logement"] == "Locataire" else 0"#
+                .to_string(),
+        )?;
+
+        let caracteristiques = HashMap::from([
+            ("Age".to_string(), Caracteristique::Entier(75)),
+            (
+                "Logement".to_string(),
+                Caracteristique::Textuel("Locataire".into()),
+            ),
+        ]);
+        let parametres = HashMap::from([("taux".to_string(), 0.5)]);
+        let variables = HashMap::from([("plan_notif".to_string(), 150.0)]);
+        let contexte = ContexteEvaluation {
+            caracteristiques: &caracteristiques,
+            parametres: &parametres,
+            variables: &variables,
+            aleatoire: &Aleatoire::new(0, "1", "test"),
+            individus: &[],
+            variables_individus: &[],
+            individu: None,
+        };
+
+        let mut lectures = vec![];
+        let result = formula.evalue(&contexte, &mut lectures)?;
+
+        assert_eq!(75.0, result);
+        assert_eq!(4, lectures.len());
+        Ok(())
+    }
+
+    #[test]
+    fn err_verifie_unknown_references_and_types() -> Result<(), String> {
+        let parametres = HashMap::from([("taux".to_string(), 0.5)]);
+        let variables = HashSet::from(["plan_notif".to_string()]);
+        let caracteristiques = vec!["Age".to_string()];
+        let contexte = contexte_verification_test(&parametres, &variables, &caracteristiques);
+
+        let valide =
+            Formula::try_from(r#"Variables["plan_notif"] * ParamsDict["taux"]"#.to_string())?;
+        assert!(valide.verifie(&contexte).is_ok());
+
+        for source in [
+            r#"ParamsDict["inconnu"]"#,
+            r#"Variables["plan_cons"]"#,
+            r#"MenageCarac["Revenu"]"#,
+            r#"MenageCarac["Age"] > 60"#,
+            r#"1 if 2 else 0"#,
+            r#""texte" + 1"#,
+        ] {
+            let formula = Formula::try_from(source.to_string())?;
+            assert!(formula.verifie(&contexte).is_err(), "{}", source);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn ok_references_all_branches() -> Result<(), String> {
+        let formula = Formula::try_from(
+            r#"Variables["plan_notif"] if MenageCarac["Age"] >= 60 else -ParamsDict["forfait"]"#
+                .to_string(),
+        )?;
+
+        let wanted = HashSet::from([
+            (Source::Variables, "plan_notif".to_string()),
+            (Source::MenageCarac, "Age".to_string()),
+            (Source::ParamsDict, "forfait".to_string()),
+        ]);
+        assert_eq!(wanted, formula.references());
+        Ok(())
+    }
+
+    #[test]
+    fn ok_agregations_individus() -> Result<(), String> {
+        let formula = Formula::try_from(
+            r#"somme_individus(IndividuVariables["salaire_net"]) + max_individus(IndividuCarac["Age"]) + nombre_individus(IndividuCarac["Age"] >= 18) + (1 if existe_individu(IndividuCarac["Age"] < 3) else 0)"#
+                .to_string(),
+        )?;
+
+        let (parametres, variables) = (HashMap::new(), HashSet::new());
+        let caracteristiques_individus = vec!["Age".to_string()];
+        let variables_individus = HashSet::from(["salaire_net".to_string()]);
+        let contexte = ContexteVerification {
+            caracteristiques_individus: &caracteristiques_individus,
+            variables_individus: &variables_individus,
+            ..contexte_verification_test(&parametres, &variables, &[])
+        };
+        assert!(formula.verifie(&contexte).is_ok());
+
+        // Hors agrégation, les individus ne sont pas lisibles au niveau du ménage
+        for source in [
+            r#"IndividuCarac["Age"]"#,
+            r#"somme_individus(somme_individus(IndividuCarac["Age"]))"#,
+            r#"nombre_individus(IndividuVariables["salaire_net"])"#,
+        ] {
+            let formula = Formula::try_from(source.to_string())?;
+            assert!(formula.verifie(&contexte).is_err(), "{}", source);
+        }
+
+        let individus: Vec<Individu> = [(1, 40), (2, 38), (3, 2)]
+            .into_iter()
+            .map(|(index, age)| Individu {
+                index,
+                caracteristiques: HashMap::from([(
+                    "Age".to_string(),
+                    Caracteristique::Entier(age),
+                )]),
+            })
+            .collect();
+        let variables_individus = [1000.0, 500.0, 0.0]
+            .map(|salaire| HashMap::from([("salaire_net".to_string(), salaire)]));
+        let contexte = ContexteEvaluation {
+            caracteristiques: &HashMap::new(),
+            parametres: &HashMap::new(),
+            variables: &HashMap::new(),
+            aleatoire: &Aleatoire::new(0, "1", "test"),
+            individus: &individus,
+            variables_individus: &variables_individus,
+            individu: None,
+        };
+
+        let result = formula.evalue(&contexte, &mut vec![])?;
+
+        assert_eq!(1500.0 + 40.0 + 2.0 + 1.0, result);
+        Ok(())
+    }
+
+    #[test]
+    fn ok_filtre_noms_seuls() -> Result<(), String> {
+        let filtre = Filtre::try_from(
+            r#"Age >= 60 and MenageCarac["GIR"] <= 4 and not Commune == "Paris""#.to_string(),
+        )?;
+        let caracteristiques = vec!["Age".to_string(), "GIR".to_string(), "Commune".to_string()];
+        filtre.verifie(&caracteristiques)?;
+
+        let menage = |age: i32| {
+            HashMap::from([
+                ("Age".to_string(), Caracteristique::Entier(age)),
+                ("GIR".to_string(), Caracteristique::Entier(2)),
+                (
+                    "Commune".to_string(),
+                    Caracteristique::Textuel("Lyon".into()),
+                ),
+            ])
+        };
+
+        assert_eq!(
+            (true, false),
+            (filtre.garde(&menage(75))?, filtre.garde(&menage(40))?)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn err_filtre_invalide() -> Result<(), String> {
+        let caracteristiques = vec!["Age".to_string()];
+
+        let wanted = true;
+        let result = Filtre::try_from(r#"Age > ParamsDict["seuil"]"#.to_string()).is_err()
+            && Filtre::try_from("alea() < 0.5".to_string()).is_err()
+            && Filtre::try_from("Age >= and".to_string()).is_err()
+            && Filtre::try_from("Age + 1".to_string())?
+                .verifie(&caracteristiques)
+                .is_err()
+            && Filtre::try_from("Revenu > 0".to_string())?
+                .verifie(&caracteristiques)
+                .is_err();
+        assert_eq!(wanted, result);
+        Ok(())
+    }
+}
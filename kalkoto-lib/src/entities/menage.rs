@@ -1,13 +1,40 @@
+use chrono::NaiveDate;
+#[cfg(feature = "python")]
 use pyo3::prelude::*;
+use serde::Serialize;
 use std::collections::HashMap;
 use std::fmt;
 use std::mem;
 
-#[derive(IntoPyObject, Clone, Debug, PartialEq)]
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
 pub enum Caracteristique {
     Entier(i32),
     Numeric(f64),
     Textuel(String),
+    Booleen(bool),
+    Date(NaiveDate),
+    Manquant, //Valeur manquante (NA, champ CSV vide), traitée selon ValeursManquantes
+}
+
+// Les booléens sont transmis aux fonctions Python sous la forme de bool, les dates de
+// datetime.date et les valeurs manquantes de None
+#[cfg(feature = "python")]
+impl<'py> IntoPyObject<'py> for Caracteristique {
+    type Target = PyAny;
+    type Output = Bound<'py, PyAny>;
+    type Error = PyErr;
+
+    fn into_pyobject(self, py: Python<'py>) -> Result<Self::Output, Self::Error> {
+        Ok(match self {
+            Caracteristique::Entier(entier) => entier.into_pyobject(py)?.into_any(),
+            Caracteristique::Numeric(numeric) => numeric.into_pyobject(py)?.into_any(),
+            Caracteristique::Textuel(texte) => texte.into_pyobject(py)?.into_any(),
+            Caracteristique::Booleen(booleen) => booleen.into_pyobject(py)?.to_owned().into_any(),
+            Caracteristique::Date(date) => date.into_pyobject(py)?.into_any(),
+            Caracteristique::Manquant => py.None().into_bound(py),
+        })
+    }
 }
 
 impl fmt::Display for Caracteristique {
@@ -16,13 +43,27 @@ impl fmt::Display for Caracteristique {
             Caracteristique::Entier(i) => write!(f, "{i}"),
             Caracteristique::Numeric(x) => write!(f, "{x}"),
             Caracteristique::Textuel(word) => write!(f, "{word}"),
+            Caracteristique::Booleen(b) => write!(f, "{b}"),
+            Caracteristique::Date(date) => write!(f, "{}", date.format("%Y-%m-%d")),
+            Caracteristique::Manquant => Ok(()),
         }
     }
 }
 
+impl Caracteristique {
+    // Une valeur manquante est compatible avec tous les types
+    pub fn meme_type(&self, other: &Caracteristique) -> bool {
+        matches!(self, Caracteristique::Manquant)
+            || matches!(other, Caracteristique::Manquant)
+            || mem::discriminant(self) == mem::discriminant(other)
+    }
+}
+
 impl From<String> for Caracteristique {
     fn from(string: String) -> Caracteristique {
-        if let Ok(entier) = string.parse::<i32>() {
+        if string.is_empty() {
+            Caracteristique::Manquant
+        } else if let Ok(entier) = string.parse::<i32>() {
             Caracteristique::Entier(entier)
         } else if let Ok(numeric) = string.parse::<f64>() {
             Caracteristique::Numeric(numeric)
@@ -32,20 +73,50 @@ impl From<String> for Caracteristique {
     }
 }
 
+// Personne d'un ménage, lue dans la table des individus et rattachée par l'identifiant du ménage
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Individu {
+    pub index: i32, //Index de la ligne dans la table des individus
+    pub caracteristiques: HashMap<String, Caracteristique>,
+}
+
 #[derive(Debug, Clone, PartialEq, Default)]
 pub struct Menage {
     pub index: i32,
+    pub identifiant: Option<String>, //Valeur de la colonne identifiant des ménages, si elle est choisie
+    pub poids: Option<f64>, //Poids de sondage du ménage, si une colonne de poids est choisie
     pub caracteristiques: HashMap<String, Caracteristique>,
+    pub individus: Vec<Individu>,
 }
 
 impl Menage {
     pub fn new(index: i32) -> Self {
         Self {
             index,
+            identifiant: None,
+            poids: None,
             caracteristiques: HashMap::new(),
+            individus: vec![],
         }
     }
 
+    // Clé du ménage dans les résultats : son identifiant, ou à défaut son index
+    pub fn cle(&self) -> String {
+        self.identifiant
+            .clone()
+            .unwrap_or_else(|| self.index.to_string())
+    }
+
+    // Clé des tirages aléatoires d'un individu : la clé de son ménage et son rang dans le ménage
+    pub fn cle_individu(&self, rang: usize) -> String {
+        format!("{}/{}", self.cle(), rang)
+    }
+
+    // Nombre de ménages réels que représente le ménage : son poids, ou 1 sans colonne de poids
+    pub fn ponderation(&self) -> f64 {
+        self.poids.unwrap_or(1.0)
+    }
+
     pub fn compare_type_carac(&self, other_menage: &Self) -> (bool, i32, String) {
         let mut validator = true;
         let mut fault_index = -1;
@@ -54,8 +125,7 @@ impl Menage {
         for (nom_carac, type_carac) in &self.caracteristiques {
             match other_menage.caracteristiques.get(nom_carac) {
                 Some(other_type_carac) => {
-                    validator = validator
-                        && (mem::discriminant(type_carac) == mem::discriminant(other_type_carac))
+                    validator = validator && type_carac.meme_type(other_type_carac)
                 }
                 None => {
                     validator = false;
@@ -74,7 +144,7 @@ impl Menage {
 
 impl fmt::Display for Menage {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        writeln!(f, "Le ménage {} a les caractéristiques :", self.index)?;
+        writeln!(f, "Le ménage {} a les caractéristiques :", self.cle())?;
         for (key, value) in &self.caracteristiques {
             writeln!(f, "{} -> {}", key, value)?;
         }
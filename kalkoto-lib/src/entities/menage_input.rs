@@ -1,10 +1,13 @@
-use crate::entities::menage::Menage;
+use crate::entities::formula::Filtre;
+use crate::entities::menage::{Caracteristique, Individu, Menage};
+use crate::entities::schema_menages::SchemaMenages;
 use crate::KalkotoResult;
 use crate::adapters::input_adapters::MenageListAdapterError;
-use crossterm::style::Stylize;
+use crate::terminal;
 use itertools::Itertools;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt::Display;
+use std::str::FromStr;
 
 //Marker trait pour définir les différents états possibles d'une liste ménages
 pub trait MenageList {}
@@ -19,16 +22,156 @@ impl MenageList for Unvalid {}
 pub struct Valid(Vec<Menage>);
 impl MenageList for Valid {}
 
+// Traitement d'une valeur manquante, choisi au chargement des ménages :
+// - Erreur : le chargement échoue (comportement par défaut)
+// - Defaut : la valeur manquante est remplacée par une valeur par défaut
+// - Transmettre : la valeur est transmise aux composantes (None en Python, () en Rhai)
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum TraitementManquant {
+    #[default]
+    Erreur,
+    Defaut(Caracteristique),
+    Transmettre,
+}
+
+// "erreur", "none" ou une valeur par défaut (option --manquant de la ligne de commande)
+impl FromStr for TraitementManquant {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "" => Err("Le traitement des valeurs manquantes ne peut pas être vide".to_string()),
+            "erreur" => Ok(TraitementManquant::Erreur),
+            "none" => Ok(TraitementManquant::Transmettre),
+            valeur => Ok(TraitementManquant::Defaut(Caracteristique::from(valeur.to_string()))),
+        }
+    }
+}
+
+// Traitement des valeurs manquantes colonne par colonne, avec un traitement pour les autres colonnes
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ValeursManquantes {
+    pub par_defaut: TraitementManquant,
+    pub colonnes: HashMap<String, TraitementManquant>,
+}
+
+impl ValeursManquantes {
+    pub fn new() -> Self {
+        ValeursManquantes::default()
+    }
+
+    pub fn pour_toutes(traitement: TraitementManquant) -> Self {
+        ValeursManquantes {
+            par_defaut: traitement,
+            colonnes: HashMap::new(),
+        }
+    }
+
+    pub fn colonne(mut self, nom_colonne: &str, traitement: TraitementManquant) -> Self {
+        self.colonnes.insert(nom_colonne.to_string(), traitement);
+        self
+    }
+
+    pub fn traitement(&self, nom_colonne: &str) -> &TraitementManquant {
+        self.colonnes.get(nom_colonne).unwrap_or(&self.par_defaut)
+    }
+
+    fn applique(&self, menage: &mut Menage) -> Result<(), MenageListAdapterError> {
+        for (nom_colonne, caracteristique) in menage.caracteristiques.iter_mut() {
+            if *caracteristique != Caracteristique::Manquant {
+                continue;
+            }
+            match self.traitement(nom_colonne) {
+                TraitementManquant::Erreur => {
+                    return Err(MenageListAdapterError::ValeurManquante {
+                        colonne: nom_colonne.clone(),
+                        menage: menage.index,
+                    })
+                }
+                TraitementManquant::Defaut(valeur) => *caracteristique = valeur.clone(),
+                TraitementManquant::Transmettre => (),
+            }
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Default)]
 pub struct MenageInput {
     pub set_caracteristiques_valide: HashSet<String>,
     pub liste_menage_valide: Vec<Menage>,
+    pub colonne_identifiant: Option<String>, //Nom de la colonne clé des résultats, si les ménages ont un identifiant
+    pub colonne_poids: Option<String>, //Nom de la colonne des poids de sondage, reprise dans les résultats
+    pub filtre: Option<Filtre>,
+    pub menages_ecartes: usize, //Nombre de ménages écartés par le filtre
+    pub cles_menages_ecartes: HashSet<String>, //Clés des ménages écartés par le filtre, pour ignorer leurs individus
+    pub set_caracteristiques_individus: HashSet<String>, //Vide si aucune table des individus n'est rattachée
 }
 
 impl MenageInput {
     pub fn get_valid_input_menages(self) -> (HashSet<String>, Vec<Menage>) {
         (self.set_caracteristiques_valide, self.liste_menage_valide)
     }
+
+    // Nombre de ménages réels représentés par les ménages simulés
+    pub fn effectif_pondere(&self) -> f64 {
+        self.liste_menage_valide.iter().map(Menage::ponderation).sum()
+    }
+
+    // Total de chaque composante sur les ménages simulés, pondéré par leur poids
+    pub fn totaux_ponderes(&self, resultats: &[HashMap<String, f64>]) -> BTreeMap<String, f64> {
+        let mut totaux = BTreeMap::new();
+        for (menage, resultats_menage) in self.liste_menage_valide.iter().zip(resultats) {
+            for (composante, valeur) in resultats_menage {
+                *totaux.entry(composante.clone()).or_insert(0.0) += menage.ponderation() * valeur;
+            }
+        }
+        totaux
+    }
+
+    // Rattache à chaque ménage les individus d'une table lue avec MenageInputBuilder::table_individus,
+    // par l'identifiant des ménages. Seuls les individus d'un ménage écarté par le filtre sont ignorés
+    pub fn rattache_individus(&mut self, individus: MenageInput) -> KalkotoResult<()> {
+        let Some(colonne_menage) = individus.colonne_identifiant else {
+            return Err(From::from(MenageListAdapterError::Validation {
+                fault_index: -1,
+                cause: "La table des individus n'a pas de colonne de rattachement aux ménages".to_string(),
+                conseil: "Lire la table des individus avec la colonne identifiant des ménages".to_string(),
+            }));
+        };
+        if self.colonne_identifiant.is_none() {
+            return Err(From::from(MenageListAdapterError::Validation {
+                fault_index: -1,
+                cause: "Les individus ne peuvent être rattachés qu'à des ménages ayant un identifiant".to_string(),
+                conseil: "Indiquer la colonne identifiant des ménages".to_string(),
+            }));
+        }
+
+        let positions: HashMap<String, usize> = self.liste_menage_valide
+            .iter()
+            .enumerate()
+            .map(|(position, menage)| (menage.cle(), position))
+            .collect();
+
+        for individu in individus.liste_menage_valide {
+            let identifiant_menage = individu.cle();
+            match positions.get(&identifiant_menage) {
+                Some(position) => self.liste_menage_valide[*position].individus.push(Individu {
+                    index: individu.index,
+                    caracteristiques: individu.caracteristiques,
+                }),
+                None if self.cles_menages_ecartes.contains(&identifiant_menage) => (),
+                None => return Err(From::from(MenageListAdapterError::Validation {
+                    fault_index: individu.index,
+                    cause: format!("L'individu {} est rattaché au ménage {identifiant_menage}, absent du fichier ménages", individu.index),
+                    conseil: format!("Vérifier la colonne {colonne_menage} de la table des individus"),
+                })),
+            }
+        }
+
+        self.set_caracteristiques_individus = individus.set_caracteristiques_valide;
+        Ok(())
+    }
 }
 
 impl Display for MenageInput {
@@ -36,13 +179,38 @@ impl Display for MenageInput {
         writeln!(
             f,
             "{}",
-            "Input Ménages correctement initialisé !\n".green().bold()
+            terminal::succes("Input Ménages correctement initialisé !\n")
         )?;
         writeln!(
             f,
             ">>>> Liste des caractéristiques trouvées dans l'input Ménages :\n\n{:?}\n",
             self.set_caracteristiques_valide
         )?;
+        if !self.set_caracteristiques_individus.is_empty() {
+            writeln!(
+                f,
+                ">>>> {} individus rattachés aux ménages, de caractéristiques :\n\n{:?}\n",
+                self.liste_menage_valide.iter().map(|menage| menage.individus.len()).sum::<usize>(),
+                self.set_caracteristiques_individus
+            )?;
+        }
+        if let Some(colonne_poids) = &self.colonne_poids {
+            writeln!(
+                f,
+                ">>>> Poids de sondage lus dans la colonne {} : {} ménages représentés\n",
+                colonne_poids,
+                self.effectif_pondere()
+            )?;
+        }
+        if let Some(filtre) = &self.filtre {
+            writeln!(
+                f,
+                ">>>> Filtre des ménages {} : {} ménages gardés, {} écartés\n",
+                filtre,
+                self.liste_menage_valide.len(),
+                self.menages_ecartes
+            )?;
+        }
         writeln!(
             f,
             ">>>> Exemple du premier ménage trouvé dans l'input Ménages :\n\n{:?}",
@@ -55,12 +223,71 @@ impl Display for MenageInput {
 pub struct MenageInputBuilder<U: MenageList> {
     set_caracteristiques: Option<HashSet<String>>,
     liste_menage: U,
+    valeurs_manquantes: ValeursManquantes,
+    schema: Option<SchemaMenages>,
+    colonne_identifiant: Option<String>,
+    colonne_poids: Option<String>,
+    colonnes: Option<HashSet<String>>,
+    filtre: Option<Filtre>,
+    menages_ecartes: usize,
+    cles_menages_ecartes: HashSet<String>,
+    table_individus: bool,
 }
 
 impl MenageInputBuilder<EmptyList> {
     pub fn new() -> Self {
         MenageInputBuilder::default()
     }
+
+    pub fn with_valeurs_manquantes(self, valeurs_manquantes: ValeursManquantes) -> Self {
+        MenageInputBuilder {
+            valeurs_manquantes,
+            ..self
+        }
+    }
+
+    pub fn with_schema(self, schema: Option<SchemaMenages>) -> Self {
+        MenageInputBuilder { schema, ..self }
+    }
+
+    // La colonne identifiant n'est pas une caractéristique : ses valeurs, uniques, deviennent
+    // l'identifiant des ménages
+    pub fn with_colonne_identifiant(self, colonne_identifiant: Option<String>) -> Self {
+        MenageInputBuilder {
+            colonne_identifiant,
+            ..self
+        }
+    }
+
+    // La colonne de poids n'est pas une caractéristique : ses valeurs, strictement positives,
+    // deviennent le poids de sondage des ménages
+    pub fn with_colonne_poids(self, colonne_poids: Option<String>) -> Self {
+        MenageInputBuilder {
+            colonne_poids,
+            ..self
+        }
+    }
+
+    // Projection : seules ces colonnes (et la colonne identifiant) sont gardées comme
+    // caractéristiques. Sans projection, toutes les colonnes le sont
+    pub fn with_colonnes(self, colonnes: Option<HashSet<String>>) -> Self {
+        MenageInputBuilder { colonnes, ..self }
+    }
+
+    // Seuls les ménages vérifiant le filtre sont gardés, une fois les valeurs manquantes
+    // traitées et le schéma appliqué. Les ménages gardés conservent leur index
+    pub fn with_filtre(self, filtre: Option<Filtre>) -> Self {
+        MenageInputBuilder { filtre, ..self }
+    }
+
+    // Table des individus : la colonne identifiant est l'identifiant du ménage de chaque
+    // individu, partagé par les individus d'un même ménage
+    pub fn table_individus(self) -> Self {
+        MenageInputBuilder {
+            table_individus: true,
+            ..self
+        }
+    }
 }
 
 impl<U> MenageInputBuilder<U> where U: MenageList{
@@ -71,6 +298,15 @@ impl<U> MenageInputBuilder<U> where U: MenageList{
         MenageInputBuilder {
             set_caracteristiques: None,
             liste_menage: Unvalid(invalid_liste_menage.to_owned()),
+            valeurs_manquantes: self.valeurs_manquantes,
+            schema: self.schema,
+            colonne_identifiant: self.colonne_identifiant,
+            colonne_poids: self.colonne_poids,
+            colonnes: self.colonnes,
+            filtre: self.filtre,
+            menages_ecartes: 0,
+            cles_menages_ecartes: HashSet::new(),
+            table_individus: self.table_individus,
         }
     }
 }
@@ -104,11 +340,188 @@ impl MenageInputBuilder<Unvalid> {
             }));
         };
 
+        // Les valeurs manquantes sont compatibles avec tous les types : on compare aussi chaque
+        // ménage au premier type renseigné de chaque colonne
+        let mut types_colonnes: HashMap<&String, &Caracteristique> = HashMap::new();
+        for menage in unvalidated_liste_menage {
+            for (nom_colonne, caracteristique) in &menage.caracteristiques {
+                if *caracteristique == Caracteristique::Manquant {
+                    continue;
+                }
+                let type_colonne = types_colonnes.entry(nom_colonne).or_insert(caracteristique);
+                if !type_colonne.meme_type(caracteristique) {
+                    return Err(crate::errors::KalkotoError::ListMenageError(MenageListAdapterError::Validation {
+                        fault_index: menage.index,
+                        cause: format!("Le type de la caractéristique {nom_colonne} n'est pas le même pour tous les ménages"),
+                        conseil: "Vérifier le fichier d'input".to_owned(),
+                    }));
+                }
+            }
+        }
+
      Ok(true)
     }
 
-    pub fn validate_liste_menage(self) -> KalkotoResult<MenageInputBuilder<Valid>> {
+    // Retire la colonne identifiant des caractéristiques de chaque ménage, en vérifiant que
+    // tous les ménages ont un identifiant différent
+    fn extrait_identifiants(&mut self) -> Result<(), MenageListAdapterError> {
+        let Some(colonne_identifiant) = &self.colonne_identifiant else {
+            return Ok(());
+        };
+
+        let mut identifiants_vus = HashSet::new();
+        for menage in self.liste_menage.0.iter_mut() {
+            let identifiant = match menage.caracteristiques.remove(colonne_identifiant) {
+                None | Some(Caracteristique::Manquant) => {
+                    return Err(MenageListAdapterError::ValeurManquante {
+                        colonne: colonne_identifiant.clone(),
+                        menage: menage.index,
+                    })
+                }
+                Some(caracteristique) => caracteristique.to_string(),
+            };
+
+            if !self.table_individus && !identifiants_vus.insert(identifiant.clone()) {
+                return Err(MenageListAdapterError::Validation {
+                    fault_index: menage.index,
+                    cause: format!("L'identifiant {identifiant} est utilisé par plusieurs ménages"),
+                    conseil: format!("Vérifier l'unicité de la colonne {colonne_identifiant}"),
+                });
+            }
+            menage.identifiant = Some(identifiant);
+        }
+        Ok(())
+    }
+
+    // Retire la colonne de poids des caractéristiques de chaque ménage, en vérifiant que
+    // chaque poids est un nombre strictement positif
+    fn extrait_poids(&mut self) -> Result<(), MenageListAdapterError> {
+        let Some(colonne_poids) = &self.colonne_poids else {
+            return Ok(());
+        };
+
+        for menage in self.liste_menage.0.iter_mut() {
+            let poids = match menage.caracteristiques.remove(colonne_poids) {
+                None | Some(Caracteristique::Manquant) => {
+                    return Err(MenageListAdapterError::ValeurManquante {
+                        colonne: colonne_poids.clone(),
+                        menage: menage.index,
+                    })
+                }
+                Some(Caracteristique::Entier(entier)) => f64::from(entier),
+                Some(Caracteristique::Numeric(numeric)) => numeric,
+                Some(caracteristique) => {
+                    return Err(MenageListAdapterError::Validation {
+                        fault_index: menage.index,
+                        cause: format!("Le poids {caracteristique} n'est pas un nombre"),
+                        conseil: format!("Vérifier la colonne de poids {colonne_poids}"),
+                    })
+                }
+            };
+
+            if !(poids.is_finite() && poids > 0.0) {
+                return Err(MenageListAdapterError::Validation {
+                    fault_index: menage.index,
+                    cause: format!("Le poids {poids} n'est pas strictement positif"),
+                    conseil: format!("Vérifier la colonne de poids {colonne_poids}"),
+                });
+            }
+            menage.poids = Some(poids);
+        }
+        Ok(())
+    }
+
+    // Retire les colonnes hors de la projection, que l'adapteur les ait déjà écartées ou non.
+    // Une colonne demandée doit être présente dans le fichier ménages
+    fn projete(&mut self) -> Result<(), MenageListAdapterError> {
+        let Some(colonnes) = &self.colonnes else {
+            return Ok(());
+        };
+
+        for menage in self.liste_menage.0.iter_mut() {
+            menage.caracteristiques.retain(|nom, _| {
+                colonnes.contains(nom)
+                    || self.colonne_identifiant.as_ref() == Some(nom)
+                    || self.colonne_poids.as_ref() == Some(nom)
+            });
+            if let Some(absente) = colonnes.iter().find(|nom| !menage.caracteristiques.contains_key(*nom)) {
+                return Err(MenageListAdapterError::Validation {
+                    fault_index: menage.index,
+                    cause: format!("La colonne {absente} demandée est absente du fichier ménages"),
+                    conseil: "Vérifier les caractéristiques des politiques et les colonnes reprises dans les résultats".to_string(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    // Garde les ménages qui vérifient le filtre et renvoie les clés des ménages écartés
+    fn filtre(&mut self) -> Result<HashSet<String>, MenageListAdapterError> {
+        let Some(filtre) = &self.filtre else {
+            return Ok(HashSet::new());
+        };
+
+        let caracteristiques: Vec<String> = self.liste_menage.0.first()
+            .map(|menage| menage.caracteristiques.keys().cloned().collect())
+            .unwrap_or_default();
+        filtre.verifie(&caracteristiques).map_err(|cause| MenageListAdapterError::Validation {
+            fault_index: -1,
+            cause: format!("Filtre des ménages invalide : {cause}"),
+            conseil: "Vérifier l'expression du filtre".to_string(),
+        })?;
+
+        let mut menages_gardes = Vec::with_capacity(self.liste_menage.0.len());
+        let mut cles_ecartees = HashSet::new();
+        for menage in self.liste_menage.0.drain(..) {
+            match filtre.garde(&menage.caracteristiques) {
+                Ok(true) => menages_gardes.push(menage),
+                Ok(false) => {
+                    cles_ecartees.insert(menage.cle());
+                }
+                Err(cause) => return Err(MenageListAdapterError::Validation {
+                    fault_index: menage.index,
+                    cause: format!("Le filtre {filtre} ne peut pas être évalué : {cause}"),
+                    conseil: "Vérifier les valeurs manquantes des caractéristiques du filtre".to_string(),
+                }),
+            }
+        }
+
+        if menages_gardes.is_empty() {
+            return Err(MenageListAdapterError::Validation {
+                fault_index: -1,
+                cause: format!("Aucun ménage ne vérifie le filtre {filtre}"),
+                conseil: "Vérifier l'expression du filtre".to_string(),
+            });
+        }
+        self.liste_menage.0 = menages_gardes;
+        Ok(cles_ecartees)
+    }
+
+    pub fn validate_liste_menage(mut self) -> KalkotoResult<MenageInputBuilder<Valid>> {
+        self.projete()?;
+        self.extrait_identifiants()?;
+
+        // Les colonnes écartées par la projection ne sont pas vérifiées par le schéma, pas plus
+        // que la colonne identifiant, déjà retirée des caractéristiques
+        let schema = self.schema.as_ref().map(|schema| SchemaMenages {
+            caracteristiques: schema
+                .caracteristiques
+                .iter()
+                .filter(|declaration| self.colonnes.as_ref().is_none_or(|colonnes| colonnes.contains(&declaration.name)))
+                .filter(|declaration| self.colonne_identifiant.as_ref() != Some(&declaration.name))
+                .cloned()
+                .collect(),
+        });
+        for menage in self.liste_menage.0.iter_mut() {
+            self.valeurs_manquantes.applique(menage)?;
+            if let Some(schema) = &schema {
+                schema.applique(menage)?;
+            }
+        }
+        // Le poids est lu une fois les valeurs manquantes traitées et le schéma appliqué
+        self.extrait_poids()?;
         let valid_liste_menage = self.has_valid_liste_menage()?;
+        let cles_menages_ecartes = self.filtre()?;
 
         let validated_set_caracteristiques: HashSet<String> = self.liste_menage.0.first().unwrap()
             .caracteristiques
@@ -119,6 +532,15 @@ impl MenageInputBuilder<Unvalid> {
         Ok(MenageInputBuilder {
             set_caracteristiques: Some(validated_set_caracteristiques),
             liste_menage: Valid(self.liste_menage.0),
+            valeurs_manquantes: self.valeurs_manquantes,
+            schema: self.schema,
+            colonne_identifiant: self.colonne_identifiant,
+            colonne_poids: self.colonne_poids,
+            colonnes: self.colonnes,
+            filtre: self.filtre,
+            menages_ecartes: cles_menages_ecartes.len(),
+            cles_menages_ecartes,
+            table_individus: self.table_individus,
         })
     }
 }
@@ -130,6 +552,12 @@ impl MenageInputBuilder<Valid> {
             Ok(MenageInput {
                 set_caracteristiques_valide: set_caracteristiques,
                 liste_menage_valide,
+                colonne_identifiant: self.colonne_identifiant,
+                colonne_poids: self.colonne_poids,
+                filtre: self.filtre,
+                menages_ecartes: self.menages_ecartes,
+                cles_menages_ecartes: self.cles_menages_ecartes,
+                set_caracteristiques_individus: HashSet::new(),
             })
         } else {
         Err(From::from(MenageListAdapterError::Validation { fault_index: -1 
@@ -1,7 +1,18 @@
+pub mod aleatoire;
+pub mod evaluator;
+pub mod formula;
 pub mod menage;
 pub mod menage_input;
 pub mod policy;
 pub mod policy_input;
+#[cfg(feature = "python")]
+pub mod python_evaluator;
+pub mod python_environnement;
+pub mod python_source_map;
+#[cfg(feature = "rhai")]
+pub mod rhai_evaluator;
+pub mod schema_menages;
 pub mod simulator;
+pub mod trace;
 
 pub use crate::{KalkotoError, KalkotoResult};
@@ -1,17 +1,16 @@
 use crate::adapters::input_adapters::PolicyAdapterError;
-use crate::entities::menage::{Caracteristique, Menage};
-use crate::entities::simulator::SimulationError;
+use crate::entities::evaluator::{
+    ContexteSimulation, Evaluateurs, SessionEvaluation, MOTEUR_FORMULA, MOTEUR_PYTHON,
+};
+use crate::entities::formula::{Formula, Source};
+use crate::entities::menage::Menage;
+use crate::entities::python_environnement::EnvironnementPython;
+use crate::entities::python_source_map::PythonSourceMap;
+use crate::entities::trace::MenageTrace;
 use crate::{KalkotoError, KalkotoResult};
-use crossterm::cursor::RestorePosition;
-use pyo3::{prelude::*, types::IntoPyDict, types::PyDict, types::PyList};
-use pyo3_ffi::c_str;
-use rayon::prelude::*;
+use itertools::Itertools;
 use serde::Deserialize;
-use std::{
-    collections::{HashMap, HashSet},
-    ffi::CString,
-    sync::Mutex,
-};
+use std::collections::{hash_map::Entry, HashMap, HashSet};
 
 #[derive(Deserialize, Debug, Clone)]
 pub struct Parameters {
@@ -20,7 +19,7 @@ pub struct Parameters {
     pub values: Vec<f64>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Debug, Clone, PartialEq)]
 pub struct Function(String);
 
 impl From<Function> for String {
@@ -29,6 +28,16 @@ impl From<Function> for String {
     }
 }
 
+// Niveau de calcul d'une composante : une valeur par ménage, ou une valeur par individu du
+// ménage (moteur formula seulement)
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Niveau {
+    #[default]
+    Menage,
+    Individu,
+}
+
 #[derive(Deserialize, Debug, Clone)]
 pub struct Composante {
     pub name: String,
@@ -36,56 +45,116 @@ pub struct Composante {
     pub parameters: Parameters,
     pub logical_order: i32,
     pub caracteristiques_dependencies: Vec<String>,
-    pub function: Function,
+    #[serde(default)]
+    pub caracteristiques_individus: Vec<String>, //Caractéristiques lues dans la table des individus
+    #[serde(default)]
+    pub niveau: Niveau,
+    pub function: Option<Function>,
+    pub formula: Option<Formula>,
+    #[serde(alias = "language")]
+    pub engine: Option<String>, //Moteur de calcul, déduit de function/formula s'il est absent
 }
 
 impl Composante {
-    pub fn simulate_all_menages<'py>(
+    pub fn engine(&self) -> &str {
+        match (&self.engine, &self.formula) {
+            (Some(engine), _) => engine,
+            (None, Some(_)) => MOTEUR_FORMULA,
+            (None, None) => MOTEUR_PYTHON,
+        }
+    }
+
+    fn definition_identique(&self, other: &Composante) -> bool {
+        self.engine() == other.engine()
+            && self.function == other.function
+            && self.formula.as_ref().map(|formula| &formula.source)
+                == other.formula.as_ref().map(|formula| &formula.source)
+            && self.caracteristiques_dependencies == other.caracteristiques_dependencies
+            && self.caracteristiques_individus == other.caracteristiques_individus
+            && self.niveau == other.niveau
+    }
+
+    // Paramètres et variables que la composante peut lire : les références de sa formule,
+    // ou à défaut tous les paramètres de la politique (la fonction reçoit ParamsDict en entier)
+    // et toutes les composantes calculées avant elle
+    fn lectures_possibles(
         &self,
-        py: Python<'py>,
-        py_menages_caract_dict: &Vec<Bound<'py, PyDict>>,
-        py_menages_variables_dict: &mut Vec<Bound<'py, PyDict>>,
-        parameters_dict: &Bound<'py, PyDict>,
-        python_functions_module: &Bound<'py, PyModule>,
-    ) -> KalkotoResult<()> {
-        let rustfunc = python_functions_module.getattr(&self.name).map_err(|e| {
-            SimulationError::PythonError {
-                source: e,
-                err_msg: format!(
-                    "Erreur lors de l'interprétation de la fonction Python de la composante {}",
-                    &self.name
-                ),
+        parametres: &HashMap<String, f64>,
+        composantes_precedentes: &[Composante],
+    ) -> (HashSet<String>, HashSet<String>) {
+        match (self.engine(), &self.formula) {
+            (MOTEUR_FORMULA, Some(formula)) => {
+                let references = formula.references();
+                let noms = |source: Source| {
+                    references
+                        .iter()
+                        .filter(|(source_reference, _)| *source_reference == source)
+                        .map(|(_, nom)| nom.to_owned())
+                        .collect()
+                };
+                let mut variables: HashSet<String> = noms(Source::Variables);
+                variables.extend(noms(Source::IndividuVariables));
+                (noms(Source::ParamsDict), variables)
             }
-        })?;
+            _ => (
+                parametres.keys().cloned().collect(),
+                noms_composantes(composantes_precedentes),
+            ),
+        }
+    }
+}
 
-        let python_simulation_result = py_menages_caract_dict
-            .iter()
-            .zip(py_menages_variables_dict.iter())
-            .try_for_each(|(py_menage_caract_dict, py_menage_variables_dict)| {
-                let args = (
-                    py_menage_variables_dict,
-                    &parameters_dict,
-                    py_menage_caract_dict,
-                );
+fn noms_composantes(composantes: &[Composante]) -> HashSet<String> {
+    composantes
+        .iter()
+        .map(|composante| composante.name.to_owned())
+        .collect()
+}
 
-                let result = rustfunc.call(args, None);
-
-                match result {
-                    Ok(result) => {
-                        (*py_menage_variables_dict).set_item(self.name.to_owned(), result);
-                        Ok(())
-                    }
-                    Err(e) => Err(SimulationError::PythonError {
-                        source: e,
-                        err_msg: format!("Erreur lors du calcul de la composante {}", self.name),
-                    }),
-                }
-            });
+// Résultats d'une simulation précédente sur la même liste de ménages (la baseline),
+// repris sans nouveau calcul pour les composantes que la politique simulée ne modifie pas
+#[derive(Clone, Copy)]
+pub struct Reprise<'a> {
+    pub policy: &'a Policy,
+    pub resultats: &'a ResultatsMenages,
+    pub traces: &'a [MenageTrace],
+    pub seed: u64,
+}
 
-        Ok(python_simulation_result?)
+impl Reprise<'_> {
+    fn reprend(
+        &self,
+        composante: &Composante,
+        variables: &mut [HashMap<String, f64>],
+        traces: &mut [Option<MenageTrace>],
+    ) {
+        for (menage_variables, resultats) in variables.iter_mut().zip(self.resultats) {
+            if let Some(valeur) = resultats.get(&composante.name) {
+                menage_variables.insert(composante.name.to_owned(), *valeur);
+            }
+        }
+
+        for trace in traces.iter_mut().flatten() {
+            let etape = self
+                .traces
+                .iter()
+                .filter(|trace_reprise| trace_reprise.menage == trace.menage)
+                .flat_map(|trace_reprise| &trace_reprise.etapes)
+                .find(|etape| etape.composante == composante.name);
+
+            if let Some(etape) = etape {
+                trace.etapes.push(etape.clone());
+            }
+        }
     }
 }
 
+// Valeurs des composantes calculées pour chaque ménage
+pub type ResultatsMenages = Vec<HashMap<String, f64>>;
+
+// Valeurs des composantes de niveau individu, pour chaque individu de chaque ménage
+pub type ResultatsIndividus<V = f64> = Vec<Vec<HashMap<String, V>>>;
+
 #[derive(Deserialize, Debug, Clone)]
 pub struct Policy {
     pub name: String,
@@ -94,25 +163,40 @@ pub struct Policy {
     pub parameters_intitules: HashMap<String, String>, //Ensemble des paramètres dont dépend la pol. publique
     pub parameters_values: HashMap<String, f64>, //Ensemble des paramètres dont dépend la pol. publique
     pub caracteristiques_menages: HashSet<String>, //Ensemble des caracteristiques dont dépend la pol. publique
+    #[serde(default)]
+    pub caracteristiques_individus: HashSet<String>, //Caractéristiques des individus dont dépend la pol. publique
     pub python_functions: Option<String>,
+    #[serde(default)]
+    pub fichier_policy: Option<String>,
+    #[serde(skip)]
+    pub python_source_map: Option<PythonSourceMap>,
+    #[serde(default)]
+    pub environnement_python: EnvironnementPython,
 }
 
 impl Policy {
+    pub fn a_composantes_individus(&self) -> bool {
+        self.composantes_ordonnees
+            .iter()
+            .any(|composante| composante.niveau == Niveau::Individu)
+    }
+
     pub fn populate_python_functions(self) -> KalkotoResult<Policy> {
         match &self.composantes_ordonnees.len() {
             0 => Err(KalkotoError::PolicyError(PolicyAdapterError::Generic(
                 "Le fichier input policy n'est pas lu !".into(),
             ))),
             _ => {
-                let py_functions_str: String = self
-                    .composantes_ordonnees
-                    .iter()
-                    .map(|composante| composante.function.clone().into())
-                    .collect::<Vec<String>>()
-                    .join("\n");
+                let python_source_map = PythonSourceMap::new(
+                    self.fichier_policy
+                        .as_deref()
+                        .unwrap_or("(fichier policy non renseigné)"),
+                    &self.composantes_ordonnees,
+                );
 
                 Ok(Policy {
-                    python_functions: Some(py_functions_str),
+                    python_functions: Some(python_source_map.module_source()),
+                    python_source_map: Some(python_source_map),
                     ..self
                 })
             }
@@ -123,116 +207,961 @@ impl Policy {
         &self,
         menages: &[Menage],
     ) -> KalkotoResult<Vec<HashMap<String, f64>>> {
-        if let Some(ref python_functions) = &self.python_functions {
-            let mut empty_vec_variables_dict: HashMap<String, f64> =
-                HashMap::with_capacity(self.composantes_ordonnees.len());
-
-            self.composantes_ordonnees.iter().map(|composante| {
-                empty_vec_variables_dict.insert(composante.name.to_owned(), 0 as f64);
-            });
-
-            let mut vec_variables_dict: Vec<HashMap<String, f64>> =
-                vec![empty_vec_variables_dict; menages.len()];
-
-            Python::initialize();
-
-            let output = Python::attach(|py| -> KalkotoResult<Vec<HashMap<String, f64>>> {
-                let composantemodule = PyModule::from_code(
-                    py,
-                    CString::new(python_functions.to_owned())
-                        .map_err(|e| SimulationError::PythonError {
-                            source: e.into(),
-                            err_msg: "Problème de lecture des fonctions Python".into(),
-                        })?
-                        .as_c_str(),
-                    c_str!("composantemodule.py"),
-                    c_str!("composantemodule"),
-                )
-                .map_err(|e| SimulationError::PythonError {
-                    source: e,
-                    err_msg: "Erreur à la création du module Python".into(),
-                })?;
-
-                let params_dict_py =
-                    self.parameters_values
-                        .clone()
-                        .into_py_dict(py)
-                        .map_err(|e| SimulationError::PythonError {
-                            source: e,
-                            err_msg: "Erreur pour dictionnaire de paramètres".into(),
-                        })?;
-
-                let py_menages_dicts: KalkotoResult<Vec<Bound<'_, PyDict>>> = menages
-                    .iter()
-                    .map(|menage| {
-                        menage
-                            .caracteristiques
-                            .clone()
-                            .into_py_dict(py)
-                            .map_err(|e| SimulationError::PythonError {
-                                source: e,
-                                err_msg: "Erreur pour dictionnaire de caractéristiques des ménages"
-                                    .into(),
-                            })
-                            .map_err(KalkotoError::from)
-                    })
-                    .collect();
-
-                let py_menages_dicts = py_menages_dicts?;
-
-                let mut py_variables_dicts: KalkotoResult<Vec<Bound<'_, PyDict>>> =
-                    vec_variables_dict
-                        .iter()
-                        .map(|dict| {
-                            dict.clone()
-                                .into_py_dict(py)
-                                .map_err(|e| SimulationError::PythonError {
-                                    source: e,
-                                    err_msg: "Erreur pour dictionnaire de variables des ménages"
-                                        .into(),
-                                })
-                                .map_err(KalkotoError::from)
-                        })
-                        .collect();
-
-                let mut py_variables_dicts = py_variables_dicts?;
-
-                self.composantes_ordonnees
+        let (results, _) = self.simulate_all_menages_with_trace(
+            menages,
+            &HashSet::new(),
+            &Evaluateurs::default(),
+        )?;
+        Ok(results)
+    }
+
+    // Simulation de la politique publique, en enregistrant le détail du calcul pour les
+    // ménages dont la clé figure dans menages_traces. Chaque composante est calculée par
+    // le moteur de evaluateurs correspondant à son champ engine
+    pub fn simulate_all_menages_with_trace(
+        &self,
+        menages: &[Menage],
+        menages_traces: &HashSet<String>,
+        evaluateurs: &Evaluateurs,
+    ) -> KalkotoResult<(ResultatsMenages, Vec<MenageTrace>)> {
+        self.simulate_all_menages_incremental(menages, menages_traces, evaluateurs, 0, None)
+    }
+
+    // Composantes dont les résultats sont identiques à ceux de la politique de référence :
+    // même définition, mêmes valeurs des paramètres lus, et variables lues elles-mêmes identiques.
+    // Une composante sans formule peut lire tout ParamsDict : elle n'est reprise que si tous les
+    // paramètres, et pour Python l'environnement de la politique, sont identiques
+    pub fn composantes_reutilisables(&self, reference: &Policy) -> HashSet<String> {
+        let mut reutilisables = HashSet::new();
+
+        for (position, composante) in self.composantes_ordonnees.iter().enumerate() {
+            // Les résultats de niveau individu ne sont pas repris : ils sont toujours recalculés
+            if composante.niveau == Niveau::Individu {
+                continue;
+            }
+
+            let Some((position_reference, composante_reference)) = reference
+                .composantes_ordonnees
+                .iter()
+                .find_position(|composante_reference| composante_reference.name == composante.name)
+            else {
+                continue;
+            };
+
+            let precedentes = &self.composantes_ordonnees[..position];
+            let precedentes_reference = &reference.composantes_ordonnees[..position_reference];
+            let (parametres_lus, variables_lues) =
+                composante.lectures_possibles(&self.parameters_values, precedentes);
+
+            // Sans formule, la fonction reçoit toutes les variables déjà calculées et tous les
+            // paramètres, y compris ceux absents de la politique de référence
+            let memes_entrees_disponibles = composante.engine() == MOTEUR_FORMULA
+                || (noms_composantes(precedentes) == noms_composantes(precedentes_reference)
+                    && self.parameters_values == reference.parameters_values);
+
+            let meme_environnement = composante.engine() != MOTEUR_PYTHON
+                || self.environnement_python == reference.environnement_python;
+
+            let reutilisable = composante.definition_identique(composante_reference)
+                && memes_entrees_disponibles
+                && meme_environnement
+                && parametres_lus.iter().all(|parametre| {
+                    self.parameters_values.get(parametre)
+                        == reference.parameters_values.get(parametre)
+                })
+                && variables_lues
                     .iter()
-                    .try_for_each(|composante: &Composante| {
-                        composante.simulate_all_menages(
-                            py,
-                            &py_menages_dicts,
-                            &mut py_variables_dicts,
-                            &params_dict_py,
-                            &composantemodule,
-                        )
-                    })?;
-
-                let final_results_variables_dict: KalkotoResult<Vec<HashMap<String, f64>>> =
-                    py_variables_dicts
-                        .into_iter()
-                        .map(|result_wrapper| {
-                            result_wrapper
-                                .extract::<HashMap<String, f64>>()
-                                .map_err(|e| SimulationError::PythonError {
-                                    source: e,
-                                    err_msg: "Erreur à l'extraction des résultats depuis Python"
-                                        .into(),
-                                })
-                                .map_err(KalkotoError::from)
-                        })
-                        .collect();
-
-                final_results_variables_dict
-            })?;
-
-            Ok(output)
-        } else {
-            Err(KalkotoError::PolicyError(PolicyAdapterError::Generic(
-                "Fichier policy pas encore lu ! Les fonctions Python ne sont pas initialisées"
-                    .into(),
-            )))
+                    .all(|variable| reutilisables.contains(variable));
+
+            if reutilisable {
+                reutilisables.insert(composante.name.to_owned());
+            }
+        }
+
+        reutilisables
+    }
+
+    // Simulation reprenant de reprise les résultats des composantes réutilisables, et ne
+    // calculant que les autres. Les résultats sont identiques à ceux d'une simulation complète.
+    // seed fixe les tirages aléatoires des composantes, pour chaque ménage et chaque composante
+    pub fn simulate_all_menages_incremental(
+        &self,
+        menages: &[Menage],
+        menages_traces: &HashSet<String>,
+        evaluateurs: &Evaluateurs,
+        seed: u64,
+        reprise: Option<Reprise<'_>>,
+    ) -> KalkotoResult<(ResultatsMenages, Vec<MenageTrace>)> {
+        let (variables, _, traces) = self.simulate_menages_et_individus(
+            menages,
+            menages_traces,
+            evaluateurs,
+            seed,
+            reprise,
+        )?;
+        Ok((variables, traces))
+    }
+
+    // Simulation complète, renvoyant aussi les valeurs des composantes de niveau individu
+    pub fn simulate_menages_et_individus(
+        &self,
+        menages: &[Menage],
+        menages_traces: &HashSet<String>,
+        evaluateurs: &Evaluateurs,
+        seed: u64,
+        reprise: Option<Reprise<'_>>,
+    ) -> KalkotoResult<(ResultatsMenages, ResultatsIndividus, Vec<MenageTrace>)> {
+        let mut variables_individus: ResultatsIndividus = menages
+            .iter()
+            .map(|menage| vec![HashMap::new(); menage.individus.len()])
+            .collect();
+
+        let mut variables: ResultatsMenages =
+            vec![HashMap::with_capacity(self.composantes_ordonnees.len()); menages.len()];
+
+        let mut traces: Vec<Option<MenageTrace>> = menages
+            .iter()
+            .map(|menage| {
+                let cle = menage.cle();
+                menages_traces
+                    .contains(&cle)
+                    .then(|| MenageTrace::new(&self.name, cle))
+            })
+            .collect();
+
+        let contexte = ContexteSimulation {
+            policy: self,
+            menages,
+            menages_traces,
+            seed,
+        };
+
+        // La reprise n'est possible que si elle porte sur les mêmes ménages, traces comprises,
+        // et sur les mêmes tirages aléatoires
+        let reutilisables = match reprise {
+            Some(reprise)
+                if reprise.seed == seed
+                    && reprise.resultats.len() == menages.len()
+                    && traces.iter().flatten().all(|trace| {
+                        reprise
+                            .traces
+                            .iter()
+                            .any(|trace_reprise| trace_reprise.menage == trace.menage)
+                    }) =>
+            {
+                self.composantes_reutilisables(reprise.policy)
+            }
+            _ => HashSet::new(),
+        };
+
+        let mut sessions: HashMap<&str, Box<dyn SessionEvaluation + '_>> = HashMap::new();
+
+        for composante in &self.composantes_ordonnees {
+            if let (Some(reprise), true) = (reprise, reutilisables.contains(&composante.name)) {
+                reprise.reprend(composante, &mut variables, &mut traces);
+                continue;
+            }
+
+            let session = match sessions.entry(composante.engine()) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => {
+                    let session = evaluateurs.get(entry.key())?.session(contexte)?;
+                    entry.insert(session)
+                }
+            };
+
+            match composante.niveau {
+                Niveau::Menage => session.evaluate_avec_individus(
+                    composante,
+                    &mut variables,
+                    &variables_individus,
+                    &mut traces,
+                )?,
+                Niveau::Individu => {
+                    session.evaluate_individus(composante, &variables, &mut variables_individus)?
+                }
+            }
         }
+
+        Ok((
+            variables,
+            variables_individus,
+            traces.into_iter().flatten().collect(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::input_adapters::toml_input_adapter::TomlInputAdapter;
+    use crate::adapters::input_adapters::PolicyCreator;
+    use crate::entities::menage::Caracteristique;
+    use crate::entities::python_source_map::PythonLocalisation;
+    use crate::entities::simulator::SimulationError;
+    use crate::entities::trace::{ComposanteTrace, Lecture};
+
+    static POLICY_TOML_BYTES: &[u8] = r#"
+name = "APA domicile"
+
+intitule_long = "Aide personnalisée à domicile"
+
+[[composante]]
+name = "plan_notif"
+intitule_long = "Plan notifié"
+parameters.names = ["tau_1"]
+parameters.intitules_long = ["Taux GIR 1"]
+parameters.values = [0.15]
+caracteristiques_dependencies = ["Age"]
+logical_order = 1
+function = """
+def plan_notif(Variables, ParamsDict, MenageCarac):
+    return ParamsDict["tau_1"] * MenageCarac["Age"]
+"""
+
+[[composante]]
+name = "plan_cons"
+intitule_long = "Plan effectivement consommé"
+parameters.names = ["taux_ss_conso"]
+parameters.intitules_long = ["Taux de sous-consommation du plan notifié"]
+parameters.values = [0.0]
+caracteristiques_dependencies = []
+logical_order = 2
+function = """
+def plan_cons(Variables, ParamsDict, MenageCarac):
+    ratio = Variables["plan_notif"] / ParamsDict["taux_ss_conso"]
+    return ratio
+"""
+"#
+    .as_bytes();
+
+    fn create_test_policy() -> KalkotoResult<Policy> {
+        let (name, intitule_long, composantes) =
+            TomlInputAdapter::new().populate_from_buf(POLICY_TOML_BYTES)?;
+
+        Policy {
+            name,
+            intitule_long,
+            composantes_ordonnees: composantes,
+            parameters_intitules: HashMap::new(),
+            parameters_values: HashMap::new(),
+            caracteristiques_menages: HashSet::new(),
+            caracteristiques_individus: HashSet::new(),
+            python_functions: None,
+            fichier_policy: Some("apa.toml".into()),
+            python_source_map: None,
+            environnement_python: EnvironnementPython::default(),
+        }
+        .populate_python_functions()
+    }
+
+    #[test]
+    fn ok_python_source_map_localise() -> KalkotoResult<()> {
+        let policy = create_test_policy()?;
+        let source_map = policy.python_source_map.unwrap();
+
+        let wanted = Some(PythonLocalisation {
+            fichier_policy: "apa.toml".into(),
+            composante: "plan_cons".into(),
+            ligne: 2,
+            ligne_source: r#"ratio = Variables["plan_notif"] / ParamsDict["taux_ss_conso"]"#.into(),
+        });
+        let result = source_map.localise(5);
+
+        assert_eq!(wanted, result);
+        assert_eq!(policy.python_functions, Some(source_map.module_source()));
+
+        Ok(())
+    }
+
+    #[cfg(feature = "python")]
+    #[test]
+    fn err_python_traceback_mapped_to_composante() -> KalkotoResult<()> {
+        let mut policy = create_test_policy()?;
+        policy.parameters_values.insert("tau_1".into(), 0.15);
+        policy.parameters_values.insert("taux_ss_conso".into(), 0.0);
+
+        let mut menage = Menage::new(42);
+        menage
+            .caracteristiques
+            .insert("Age".into(), Caracteristique::Entier(80));
+
+        let result = policy.simulate_all_menages(&[menage]);
+
+        match result {
+            Err(KalkotoError::SimError(SimulationError::PythonComposanteError {
+                localisation,
+                menage,
+                ..
+            })) => {
+                assert_eq!("apa.toml", localisation.fichier_policy);
+                assert_eq!("plan_cons", localisation.composante);
+                assert_eq!(2, localisation.ligne);
+                assert!(menage.contains("Le ménage 42"));
+            }
+            _ => panic!("L'erreur Python n'a pas été localisée dans la composante"),
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "python")]
+    #[test]
+    fn ok_trace_selected_menage() -> KalkotoResult<()> {
+        let mut policy = create_test_policy()?;
+        policy.parameters_values.insert("tau_1".into(), 0.5);
+        policy.parameters_values.insert("taux_ss_conso".into(), 2.0);
+
+        let menages: Vec<Menage> = [41, 42]
+            .into_iter()
+            .map(|index| {
+                let mut menage = Menage::new(index);
+                menage
+                    .caracteristiques
+                    .insert("Age".into(), Caracteristique::Entier(80));
+                menage
+            })
+            .collect();
+
+        let (results, traces) = policy.simulate_all_menages_with_trace(
+            &menages,
+            &HashSet::from(["42".to_string()]),
+            &Evaluateurs::default(),
+        )?;
+
+        let wanted = vec![MenageTrace {
+            policy: "APA domicile".into(),
+            menage: "42".into(),
+            etapes: vec![
+                ComposanteTrace {
+                    composante: "plan_notif".into(),
+                    intitule_long: "Plan notifié".into(),
+                    caracteristiques_lues: vec![Lecture {
+                        nom: "Age".into(),
+                        valeur: Caracteristique::Entier(80),
+                    }],
+                    parametres_lus: vec![Lecture {
+                        nom: "tau_1".into(),
+                        valeur: 0.5,
+                    }],
+                    variables_lues: vec![],
+                    resultat: 40.0,
+                    lectures_enregistrees: true,
+                },
+                ComposanteTrace {
+                    composante: "plan_cons".into(),
+                    intitule_long: "Plan effectivement consommé".into(),
+                    caracteristiques_lues: vec![],
+                    parametres_lus: vec![Lecture {
+                        nom: "taux_ss_conso".into(),
+                        valeur: 2.0,
+                    }],
+                    variables_lues: vec![Lecture {
+                        nom: "plan_notif".into(),
+                        valeur: 40.0,
+                    }],
+                    resultat: 20.0,
+                    lectures_enregistrees: true,
+                },
+            ],
+        }];
+
+        assert_eq!(wanted, traces);
+        assert_eq!(results[0], results[1]);
+
+        Ok(())
+    }
+
+    // Variante ne modifiant que la fonction de plan_cons : plan_notif reste réutilisable
+    #[cfg(feature = "python")]
+    fn variante_plan_cons(baseline: &Policy) -> KalkotoResult<Policy> {
+        let mut variante = baseline.clone();
+        variante.composantes_ordonnees[1].function = Some(Function(
+            r#"
+def plan_cons(Variables, ParamsDict, MenageCarac):
+    return Variables["plan_notif"] / (2 * ParamsDict["taux_ss_conso"])
+"#
+            .into(),
+        ));
+        variante.populate_python_functions()
+    }
+
+    #[cfg(feature = "python")]
+    #[test]
+    fn ok_incremental_variante_identical_to_full_run() -> KalkotoResult<()> {
+        let mut baseline = create_test_policy()?;
+        baseline.parameters_values.insert("tau_1".into(), 0.5);
+        baseline
+            .parameters_values
+            .insert("taux_ss_conso".into(), 2.0);
+
+        let variante = variante_plan_cons(&baseline)?;
+
+        let menages: Vec<Menage> = [41, 42]
+            .into_iter()
+            .map(|index| {
+                let mut menage = Menage::new(index);
+                menage
+                    .caracteristiques
+                    .insert("Age".into(), Caracteristique::Entier(index));
+                menage
+            })
+            .collect();
+        let menages_traces = HashSet::from(["42".to_string()]);
+        let evaluateurs = Evaluateurs::default();
+
+        let (resultats_baseline, traces_baseline) =
+            baseline.simulate_all_menages_with_trace(&menages, &menages_traces, &evaluateurs)?;
+
+        let wanted = HashSet::from(["plan_notif".to_string()]);
+        assert_eq!(wanted, variante.composantes_reutilisables(&baseline));
+
+        let wanted =
+            variante.simulate_all_menages_with_trace(&menages, &menages_traces, &evaluateurs)?;
+        let result = variante.simulate_all_menages_incremental(
+            &menages,
+            &menages_traces,
+            &evaluateurs,
+            0,
+            Some(Reprise {
+                policy: &baseline,
+                resultats: &resultats_baseline,
+                traces: &traces_baseline,
+                seed: 0,
+            }),
+        )?;
+        assert_eq!(wanted, result);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "python")]
+    #[test]
+    fn ok_incremental_variante_reuses_baseline_values() -> KalkotoResult<()> {
+        let mut baseline = create_test_policy()?;
+        baseline.parameters_values.insert("tau_1".into(), 0.5);
+        baseline
+            .parameters_values
+            .insert("taux_ss_conso".into(), 2.0);
+
+        let variante = variante_plan_cons(&baseline)?;
+
+        let mut menage = Menage::new(1);
+        menage
+            .caracteristiques
+            .insert("Age".into(), Caracteristique::Entier(80));
+
+        // plan_notif n'est pas recalculé : la valeur de la reprise est conservée
+        let resultats_baseline = vec![HashMap::from([
+            ("plan_notif".to_string(), 1000.0),
+            ("plan_cons".to_string(), 500.0),
+        ])];
+
+        let (results, _) = variante.simulate_all_menages_incremental(
+            &[menage],
+            &HashSet::new(),
+            &Evaluateurs::default(),
+            0,
+            Some(Reprise {
+                policy: &baseline,
+                resultats: &resultats_baseline,
+                traces: &[],
+                seed: 0,
+            }),
+        )?;
+
+        let wanted = vec![HashMap::from([
+            ("plan_notif".to_string(), 1000.0),
+            ("plan_cons".to_string(), 250.0),
+        ])];
+        assert_eq!(wanted, results);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "python")]
+    #[test]
+    fn ok_incremental_parametre_non_declare_recalcule() -> KalkotoResult<()> {
+        let toml = r#"
+name = "Paramètre non déclaré"
+intitule_long = "Composante lisant un paramètre d'une autre composante"
+
+[[composante]]
+name = "a"
+intitule_long = "Composante a"
+parameters.names = ["pa"]
+parameters.intitules_long = ["Paramètre a"]
+parameters.values = [1.0]
+caracteristiques_dependencies = ["Age"]
+logical_order = 1
+function = """
+def a(Variables, ParamsDict, MenageCarac):
+    return ParamsDict["pb"] * MenageCarac["Age"]
+"""
+
+[[composante]]
+name = "b"
+intitule_long = "Composante b"
+parameters.names = ["pb"]
+parameters.intitules_long = ["Paramètre b"]
+parameters.values = [2.0]
+caracteristiques_dependencies = []
+logical_order = 2
+function = """
+def b(Variables, ParamsDict, MenageCarac):
+    return Variables["a"]
+"""
+"#;
+        let baseline = TomlInputAdapter::new()
+            .populate_from_memory(toml.as_bytes())?
+            .create_valid_policy_input()?
+            .valid_policy;
+        let mut variante = baseline.clone();
+        variante.parameters_values.insert("pb".into(), 5.0);
+
+        let mut menage = Menage::new(1);
+        menage
+            .caracteristiques
+            .insert("Age".into(), Caracteristique::Entier(10));
+        let menages = [menage];
+        let evaluateurs = Evaluateurs::default();
+
+        assert_eq!(
+            HashSet::new(),
+            variante.composantes_reutilisables(&baseline)
+        );
+
+        let (resultats_baseline, traces_baseline) =
+            baseline.simulate_all_menages_with_trace(&menages, &HashSet::new(), &evaluateurs)?;
+        let (results, _) = variante.simulate_all_menages_incremental(
+            &menages,
+            &HashSet::new(),
+            &evaluateurs,
+            0,
+            Some(Reprise {
+                policy: &baseline,
+                resultats: &resultats_baseline,
+                traces: &traces_baseline,
+                seed: 0,
+            }),
+        )?;
+
+        assert_eq!(50.0, results[0]["a"]);
+        assert_eq!(variante.simulate_all_menages(&menages)?, results);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "python")]
+    #[test]
+    fn ok_python_variables_conservees_entre_composantes() -> KalkotoResult<()> {
+        let toml = r#"
+name = "Variables partagées"
+intitule_long = "Écriture dans Variables lue par une composante suivante"
+
+[[composante]]
+name = "base"
+intitule_long = "Base"
+parameters.names = []
+parameters.intitules_long = []
+parameters.values = []
+caracteristiques_dependencies = []
+logical_order = 1
+function = """
+def base(Variables, ParamsDict, MenageCarac):
+    Variables["intermediaire"] = 3.0
+    return 1.0
+"""
+
+[[composante]]
+name = "double"
+intitule_long = "Double"
+parameters.names = []
+parameters.intitules_long = []
+parameters.values = []
+caracteristiques_dependencies = []
+logical_order = 2
+formula = 'Variables["base"] * 2'
+
+[[composante]]
+name = "total"
+intitule_long = "Total"
+parameters.names = []
+parameters.intitules_long = []
+parameters.values = []
+caracteristiques_dependencies = []
+logical_order = 3
+function = """
+def total(Variables, ParamsDict, MenageCarac):
+    return Variables["intermediaire"] + Variables["double"]
+"""
+"#;
+        let policy = TomlInputAdapter::new()
+            .populate_from_memory(toml.as_bytes())?
+            .create_valid_policy_input()?
+            .valid_policy;
+        let menages = [Menage::new(1), Menage::new(2)];
+
+        let (results, _) = policy.simulate_all_menages_with_trace(
+            &menages,
+            &HashSet::from(["2".to_string()]),
+            &Evaluateurs::default(),
+        )?;
+
+        assert_eq!(5.0, results[0]["total"]);
+        assert_eq!(results[0], results[1]);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "python")]
+    #[test]
+    fn ok_tirages_aleatoires_independants_de_l_ordre() -> KalkotoResult<()> {
+        let toml = r#"
+name = "Non-recours"
+intitule_long = "Simulation du non-recours"
+
+[[composante]]
+name = "tirage_python"
+intitule_long = "Tirage Python"
+parameters.names = []
+parameters.intitules_long = []
+parameters.values = []
+caracteristiques_dependencies = []
+logical_order = 1
+function = """
+def tirage_python(Variables, ParamsDict, MenageCarac, Alea):
+    return Alea.random()
+"""
+
+[[composante]]
+name = "tirage_formula"
+intitule_long = "Tirage formula"
+parameters.names = []
+parameters.intitules_long = []
+parameters.values = []
+caracteristiques_dependencies = []
+logical_order = 2
+formula = 'alea()'
+"#;
+        let policy = TomlInputAdapter::new()
+            .populate_from_memory(toml.as_bytes())?
+            .create_valid_policy_input()?
+            .valid_policy;
+
+        let simule = |menages: &[Menage], seed: u64| {
+            policy
+                .simulate_all_menages_incremental(
+                    menages,
+                    &HashSet::new(),
+                    &Evaluateurs::default(),
+                    seed,
+                    None,
+                )
+                .map(|(results, _)| results)
+        };
+        let menages = [Menage::new(1), Menage::new(2)];
+        let menages_inverses = [Menage::new(2), Menage::new(1)];
+
+        let results = simule(&menages, 42)?;
+        let mut results_inverses = simule(&menages_inverses, 42)?;
+        results_inverses.reverse();
+        assert_eq!(results, results_inverses);
+
+        assert_ne!(results[0]["tirage_python"], results[1]["tirage_python"]);
+        assert_ne!(results[0]["tirage_formula"], results[1]["tirage_formula"]);
+        assert_ne!(results, simule(&menages, 43)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn ok_tirages_et_trace_par_identifiant() -> KalkotoResult<()> {
+        let toml = r#"
+name = "Non-recours"
+intitule_long = "Simulation du non-recours"
+
+[[composante]]
+name = "tirage"
+intitule_long = "Tirage"
+parameters.names = []
+parameters.intitules_long = []
+parameters.values = []
+caracteristiques_dependencies = []
+logical_order = 1
+formula = 'alea()'
+"#;
+        let policy = TomlInputAdapter::new()
+            .populate_from_memory(toml.as_bytes())?
+            .create_valid_policy_input()?
+            .valid_policy;
+
+        // Les index suivent la position dans le fichier, les identifiants suivent le ménage
+        let menages = |identifiants: &[&str]| -> Vec<Menage> {
+            identifiants
+                .iter()
+                .enumerate()
+                .map(|(position, identifiant)| Menage {
+                    identifiant: Some(identifiant.to_string()),
+                    ..Menage::new(position as i32 + 1)
+                })
+                .collect()
+        };
+        let simule = |menages: &[Menage]| {
+            policy.simulate_all_menages_incremental(
+                menages,
+                &HashSet::from(["B7".to_string()]),
+                &Evaluateurs::default(),
+                42,
+                None,
+            )
+        };
+
+        let (results, traces) = simule(&menages(&["0012", "B7", "C3"]))?;
+        let (results_inverses, traces_inverses) = simule(&menages(&["C3", "B7", "0012"]))?;
+        let (results_filtres, traces_filtres) = simule(&menages(&["C3", "B7"]))?;
+
+        assert_eq!(results[0], results_inverses[2]);
+        assert_eq!(results[2], results_inverses[0]);
+        assert_eq!(results[1], results_filtres[1]);
+        assert_eq!(results[2], results_filtres[0]);
+        assert_ne!(results[0], results[1]);
+
+        assert_eq!(1, traces.len());
+        assert_eq!("B7", traces[0].menage);
+        assert_eq!(traces, traces_inverses);
+        assert_eq!(traces, traces_filtres);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "python")]
+    #[test]
+    fn ok_python_module_partage() -> KalkotoResult<()> {
+        static POLICY_MODULE_PARTAGE: &str = r#"
+name = "APA domicile"
+intitule_long = "Aide personnalisée à domicile"
+
+[python]
+paths = ["lib"]
+modules = ["arrondis_partages"]
+
+[[composante]]
+name = "plan_notif"
+intitule_long = "Plan notifié"
+parameters.names = ["tau_1"]
+parameters.intitules_long = ["Taux GIR 1"]
+parameters.values = [0.15]
+caracteristiques_dependencies = ["Age"]
+logical_order = 1
+function = """
+from arrondis_partages import arrondi
+
+def plan_notif(Variables, ParamsDict, MenageCarac):
+    return arrondi(ParamsDict["tau_1"] * MenageCarac["Age"])
+"""
+"#;
+
+        let tmp_dir = tempdir::TempDir::new("test-python-path").map_err(PolicyAdapterError::IO)?;
+        std::fs::create_dir(tmp_dir.path().join("lib")).map_err(PolicyAdapterError::IO)?;
+        std::fs::write(
+            tmp_dir.path().join("lib").join("arrondis_partages.py"),
+            "def arrondi(valeur):\n    return float(round(valeur))\n",
+        )
+        .map_err(PolicyAdapterError::IO)?;
+        let fichier_policy = tmp_dir.path().join("apa.toml");
+        std::fs::write(&fichier_policy, POLICY_MODULE_PARTAGE).map_err(PolicyAdapterError::IO)?;
+
+        let policy = TomlInputAdapter::new()
+            .populate_from_path(&fichier_policy, &mut String::new())?
+            .create_valid_policy_input()?
+            .valid_policy;
+
+        let mut menage = Menage::new(1);
+        menage
+            .caracteristiques
+            .insert("Age".into(), Caracteristique::Entier(83));
+
+        let wanted = vec![HashMap::from([("plan_notif".to_string(), 12.0)])];
+        assert_eq!(wanted, policy.simulate_all_menages(&[menage])?);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "python")]
+    #[test]
+    fn ok_python_environnements_successifs_isoles() -> KalkotoResult<()> {
+        let toml = r#"
+name = "Barème versionné"
+intitule_long = "Module partagé dont chaque politique a sa version"
+
+[python]
+paths = ["lib"]
+
+[[composante]]
+name = "montant"
+intitule_long = "Montant"
+parameters.names = []
+parameters.intitules_long = []
+parameters.values = []
+caracteristiques_dependencies = []
+logical_order = 1
+function = """
+from bareme_versionne import montant as montant_bareme
+
+def montant(Variables, ParamsDict, MenageCarac):
+    return montant_bareme()
+"""
+"#;
+        let tmp_dir =
+            tempdir::TempDir::new("test-python-versions").map_err(PolicyAdapterError::IO)?;
+        let policies = [1.0, 2.0]
+            .into_iter()
+            .map(|version| {
+                let repertoire = tmp_dir.path().join(format!("v{}", version));
+                std::fs::create_dir_all(repertoire.join("lib")).map_err(PolicyAdapterError::IO)?;
+                std::fs::write(
+                    repertoire.join("lib").join("bareme_versionne.py"),
+                    format!("def montant():\n    return {:?}\n", version),
+                )
+                .map_err(PolicyAdapterError::IO)?;
+                let fichier_policy = repertoire.join("bareme.toml");
+                std::fs::write(&fichier_policy, toml).map_err(PolicyAdapterError::IO)?;
+
+                Ok(TomlInputAdapter::new()
+                    .populate_from_path(&fichier_policy, &mut String::new())?
+                    .create_valid_policy_input()?
+                    .valid_policy)
+            })
+            .collect::<KalkotoResult<Vec<Policy>>>()?;
+
+        let menages = [Menage::new(1)];
+        for (policy, wanted) in policies.iter().chain(&policies).zip([1.0, 2.0, 1.0, 2.0]) {
+            assert_eq!(wanted, policy.simulate_all_menages(&menages)?[0]["montant"]);
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "python")]
+    #[test]
+    fn ok_python_valeur_manquante_none() -> KalkotoResult<()> {
+        let toml = r#"
+name = "Valeur manquante"
+intitule_long = "Caractéristique manquante transmise à Python"
+
+[[composante]]
+name = "plan_notif"
+intitule_long = "Plan notifié"
+parameters.names = ["tau_1"]
+parameters.intitules_long = ["Taux GIR 1"]
+parameters.values = [0.15]
+caracteristiques_dependencies = ["Age"]
+logical_order = 1
+function = """
+def plan_notif(Variables, ParamsDict, MenageCarac):
+    if MenageCarac["Age"] is None:
+        return -1.0
+    return ParamsDict["tau_1"] * MenageCarac["Age"]
+"""
+"#;
+        let policy = TomlInputAdapter::new()
+            .populate_from_memory(toml.as_bytes())?
+            .create_valid_policy_input()?
+            .valid_policy;
+
+        let mut menage = Menage::new(1);
+        menage
+            .caracteristiques
+            .insert("Age".into(), Caracteristique::Manquant);
+
+        let wanted = vec![HashMap::from([("plan_notif".to_string(), -1.0)])];
+        assert_eq!(wanted, policy.simulate_all_menages(&[menage])?);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "python")]
+    #[test]
+    fn ok_python_booleen_et_date() -> KalkotoResult<()> {
+        let toml = r#"
+name = "Booléens et dates"
+intitule_long = "Caractéristiques booléennes et dates transmises à Python"
+
+[[composante]]
+name = "plan_notif"
+intitule_long = "Plan notifié"
+parameters.names = []
+parameters.intitules_long = []
+parameters.values = []
+caracteristiques_dependencies = ["Age", "Locataire"]
+logical_order = 1
+function = """
+import datetime
+
+def plan_notif(Variables, ParamsDict, MenageCarac):
+    locataire = MenageCarac["Locataire"] is True
+    return float(locataire and MenageCarac["Age"] == datetime.date(1950, 7, 14))
+"""
+"#;
+        let policy = TomlInputAdapter::new()
+            .populate_from_memory(toml.as_bytes())?
+            .create_valid_policy_input()?
+            .valid_policy;
+
+        let mut menage = Menage::new(1);
+        menage.caracteristiques.insert(
+            "Age".into(),
+            Caracteristique::Date(chrono::NaiveDate::from_ymd_opt(1950, 7, 14).unwrap()),
+        );
+        menage
+            .caracteristiques
+            .insert("Locataire".into(), Caracteristique::Booleen(true));
+
+        let wanted = vec![HashMap::from([("plan_notif".to_string(), 1.0)])];
+        assert_eq!(wanted, policy.simulate_all_menages(&[menage])?);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "python")]
+    #[test]
+    fn err_python_module_manquant() -> KalkotoResult<()> {
+        let toml = r#"
+name = "Module manquant"
+intitule_long = "Politique requérant un module absent"
+
+[python]
+modules = ["module_kalkoto_absent"]
+
+[[composante]]
+name = "plan_notif"
+intitule_long = "Plan notifié"
+parameters.names = []
+parameters.intitules_long = []
+parameters.values = []
+caracteristiques_dependencies = []
+logical_order = 1
+function = """
+def plan_notif(Variables, ParamsDict, MenageCarac):
+    return 0.0
+"""
+"#;
+        let policy = TomlInputAdapter::new()
+            .populate_from_memory(toml.as_bytes())?
+            .create_valid_policy_input()?
+            .valid_policy;
+
+        let result = policy.simulate_all_menages(&[Menage::new(1)]);
+
+        let wanted = true;
+        let result = matches!(
+            result,
+            Err(KalkotoError::SimError(SimulationError::PythonError { ref err_msg, .. }))
+                if err_msg.contains("module_kalkoto_absent")
+        );
+        assert_eq!(wanted, result);
+
+        Ok(())
     }
 }
@@ -1,5 +1,5 @@
 use crate::entities::policy::Policy;
-use crossterm::style::Stylize;
+use crate::terminal;
 use std::fmt::Display;
 
 #[derive(Debug, Clone)]
@@ -12,17 +12,13 @@ impl Display for PolicyInput {
         writeln!(
             f,
             "{}",
-            "Input Policy correctement initialisé !\n".green().bold()
+            terminal::succes("Input Policy correctement initialisé !\n")
         )?;
         writeln!(
             f,
             "Politique publique à simuler trouvée dans l'input Policy :",
         )?;
-        writeln!(
-            f,
-            "{}\n",
-            self.valid_policy.intitule_long.clone().red().bold()
-        )?;
+        writeln!(f, "{}\n", terminal::titre(&self.valid_policy.intitule_long))?;
         writeln!(
             f,
             ">>>> Liste ordonnée des composantes de cette politique publique :\n"
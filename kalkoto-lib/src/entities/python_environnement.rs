@@ -0,0 +1,110 @@
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+// Environnement de l'interpréteur Python, déclaré dans la table [python] du fichier policy
+// ou fourni pour toute la simulation (option de la ligne de commande par exemple) :
+// - paths : répertoires ajoutés en tête de sys.path (bibliothèques partagées entre politiques)
+// - virtualenv : environnement virtuel dont le site-packages est ajouté à sys.path
+// - modules : modules dont l'import est vérifié avant le calcul de la première composante
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct EnvironnementPython {
+    #[serde(default)]
+    pub paths: Vec<PathBuf>,
+    pub virtualenv: Option<PathBuf>,
+    #[serde(default)]
+    pub modules: Vec<String>,
+}
+
+impl EnvironnementPython {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Les chemins relatifs d'un fichier policy sont relatifs au répertoire de ce fichier
+    pub fn relatif_a(self, fichier_policy: Option<&str>) -> Self {
+        let Some(repertoire) = fichier_policy.and_then(|fichier| Path::new(fichier).parent())
+        else {
+            return self;
+        };
+        let resous = |chemin: PathBuf| match chemin.is_relative() {
+            true => repertoire.join(chemin),
+            false => chemin,
+        };
+
+        Self {
+            paths: self.paths.into_iter().map(resous).collect(),
+            virtualenv: self.virtualenv.map(resous),
+            modules: self.modules,
+        }
+    }
+
+    // Environnement de la simulation complété par celui de la politique : les répertoires de la
+    // simulation passent en premier, et son virtualenv remplace celui de la politique
+    pub fn complete_par(&self, politique: &EnvironnementPython) -> Self {
+        let mut modules = self.modules.clone();
+        modules.extend(
+            politique
+                .modules
+                .iter()
+                .filter(|module| !self.modules.contains(module))
+                .cloned(),
+        );
+
+        Self {
+            paths: self.paths.iter().chain(&politique.paths).cloned().collect(),
+            virtualenv: self
+                .virtualenv
+                .clone()
+                .or_else(|| politique.virtualenv.clone()),
+            modules,
+        }
+    }
+
+    // Répertoire site-packages du virtualenv pour la version de l'interpréteur embarqué
+    pub fn site_packages(&self, version: (u8, u8)) -> Option<PathBuf> {
+        let virtualenv = self.virtualenv.as_ref()?;
+
+        [
+            virtualenv
+                .join("lib")
+                .join(format!("python{}.{}", version.0, version.1))
+                .join("site-packages"),
+            virtualenv.join("Lib").join("site-packages"),
+        ]
+        .into_iter()
+        .find(|repertoire| repertoire.is_dir())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ok_environnement_politique_complete() {
+        let politique = EnvironnementPython {
+            paths: vec!["lib".into(), "/opt/kalkoto".into()],
+            virtualenv: Some(".venv".into()),
+            modules: vec!["arrondis".into(), "baremes".into()],
+        }
+        .relatif_a(Some("politiques/apa.toml"));
+
+        let simulation = EnvironnementPython {
+            paths: vec!["/srv/commun".into()],
+            virtualenv: None,
+            modules: vec!["baremes".into()],
+        };
+
+        let wanted = EnvironnementPython {
+            paths: vec![
+                "/srv/commun".into(),
+                "politiques/lib".into(),
+                "/opt/kalkoto".into(),
+            ],
+            virtualenv: Some("politiques/.venv".into()),
+            modules: vec!["baremes".into(), "arrondis".into()],
+        };
+        assert_eq!(wanted, simulation.complete_par(&politique));
+    }
+}
@@ -0,0 +1,630 @@
+use crate::adapters::input_adapters::PolicyAdapterError;
+use crate::entities::aleatoire::Aleatoire;
+use crate::entities::evaluator::{ComposanteEvaluator, ContexteSimulation, SessionEvaluation};
+use crate::entities::menage::Menage;
+use crate::entities::policy::Composante;
+use crate::entities::python_environnement::EnvironnementPython;
+use crate::entities::python_source_map::PythonSourceMap;
+use crate::entities::simulator::SimulationError;
+use crate::entities::trace::{ComposanteTrace, Lecture, MenageTrace};
+use crate::{KalkotoError, KalkotoResult};
+use itertools::Itertools;
+use pyo3::{
+    exceptions::{PyModuleNotFoundError, PySyntaxError},
+    prelude::*,
+    sync::MutexExt,
+    types::{IntoPyDict, PyDict, PyTuple},
+};
+use pyo3_ffi::c_str;
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::CString,
+    path::PathBuf,
+    sync::{Mutex, PoisonError},
+};
+
+// Classe Python enregistrant les clefs lues dans les dictionnaires passés aux composantes
+pub const DICT_TRACE_PYTHON: &str = r#"
+class DictTrace(dict):
+    def __init__(self, *args):
+        super().__init__(*args)
+        self.lectures = []
+
+    def __getitem__(self, cle):
+        valeur = super().__getitem__(cle)
+        self.lectures.append(cle)
+        return valeur
+
+    def get(self, cle, defaut=None):
+        if cle in self:
+            self.lectures.append(cle)
+        return super().get(cle, defaut)
+"#;
+
+impl PythonSourceMap {
+    // Ligne de composantemodule.py la plus profonde de la traceback d'une erreur Python
+    fn ligne_erreur(py: Python<'_>, err: &PyErr) -> Option<usize> {
+        if err.is_instance_of::<PySyntaxError>(py) {
+            return err
+                .value(py)
+                .getattr("lineno")
+                .ok()?
+                .extract::<usize>()
+                .ok();
+        }
+
+        let mut ligne_erreur = None;
+        let mut traceback = err.traceback(py).map(Bound::into_any);
+
+        while let Some(frame) = traceback.filter(|tb| !tb.is_none()) {
+            let nom_fichier = frame
+                .getattr("tb_frame")
+                .and_then(|f| f.getattr("f_code"))
+                .and_then(|code| code.getattr("co_filename"))
+                .and_then(|nom| nom.extract::<String>())
+                .ok();
+
+            if nom_fichier.as_deref() == Some(PYTHON_MODULE_FILE) {
+                ligne_erreur = frame
+                    .getattr("tb_lineno")
+                    .and_then(|ligne| ligne.extract::<usize>())
+                    .ok();
+            }
+
+            traceback = frame.getattr("tb_next").ok();
+        }
+
+        ligne_erreur
+    }
+
+    pub fn python_error(
+        &self,
+        py: Python<'_>,
+        err: PyErr,
+        menage: Option<&Menage>,
+        err_msg: String,
+    ) -> SimulationError {
+        let localisation =
+            Self::ligne_erreur(py, &err).and_then(|ligne_module| self.localise(ligne_module));
+
+        match (localisation, menage) {
+            (Some(localisation), Some(menage)) => SimulationError::PythonComposanteError {
+                source: err,
+                localisation: Box::new(localisation),
+                menage: menage.to_string(),
+            },
+            (Some(localisation), None) => SimulationError::PythonModuleError {
+                source: err,
+                localisation: Box::new(localisation),
+            },
+            (None, _) => SimulationError::PythonError {
+                source: err,
+                err_msg,
+            },
+        }
+    }
+}
+
+const PYTHON_MODULE_FILE: &str = "composantemodule.py";
+
+// Clefs lues par une composante dans un DictTrace, sans doublons et dans l'ordre de lecture
+fn lectures_dict_trace(dict_trace: &Bound<'_, PyAny>) -> PyResult<Vec<String>> {
+    Ok(dict_trace
+        .getattr("lectures")?
+        .extract::<Vec<String>>()?
+        .into_iter()
+        .unique()
+        .collect())
+}
+
+fn lectures_f64(
+    dict: &Bound<'_, PyDict>,
+    dict_trace: &Bound<'_, PyAny>,
+) -> PyResult<Vec<Lecture<f64>>> {
+    lectures_dict_trace(dict_trace)?
+        .into_iter()
+        .filter_map(|nom| match dict.get_item(&nom) {
+            Ok(Some(valeur)) => Some(
+                valeur
+                    .extract::<f64>()
+                    .map(|valeur| Lecture { nom, valeur }),
+            ),
+            Ok(None) => None,
+            Err(e) => Some(Err(e)),
+        })
+        .collect()
+}
+
+// Moteur des composantes dont le bloc function est écrit en Python. Une fonction déclarant un
+// quatrième argument (Variables, ParamsDict, MenageCarac, Alea) reçoit un random.Random dont la
+// graine est dérivée de la graine de la simulation, de la clé du ménage et du nom de la composante.
+// L'environnement de la simulation complète celui déclaré par chaque politique
+#[derive(Default)]
+pub struct PythonEvaluator {
+    environnement: EnvironnementPython,
+}
+
+impl PythonEvaluator {
+    pub fn new(environnement: EnvironnementPython) -> Self {
+        Self { environnement }
+    }
+}
+
+// Répertoires que kalkoto a ajoutés à sys.path pour la dernière session Python. L'interpréteur
+// étant partagé par toutes les simulations du processus, chaque session retire ceux que son
+// environnement ne déclare pas, avec les modules importés depuis ces répertoires : la variante
+// n'hérite ni des répertoires de la baseline ni des versions de ses modules déjà chargées
+struct RepertoiresAjoutes {
+    paths: Vec<PathBuf>,
+    site_packages: Option<PathBuf>,
+    // Répertoires ajoutés par site.addsitedir (site-packages et fichiers .pth)
+    site: Vec<PathBuf>,
+}
+
+static REPERTOIRES_AJOUTES: Mutex<RepertoiresAjoutes> = Mutex::new(RepertoiresAjoutes {
+    paths: Vec::new(),
+    site_packages: None,
+    site: Vec::new(),
+});
+
+// Retrait de répertoires de sys.path et des modules de sys.modules dont le fichier s'y trouve
+fn retire_repertoires(py: Python<'_>, repertoires: &[PathBuf]) -> PyResult<()> {
+    if repertoires.is_empty() {
+        return Ok(());
+    }
+
+    let sys = py.import("sys")?;
+    let sys_path = sys.getattr("path")?;
+    for repertoire in repertoires {
+        let repertoire = repertoire.display().to_string();
+        while sys_path.contains(&repertoire)? {
+            sys_path.call_method1("remove", (&repertoire,))?;
+        }
+    }
+
+    let modules = sys.getattr("modules")?;
+    let modules = modules.cast::<PyDict>()?;
+    let importes: Vec<Bound<'_, PyAny>> = modules
+        .items()
+        .iter()
+        .filter_map(|item| {
+            let (nom, module) = item
+                .extract::<(Bound<'_, PyAny>, Bound<'_, PyAny>)>()
+                .ok()?;
+            let fichier = module.getattr("__file__").ok()?.extract::<PathBuf>().ok()?;
+            let fichier = std::path::absolute(fichier).ok()?;
+            repertoires
+                .iter()
+                .any(|repertoire| fichier.starts_with(repertoire))
+                .then_some(nom)
+        })
+        .collect();
+
+    for nom in importes {
+        modules.del_item(nom)?;
+    }
+
+    Ok(())
+}
+
+// Préparation de sys.path pour l'environnement d'une session. Un répertoire déjà présent dans
+// sys.path n'est pas ajouté une seconde fois, et n'est jamais retiré s'il n'a pas été ajouté
+// par kalkoto
+fn prepare_environnement(
+    py: Python<'_>,
+    environnement: &EnvironnementPython,
+    ajoutes: &mut RepertoiresAjoutes,
+) -> PyResult<()> {
+    let sys = py.import("sys")?;
+    let sys_path = sys.getattr("path")?;
+
+    let paths = environnement
+        .paths
+        .iter()
+        .map(std::path::absolute)
+        .collect::<Result<Vec<PathBuf>, _>>()?;
+
+    let site_packages = match &environnement.virtualenv {
+        None => None,
+        Some(virtualenv) => {
+            let version: (u8, u8) = sys
+                .getattr("version_info")?
+                .extract::<(u8, u8, u8, String, u8)>()
+                .map(|(majeure, mineure, ..)| (majeure, mineure))?;
+
+            let site_packages = environnement.site_packages(version).ok_or_else(|| {
+                PyModuleNotFoundError::new_err(format!(
+                    "le virtualenv {} ne contient pas de site-packages pour Python {}.{}",
+                    virtualenv.display(),
+                    version.0,
+                    version.1
+                ))
+            })?;
+            Some(std::path::absolute(site_packages)?)
+        }
+    };
+
+    let retires: Vec<PathBuf> = ajoutes
+        .paths
+        .iter()
+        .filter(|repertoire| !paths.contains(repertoire))
+        .chain(
+            ajoutes
+                .site
+                .iter()
+                .filter(|_| ajoutes.site_packages != site_packages),
+        )
+        .cloned()
+        .collect();
+    retire_repertoires(py, &retires)?;
+
+    let mut paths_ajoutes = vec![];
+    for repertoire in paths.iter().rev() {
+        let chemin = repertoire.display().to_string();
+        if ajoutes.paths.contains(repertoire) {
+            paths_ajoutes.push(repertoire.to_owned());
+        } else if !sys_path.contains(&chemin)? {
+            sys_path.call_method1("insert", (0, chemin))?;
+            paths_ajoutes.push(repertoire.to_owned());
+        }
+    }
+    ajoutes.paths = paths_ajoutes;
+
+    if ajoutes.site_packages != site_packages {
+        ajoutes.site.clear();
+        ajoutes.site_packages.clone_from(&site_packages);
+
+        if let Some(site_packages) = site_packages {
+            let site_packages = site_packages.display().to_string();
+            if !sys_path.contains(&site_packages)? {
+                let avant: Vec<String> = sys_path.extract()?;
+                // addsitedir traite aussi les fichiers .pth des paquets installés en mode éditable
+                py.import("site")?
+                    .call_method1("addsitedir", (site_packages,))?;
+                ajoutes.site = sys_path
+                    .extract::<Vec<String>>()?
+                    .into_iter()
+                    .filter(|repertoire| !avant.contains(repertoire))
+                    .map(PathBuf::from)
+                    .collect();
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Objets Python créés une seule fois par simulation et partagés par toutes les composantes.
+// Le dictionnaire Variables d'un ménage est conservé d'une composante à l'autre ; il reçoit
+// chaque résultat, ainsi que les variables calculées par les autres moteurs ou reprises
+struct PythonSession<'a> {
+    contexte: ContexteSimulation<'a>,
+    source_map: &'a PythonSourceMap,
+    functions_module: Py<PyModule>,
+    parameters_dict: Py<PyDict>,
+    menages_caract_dicts: Vec<Py<PyDict>>,
+    menages_variables_dicts: Vec<Py<PyDict>>,
+    variables_synchronisees: HashSet<String>,
+    dict_trace: Option<Py<PyAny>>,
+    random_class: Py<PyAny>,
+}
+
+impl ComposanteEvaluator for PythonEvaluator {
+    fn session<'a>(
+        &'a self,
+        contexte: ContexteSimulation<'a>,
+    ) -> KalkotoResult<Box<dyn SessionEvaluation + 'a>> {
+        let policy = contexte.policy;
+        let (Some(python_functions), Some(source_map)) =
+            (&policy.python_functions, &policy.python_source_map)
+        else {
+            return Err(KalkotoError::PolicyError(PolicyAdapterError::Generic(
+                "Fichier policy pas encore lu ! Les fonctions Python ne sont pas initialisées"
+                    .into(),
+            )));
+        };
+
+        Python::initialize();
+
+        let environnement = self
+            .environnement
+            .complete_par(&policy.environnement_python);
+
+        Python::attach(|py| -> KalkotoResult<Box<dyn SessionEvaluation + 'a>> {
+            // Verrou conservé jusqu'à la fin des imports de la session
+            let mut repertoires_ajoutes = REPERTOIRES_AJOUTES
+                .lock_py_attached(py)
+                .unwrap_or_else(PoisonError::into_inner);
+
+            prepare_environnement(py, &environnement, &mut repertoires_ajoutes).map_err(|e| {
+                SimulationError::PythonError {
+                    source: e,
+                    err_msg: "Erreur à la configuration de l'environnement Python".into(),
+                }
+            })?;
+
+            for module in &environnement.modules {
+                py.import(module.as_str())
+                    .map_err(|e| SimulationError::PythonError {
+                        source: e,
+                        err_msg: format!(
+                            "Le module {} requis par la politique {} ne peut pas être importé (répertoires ajoutés : {:?}, virtualenv : {:?})",
+                            module,
+                            policy.name,
+                            environnement.paths,
+                            environnement.virtualenv
+                        ),
+                    })?;
+            }
+
+            let functions_module = PyModule::from_code(
+                py,
+                CString::new(python_functions.to_owned())
+                    .map_err(|e| SimulationError::PythonError {
+                        source: e.into(),
+                        err_msg: "Problème de lecture des fonctions Python".into(),
+                    })?
+                    .as_c_str(),
+                c_str!("composantemodule.py"),
+                c_str!("composantemodule"),
+            )
+            .map_err(|e| {
+                source_map.python_error(py, e, None, "Erreur à la création du module Python".into())
+            })?;
+
+            let parameters_dict =
+                policy
+                    .parameters_values
+                    .clone()
+                    .into_py_dict(py)
+                    .map_err(|e| SimulationError::PythonError {
+                        source: e,
+                        err_msg: "Erreur pour dictionnaire de paramètres".into(),
+                    })?;
+
+            let menages_caract_dicts = contexte
+                .menages
+                .iter()
+                .map(|menage| {
+                    menage
+                        .caracteristiques
+                        .clone()
+                        .into_py_dict(py)
+                        .map(Bound::unbind)
+                        .map_err(|e| SimulationError::PythonError {
+                            source: e,
+                            err_msg: "Erreur pour dictionnaire de caractéristiques des ménages"
+                                .into(),
+                        })
+                })
+                .collect::<Result<Vec<Py<PyDict>>, SimulationError>>()?;
+
+            let menages_variables_dicts = contexte
+                .menages
+                .iter()
+                .map(|_| PyDict::new(py).unbind())
+                .collect();
+
+            let dict_trace = match contexte.menages_traces.is_empty() {
+                true => None,
+                false => Some(
+                    PyModule::from_code(
+                        py,
+                        CString::new(DICT_TRACE_PYTHON)
+                            .map_err(|e| SimulationError::PythonError {
+                                source: e.into(),
+                                err_msg: "Problème de lecture du module de trace".into(),
+                            })?
+                            .as_c_str(),
+                        c_str!("tracemodule.py"),
+                        c_str!("tracemodule"),
+                    )
+                    .and_then(|module| module.getattr("DictTrace"))
+                    .map_err(|e| SimulationError::PythonError {
+                        source: e,
+                        err_msg: "Erreur à la création du module de trace".into(),
+                    })?
+                    .unbind(),
+                ),
+            };
+
+            let random_class = py
+                .import("random")
+                .and_then(|module| module.getattr("Random"))
+                .map_err(|e| SimulationError::PythonError {
+                    source: e,
+                    err_msg: "Erreur à l'import du module random".into(),
+                })?
+                .unbind();
+
+            Ok(Box::new(PythonSession {
+                contexte,
+                source_map,
+                functions_module: functions_module.unbind(),
+                parameters_dict: parameters_dict.unbind(),
+                menages_caract_dicts,
+                menages_variables_dicts,
+                variables_synchronisees: HashSet::new(),
+                dict_trace,
+                random_class,
+            }))
+        })
+    }
+}
+
+impl SessionEvaluation for PythonSession<'_> {
+    fn evaluate(
+        &mut self,
+        composante: &Composante,
+        variables: &mut [HashMap<String, f64>],
+        traces: &mut [Option<MenageTrace>],
+    ) -> KalkotoResult<()> {
+        Python::attach(|py| -> KalkotoResult<()> {
+            let parameters_dict = self.parameters_dict.bind(py);
+            let dict_trace = self
+                .dict_trace
+                .as_ref()
+                .map(|dict_trace| dict_trace.bind(py));
+
+            let rustfunc = self
+                .functions_module
+                .bind(py)
+                .getattr(&composante.name)
+                .map_err(|e| SimulationError::PythonError {
+                    source: e,
+                    err_msg: format!(
+                        "Erreur lors de l'interprétation de la fonction Python de la composante {}",
+                        &composante.name
+                    ),
+                })?;
+
+            let avec_aleatoire = rustfunc
+                .getattr("__code__")
+                .and_then(|code| code.getattr("co_argcount"))
+                .and_then(|nombre_arguments| nombre_arguments.extract::<usize>())
+                .is_ok_and(|nombre_arguments| nombre_arguments == 4);
+            let random_class = self.random_class.bind(py);
+            let seed = self.contexte.seed;
+
+            for (
+                (((menage_caract_dict, menage_variables_dict), menage_variables), menage),
+                trace,
+            ) in self
+                .menages_caract_dicts
+                .iter()
+                .zip(&self.menages_variables_dicts)
+                .zip(variables.iter_mut())
+                .zip(self.contexte.menages)
+                .zip(traces.iter_mut())
+            {
+                let menage_caract_dict = menage_caract_dict.bind(py);
+                let menage_variables_dict = menage_variables_dict.bind(py);
+
+                menage_variables
+                    .iter()
+                    .filter(|(nom, _)| !self.variables_synchronisees.contains(*nom))
+                    .try_for_each(|(nom, valeur)| menage_variables_dict.set_item(nom, valeur))
+                    .map_err(|e| SimulationError::PythonError {
+                        source: e,
+                        err_msg: "Erreur pour dictionnaire de variables des ménages".into(),
+                    })?;
+
+                let resultat = avec_aleatoire
+                    .then(|| {
+                        let graine =
+                            Aleatoire::new(seed, &menage.cle(), &composante.name).next_u64();
+                        random_class.call1((graine,))
+                    })
+                    .transpose()
+                    .and_then(|aleatoire| match (trace, dict_trace) {
+                        (Some(trace), Some(dict_trace)) => call_with_trace(
+                            composante,
+                            &rustfunc,
+                            menage,
+                            (menage_variables_dict, parameters_dict, menage_caract_dict),
+                            aleatoire.as_ref(),
+                            dict_trace,
+                            trace,
+                        ),
+                        _ => call_composante(
+                            &rustfunc,
+                            (
+                                menage_variables_dict.as_any(),
+                                parameters_dict.as_any(),
+                                menage_caract_dict.as_any(),
+                            ),
+                            aleatoire.as_ref(),
+                        ),
+                    })
+                    .and_then(|resultat| {
+                        menage_variables_dict.set_item(&composante.name, &resultat)?;
+                        resultat.extract::<f64>()
+                    })
+                    .map_err(|e| {
+                        self.source_map.python_error(
+                            py,
+                            e,
+                            Some(menage),
+                            format!("Erreur lors du calcul de la composante {}", composante.name),
+                        )
+                    })?;
+
+                menage_variables.insert(composante.name.to_owned(), resultat);
+            }
+
+            for nom in variables.iter().flat_map(HashMap::keys) {
+                if !self.variables_synchronisees.contains(nom) {
+                    self.variables_synchronisees.insert(nom.to_owned());
+                }
+            }
+
+            Ok(())
+        })
+    }
+}
+
+// Appel de la fonction d'une composante, avec le générateur aléatoire en quatrième argument
+// si la fonction le déclare
+fn call_composante<'py>(
+    rustfunc: &Bound<'py, PyAny>,
+    (variables, parametres, caracteristiques): (
+        &Bound<'py, PyAny>,
+        &Bound<'py, PyAny>,
+        &Bound<'py, PyAny>,
+    ),
+    aleatoire: Option<&Bound<'py, PyAny>>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let arguments = [variables, parametres, caracteristiques]
+        .into_iter()
+        .chain(aleatoire)
+        .collect::<Vec<_>>();
+
+    rustfunc.call1(PyTuple::new(rustfunc.py(), arguments)?)
+}
+
+fn call_with_trace<'py>(
+    composante: &Composante,
+    rustfunc: &Bound<'py, PyAny>,
+    menage: &Menage,
+    (menage_variables_dict, parameters_dict, menage_caract_dict): (
+        &Bound<'py, PyDict>,
+        &Bound<'py, PyDict>,
+        &Bound<'py, PyDict>,
+    ),
+    aleatoire: Option<&Bound<'py, PyAny>>,
+    dict_trace: &Bound<'py, PyAny>,
+    trace: &mut MenageTrace,
+) -> PyResult<Bound<'py, PyAny>> {
+    let variables_trace = dict_trace.call1((menage_variables_dict,))?;
+    let parameters_trace = dict_trace.call1((parameters_dict,))?;
+    let caract_trace = dict_trace.call1((menage_caract_dict,))?;
+
+    let result = call_composante(
+        rustfunc,
+        (&variables_trace, &parameters_trace, &caract_trace),
+        aleatoire,
+    )?;
+
+    // Les écritures de la fonction dans Variables restent visibles des composantes suivantes
+    menage_variables_dict.update(variables_trace.cast::<PyDict>()?.as_mapping())?;
+
+    let caracteristiques_lues = lectures_dict_trace(&caract_trace)?
+        .into_iter()
+        .filter_map(|nom| {
+            let valeur = menage.caracteristiques.get(&nom)?.clone();
+            Some(Lecture { nom, valeur })
+        })
+        .collect();
+
+    trace.etapes.push(ComposanteTrace {
+        composante: composante.name.to_owned(),
+        intitule_long: composante.intitule_long.to_owned(),
+        caracteristiques_lues,
+        parametres_lus: lectures_f64(parameters_dict, &parameters_trace)?,
+        variables_lues: lectures_f64(menage_variables_dict, &variables_trace)?,
+        resultat: result.extract::<f64>()?,
+        lectures_enregistrees: true,
+    });
+
+    Ok(result)
+}
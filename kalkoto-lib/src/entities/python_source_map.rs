@@ -0,0 +1,93 @@
+use crate::entities::evaluator::MOTEUR_PYTHON;
+use crate::entities::policy::Composante;
+use std::fmt;
+
+// Position de la fonction d'une composante dans le module Python synthétique
+#[derive(Debug, Clone, PartialEq)]
+pub struct PythonFunctionOffset {
+    pub composante: String,
+    pub premiere_ligne: usize,
+    pub nb_lignes: usize,
+}
+
+// Localisation d'une ligne du module Python synthétique dans le fichier policy
+#[derive(Debug, Clone, PartialEq)]
+pub struct PythonLocalisation {
+    pub fichier_policy: String,
+    pub composante: String,
+    pub ligne: usize,
+    pub ligne_source: String,
+}
+
+// Table de correspondance entre les lignes de composantemodule.py et les blocs function
+// des composantes, pour rendre lisibles les tracebacks Python
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PythonSourceMap {
+    pub fichier_policy: String,
+    pub offsets: Vec<PythonFunctionOffset>,
+    lignes_module: Vec<String>,
+}
+
+impl fmt::Display for PythonLocalisation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "composante {} du fichier {}, ligne {} du bloc function :\n\t\t -> {}",
+            self.composante, self.fichier_policy, self.ligne, self.ligne_source
+        )
+    }
+}
+
+impl PythonSourceMap {
+    pub fn new(fichier_policy: &str, composantes: &[Composante]) -> Self {
+        let mut offsets = Vec::with_capacity(composantes.len());
+        let mut lignes_module = vec![];
+
+        for composante in composantes {
+            if composante.engine() != MOTEUR_PYTHON {
+                continue;
+            }
+            let Some(function) = composante.function.clone() else {
+                continue;
+            };
+            let function: String = function.into();
+            let lignes_function: Vec<String> = function.split('\n').map(String::from).collect();
+
+            offsets.push(PythonFunctionOffset {
+                composante: composante.name.to_owned(),
+                premiere_ligne: lignes_module.len() + 1,
+                nb_lignes: lignes_function.len(),
+            });
+
+            lignes_module.extend(lignes_function);
+        }
+
+        Self {
+            fichier_policy: fichier_policy.to_owned(),
+            offsets,
+            lignes_module,
+        }
+    }
+
+    pub fn module_source(&self) -> String {
+        self.lignes_module.join("\n")
+    }
+
+    pub fn localise(&self, ligne_module: usize) -> Option<PythonLocalisation> {
+        let offset = self.offsets.iter().find(|offset| {
+            ligne_module >= offset.premiere_ligne
+                && ligne_module < offset.premiere_ligne + offset.nb_lignes
+        })?;
+
+        Some(PythonLocalisation {
+            fichier_policy: self.fichier_policy.to_owned(),
+            composante: offset.composante.to_owned(),
+            ligne: ligne_module - offset.premiere_ligne + 1,
+            ligne_source: self
+                .lignes_module
+                .get(ligne_module - 1)
+                .map(|ligne| ligne.trim().to_owned())
+                .unwrap_or_default(),
+        })
+    }
+}
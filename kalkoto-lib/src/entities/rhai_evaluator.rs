@@ -0,0 +1,392 @@
+use crate::entities::aleatoire::Aleatoire;
+use crate::entities::evaluator::{ComposanteEvaluator, ContexteSimulation, SessionEvaluation};
+use crate::entities::menage::Caracteristique;
+use crate::entities::policy::Composante;
+use crate::entities::simulator::SimulationError;
+use crate::entities::trace::{ComposanteTrace, MenageTrace};
+use crate::KalkotoResult;
+use rhai::{Dynamic, Engine, EvalAltResult, Map, Scope, AST, INT};
+use std::collections::HashMap;
+
+// Moteur des composantes dont le bloc function est écrit en Rhai. La fonction reçoit les mêmes
+// arguments que les fonctions Python : (Variables, ParamsDict, MenageCarac), sous forme de maps Rhai.
+// Une fonction à quatre arguments reçoit en plus un générateur Alea, dont la méthode uniforme()
+// renvoie un tirage reproductible pour le ménage et la composante.
+// Les traces ne contiennent que le résultat, les lectures dans les maps n'étant pas observées
+pub struct RhaiEvaluator;
+
+// Limites d'un appel de fonction Rhai, pour qu'une boucle infinie ou une récursion sans fin dans
+// une composante produise une erreur au lieu de bloquer ou de faire planter la simulation
+const RHAI_MAX_OPERATIONS: u64 = 10_000_000;
+const RHAI_MAX_CALL_LEVELS: usize = 64;
+const RHAI_MAX_EXPR_DEPTH: usize = 64;
+const RHAI_MAX_FUNCTION_EXPR_DEPTH: usize = 32;
+const RHAI_MAX_TAILLE: usize = 1_000_000; //Caractères d'une chaîne, éléments d'un tableau ou d'une map
+
+struct RhaiSession<'a> {
+    contexte: ContexteSimulation<'a>,
+    engine: Engine,
+    parametres: Map,
+    menages_caracteristiques: Vec<Map>,
+}
+
+impl ComposanteEvaluator for RhaiEvaluator {
+    fn session<'a>(
+        &'a self,
+        contexte: ContexteSimulation<'a>,
+    ) -> KalkotoResult<Box<dyn SessionEvaluation + 'a>> {
+        let parametres = contexte
+            .policy
+            .parameters_values
+            .iter()
+            .map(|(nom, valeur)| (nom.into(), Dynamic::from_float(*valeur)))
+            .collect();
+
+        let menages_caracteristiques = contexte
+            .menages
+            .iter()
+            .map(|menage| {
+                menage
+                    .caracteristiques
+                    .iter()
+                    .map(|(nom, valeur)| (nom.into(), caracteristique_to_dynamic(valeur)))
+                    .collect()
+            })
+            .collect();
+
+        let mut engine = Engine::new();
+        engine
+            .set_max_operations(RHAI_MAX_OPERATIONS)
+            .set_max_call_levels(RHAI_MAX_CALL_LEVELS)
+            .set_max_expr_depths(RHAI_MAX_EXPR_DEPTH, RHAI_MAX_FUNCTION_EXPR_DEPTH)
+            .set_max_string_size(RHAI_MAX_TAILLE)
+            .set_max_array_size(RHAI_MAX_TAILLE)
+            .set_max_map_size(RHAI_MAX_TAILLE)
+            .register_type_with_name::<Aleatoire>("Alea")
+            .register_fn("uniforme", |aleatoire: &mut Aleatoire| aleatoire.uniforme());
+
+        Ok(Box::new(RhaiSession {
+            contexte,
+            engine,
+            parametres,
+            menages_caracteristiques,
+        }))
+    }
+}
+
+fn caracteristique_to_dynamic(caracteristique: &Caracteristique) -> Dynamic {
+    match caracteristique {
+        Caracteristique::Entier(entier) => Dynamic::from_int(*entier as INT),
+        Caracteristique::Numeric(numeric) => Dynamic::from_float(*numeric),
+        Caracteristique::Textuel(texte) => Dynamic::from(texte.to_owned()),
+        Caracteristique::Booleen(booleen) => Dynamic::from_bool(*booleen),
+        Caracteristique::Date(_) => Dynamic::from(caracteristique.to_string()),
+        Caracteristique::Manquant => Dynamic::UNIT,
+    }
+}
+
+// Message d'une erreur d'exécution, en signalant les limites atteintes
+fn message_execution(erreur: &EvalAltResult) -> String {
+    match erreur {
+        EvalAltResult::ErrorTooManyOperations(_)
+        | EvalAltResult::ErrorStackOverflow(_)
+        | EvalAltResult::ErrorDataTooLarge(..) => format!(
+            "{erreur} (limite du moteur Rhai atteinte : boucle infinie, récursion sans fin ou donnée trop grande ?)"
+        ),
+        _ => erreur.to_string(),
+    }
+}
+
+fn rhai_error(composante: &Composante, message: String, menage: String) -> SimulationError {
+    SimulationError::RhaiError {
+        composante: composante.name.to_owned(),
+        message,
+        menage,
+    }
+}
+
+impl RhaiSession<'_> {
+    // Compilation du bloc function d'une composante, en vérifiant qu'il définit bien
+    // une fonction du nom de la composante avec la convention d'appel attendue.
+    // Renvoie aussi si la fonction attend le générateur aléatoire
+    fn compile(&self, composante: &Composante) -> Result<(AST, bool), SimulationError> {
+        let function: String = composante
+            .function
+            .clone()
+            .ok_or_else(|| {
+                rhai_error(
+                    composante,
+                    "le bloc function est manquant".into(),
+                    String::new(),
+                )
+            })?
+            .into();
+
+        let ast = self
+            .engine
+            .compile(function)
+            .map_err(|e| rhai_error(composante, e.to_string(), String::new()))?;
+
+        let nombre_arguments = ast
+            .iter_functions()
+            .find(|fonction| fonction.name == composante.name)
+            .map(|fonction| fonction.params.len());
+
+        match nombre_arguments {
+            Some(3) => Ok((ast, false)),
+            Some(4) => Ok((ast, true)),
+            _ => Err(rhai_error(
+                composante,
+                format!(
+                    "le bloc function doit définir fn {}(Variables, ParamsDict, MenageCarac) ou fn {}(Variables, ParamsDict, MenageCarac, Alea)",
+                    composante.name, composante.name
+                ),
+                String::new(),
+            )),
+        }
+    }
+}
+
+impl SessionEvaluation for RhaiSession<'_> {
+    fn evaluate(
+        &mut self,
+        composante: &Composante,
+        variables: &mut [HashMap<String, f64>],
+        traces: &mut [Option<MenageTrace>],
+    ) -> KalkotoResult<()> {
+        let (ast, avec_aleatoire) = self.compile(composante)?;
+
+        for (((menage, menage_caracteristiques), menage_variables), trace) in self
+            .contexte
+            .menages
+            .iter()
+            .zip(&self.menages_caracteristiques)
+            .zip(variables.iter_mut())
+            .zip(traces.iter_mut())
+        {
+            let variables_map: Map = menage_variables
+                .iter()
+                .map(|(nom, valeur)| (nom.into(), Dynamic::from_float(*valeur)))
+                .collect();
+
+            let arguments = (
+                variables_map,
+                self.parametres.clone(),
+                menage_caracteristiques.clone(),
+            );
+            let resultat = match avec_aleatoire {
+                true => {
+                    let aleatoire =
+                        Aleatoire::new(self.contexte.seed, &menage.cle(), &composante.name);
+                    let (variables_map, parametres, caracteristiques) = arguments;
+                    self.engine.call_fn::<Dynamic>(
+                        &mut Scope::new(),
+                        &ast,
+                        &composante.name,
+                        (variables_map, parametres, caracteristiques, aleatoire),
+                    )
+                }
+                false => self.engine.call_fn::<Dynamic>(
+                    &mut Scope::new(),
+                    &ast,
+                    &composante.name,
+                    arguments,
+                ),
+            }
+            .map_err(|e| rhai_error(composante, message_execution(&e), menage.to_string()))?;
+
+            let resultat = match (resultat.as_float(), resultat.as_int()) {
+                (Ok(resultat), _) => resultat,
+                (_, Ok(resultat)) => resultat as f64,
+                _ => {
+                    return Err(From::from(rhai_error(
+                        composante,
+                        format!(
+                            "la fonction doit renvoyer un nombre, et non une valeur de type {}",
+                            resultat.type_name()
+                        ),
+                        menage.to_string(),
+                    )))
+                }
+            };
+
+            if let Some(trace) = trace {
+                trace.etapes.push(ComposanteTrace {
+                    composante: composante.name.to_owned(),
+                    intitule_long: composante.intitule_long.to_owned(),
+                    caracteristiques_lues: vec![],
+                    parametres_lus: vec![],
+                    variables_lues: vec![],
+                    resultat,
+                    lectures_enregistrees: false,
+                });
+            }
+
+            menage_variables.insert(composante.name.to_owned(), resultat);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::input_adapters::toml_input_adapter::TomlInputAdapter;
+    use crate::adapters::input_adapters::PolicyCreator;
+    use crate::entities::menage::Menage;
+    use crate::KalkotoError;
+
+    static RHAI_TOML: &str = r#"
+name = "APA domicile"
+
+intitule_long = "Aide personnalisée à domicile"
+
+[[composante]]
+name = "plan_notif"
+intitule_long = "Plan notifié"
+parameters.names = ["tau_1", "tau_2"]
+parameters.intitules_long = ["Taux GIR 1", "Taux GIR 2"]
+parameters.values = [0.15, 0.3]
+caracteristiques_dependencies = ["Age", "GIR"]
+logical_order = 1
+language = "rhai"
+function = """
+fn plan_notif(Variables, ParamsDict, MenageCarac) {
+    if MenageCarac["GIR"] == 1 {
+        return ParamsDict["tau_1"] * MenageCarac["Age"];
+    }
+    ParamsDict["tau_2"] * MenageCarac["Age"]
+}
+"""
+
+[[composante]]
+name = "plan_cons"
+intitule_long = "Plan effectivement consommé"
+parameters.names = ["taux_ss_conso"]
+parameters.intitules_long = ["Taux de sous-consommation du plan notifié"]
+parameters.values = [0.8]
+caracteristiques_dependencies = []
+logical_order = 2
+formula = 'Variables["plan_notif"] * ParamsDict["taux_ss_conso"]'
+"#;
+
+    fn create_test_menage(gir: i32) -> Menage {
+        let mut menage = Menage::new(1);
+        menage
+            .caracteristiques
+            .insert("Age".into(), Caracteristique::Entier(80));
+        menage
+            .caracteristiques
+            .insert("GIR".into(), Caracteristique::Entier(gir));
+        menage
+    }
+
+    #[test]
+    fn ok_rhai_policy_simulate() -> KalkotoResult<()> {
+        let policy_input = TomlInputAdapter::new()
+            .populate_from_memory(RHAI_TOML.as_bytes())?
+            .create_valid_policy_input()?;
+
+        let results = policy_input
+            .valid_policy
+            .simulate_all_menages(&[create_test_menage(1), create_test_menage(2)])?;
+
+        let wanted = vec![
+            HashMap::from([
+                ("plan_notif".to_string(), 12.0),
+                ("plan_cons".to_string(), 9.600000000000001),
+            ]),
+            HashMap::from([
+                ("plan_notif".to_string(), 24.0),
+                ("plan_cons".to_string(), 19.200000000000003),
+            ]),
+        ];
+        assert_eq!(wanted, results);
+
+        Ok(())
+    }
+
+    #[test]
+    fn err_rhai_limites_atteintes() -> KalkotoResult<()> {
+        for corps in ["loop { }", "plan_notif(Variables, ParamsDict, MenageCarac)"] {
+            let toml = format!(
+                r#"
+name = "APA domicile"
+intitule_long = "Aide personnalisée à domicile"
+
+[[composante]]
+name = "plan_notif"
+intitule_long = "Plan notifié"
+parameters.names = []
+parameters.intitules_long = []
+parameters.values = []
+caracteristiques_dependencies = []
+logical_order = 1
+language = "rhai"
+function = """
+fn plan_notif(Variables, ParamsDict, MenageCarac) {{
+    {corps}
+}}
+"""
+"#
+            );
+            let policy_input = TomlInputAdapter::new()
+                .populate_from_memory(toml.as_bytes())?
+                .create_valid_policy_input()?;
+
+            let result = policy_input
+                .valid_policy
+                .simulate_all_menages(&[create_test_menage(1)]);
+
+            let wanted = true;
+            let result = matches!(
+                result,
+                Err(KalkotoError::SimError(SimulationError::RhaiError { ref message, .. }))
+                    if message.contains("limite du moteur Rhai atteinte")
+            );
+            assert_eq!(wanted, result);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn err_rhai_function_name() -> KalkotoResult<()> {
+        let toml = r#"
+name = "APA domicile"
+intitule_long = "Aide personnalisée à domicile"
+
+[[composante]]
+name = "plan_notif"
+intitule_long = "Plan notifié"
+parameters.names = []
+parameters.intitules_long = []
+parameters.values = []
+caracteristiques_dependencies = ["Age"]
+logical_order = 1
+language = "rhai"
+function = """
+fn plan(Variables, ParamsDict, MenageCarac) {
+    MenageCarac["Age"]
+}
+"""
+"#;
+        let policy_input = TomlInputAdapter::new()
+            .populate_from_memory(toml.as_bytes())?
+            .create_valid_policy_input()?;
+
+        let result = policy_input
+            .valid_policy
+            .simulate_all_menages(&[create_test_menage(1)]);
+
+        let wanted = true;
+        let result = matches!(
+            result,
+            Err(KalkotoError::SimError(SimulationError::RhaiError { .. }))
+        );
+        assert_eq!(wanted, result);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,440 @@
+use crate::adapters::input_adapters::MenageListAdapterError;
+use crate::entities::menage::{Caracteristique, Menage};
+use crate::KalkotoResult;
+use chrono::NaiveDate;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::ffi::OsStr;
+use std::fmt;
+use std::path::Path;
+
+// Schéma des ménages, déclaré dans un fichier TOML à raison d'une table par caractéristique :
+//
+// [[caracteristique]]
+// name = "CodePostal"
+// type = "textuel"
+// intitule_long = "Code postal de la commune de résidence"
+//
+// [[caracteristique]]
+// name = "GIR"
+// type = "entier"
+// valeurs = [1, 2, 3, 4, 5, 6]
+//
+// [[caracteristique]]
+// name = "Naissance"
+// type = "date"
+//
+// Les caractéristiques déclarées sont lues selon leur type au lieu d'être devinées valeur par
+// valeur ("01500" reste un texte), et doivent être présentes dans le fichier ménages.
+// Les caractéristiques non déclarées restent devinées
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(deny_unknown_fields)]
+pub struct SchemaMenages {
+    #[serde(rename = "caracteristique", default)]
+    pub caracteristiques: Vec<SchemaCaracteristique>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TypeCaracteristique {
+    Entier,
+    Numeric,
+    Textuel,
+    Booleen,
+    Date,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(try_from = "SchemaCaracteristiqueToml")]
+pub struct SchemaCaracteristique {
+    pub name: String,
+    pub type_caracteristique: TypeCaracteristique,
+    pub intitule_long: Option<String>,
+    pub valeurs: Option<Vec<Caracteristique>>, //Valeurs autorisées, converties dans le type déclaré
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SchemaCaracteristiqueToml {
+    name: String,
+    #[serde(rename = "type")]
+    type_caracteristique: TypeCaracteristique,
+    intitule_long: Option<String>,
+    valeurs: Option<Vec<ValeurToml>>,
+    min: Option<f64>,
+    max: Option<f64>,
+}
+
+// Valeur d'une caractéristique écrite dans un fichier TOML (schéma, générateur de cas-types)
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub(crate) enum ValeurToml {
+    Entier(i64),
+    Numeric(f64),
+    Booleen(bool),
+    Textuel(String),
+}
+
+impl TryFrom<ValeurToml> for Caracteristique {
+    type Error = String;
+
+    fn try_from(valeur: ValeurToml) -> Result<Self, Self::Error> {
+        match valeur {
+            ValeurToml::Entier(entier) => i32::try_from(entier)
+                .map(Caracteristique::Entier)
+                .map_err(|_| format!("{entier} dépasse la capacité d'un entier 32 bits")),
+            ValeurToml::Numeric(numeric) => Ok(Caracteristique::Numeric(numeric)),
+            ValeurToml::Booleen(booleen) => Ok(Caracteristique::Booleen(booleen)),
+            ValeurToml::Textuel(texte) => Ok(Caracteristique::Textuel(texte)),
+        }
+    }
+}
+
+impl TryFrom<SchemaCaracteristiqueToml> for SchemaCaracteristique {
+    type Error = String;
+
+    fn try_from(toml: SchemaCaracteristiqueToml) -> Result<Self, Self::Error> {
+        let type_caracteristique = toml.type_caracteristique;
+
+        let numerique = matches!(
+            type_caracteristique,
+            TypeCaracteristique::Entier | TypeCaracteristique::Numeric
+        );
+        if !numerique && (toml.min.is_some() || toml.max.is_some()) {
+            return Err(format!(
+                "La caractéristique {} de type {} ne peut pas avoir de min ou de max",
+                toml.name, type_caracteristique
+            ));
+        }
+
+        let valeurs = toml
+            .valeurs
+            .map(|valeurs| {
+                valeurs
+                    .into_iter()
+                    .map(|valeur| {
+                        type_caracteristique.convertit(Caracteristique::try_from(valeur)?)
+                    })
+                    .collect::<Result<Vec<Caracteristique>, String>>()
+            })
+            .transpose()
+            .map_err(|cause| format!("Valeurs autorisées de {} invalides : {cause}", toml.name))?;
+
+        Ok(SchemaCaracteristique {
+            name: toml.name,
+            type_caracteristique,
+            intitule_long: toml.intitule_long,
+            valeurs,
+            min: toml.min,
+            max: toml.max,
+        })
+    }
+}
+
+impl fmt::Display for TypeCaracteristique {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TypeCaracteristique::Entier => write!(f, "entier"),
+            TypeCaracteristique::Numeric => write!(f, "numeric"),
+            TypeCaracteristique::Textuel => write!(f, "textuel"),
+            TypeCaracteristique::Booleen => write!(f, "booleen"),
+            TypeCaracteristique::Date => write!(f, "date"),
+        }
+    }
+}
+
+impl fmt::Display for SchemaCaracteristique {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.type_caracteristique)?;
+        if let Some(valeurs) = &self.valeurs {
+            write!(
+                f,
+                " parmi [{}]",
+                valeurs
+                    .iter()
+                    .map(|valeur| valeur.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )?;
+        }
+        match (self.min, self.max) {
+            (Some(min), Some(max)) => write!(f, " entre {min} et {max}"),
+            (Some(min), None) => write!(f, " supérieur ou égal à {min}"),
+            (None, Some(max)) => write!(f, " inférieur ou égal à {max}"),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+impl TypeCaracteristique {
+    // Lecture d'un champ texte (fichier CSV) ; un champ vide est une valeur manquante.
+    // Les booléens s'écrivent true/false ou 1/0, les dates AAAA-MM-JJ
+    pub fn lit(&self, champ: &str) -> Result<Caracteristique, String> {
+        match (self, champ) {
+            (_, "") => Ok(Caracteristique::Manquant),
+            (TypeCaracteristique::Entier, _) => champ
+                .trim()
+                .parse::<i32>()
+                .map(Caracteristique::Entier)
+                .map_err(|e| e.to_string()),
+            (TypeCaracteristique::Numeric, _) => champ
+                .trim()
+                .parse::<f64>()
+                .map(Caracteristique::Numeric)
+                .map_err(|e| e.to_string()),
+            (TypeCaracteristique::Textuel, _) => Ok(Caracteristique::Textuel(champ.to_string())),
+            (TypeCaracteristique::Booleen, _) => match champ.trim() {
+                "true" | "1" => Ok(Caracteristique::Booleen(true)),
+                "false" | "0" => Ok(Caracteristique::Booleen(false)),
+                autre => Err(format!("{autre} n'est pas un booléen (true ou false)")),
+            },
+            (TypeCaracteristique::Date, _) => NaiveDate::parse_from_str(champ.trim(), "%Y-%m-%d")
+                .map(Caracteristique::Date)
+                .map_err(|e| format!("{e} (date attendue au format AAAA-MM-JJ)")),
+        }
+    }
+
+    // Conversion d'une valeur déjà typée (fichiers Arrow et Parquet, valeurs par défaut)
+    pub fn convertit(&self, caracteristique: Caracteristique) -> Result<Caracteristique, String> {
+        match (self, caracteristique) {
+            (_, Caracteristique::Manquant) => Ok(Caracteristique::Manquant),
+            (TypeCaracteristique::Entier, Caracteristique::Entier(entier)) => {
+                Ok(Caracteristique::Entier(entier))
+            }
+            (TypeCaracteristique::Entier, Caracteristique::Numeric(numeric))
+                if numeric.fract() == 0.0
+                    && numeric >= i32::MIN as f64
+                    && numeric <= i32::MAX as f64 =>
+            {
+                Ok(Caracteristique::Entier(numeric as i32))
+            }
+            (TypeCaracteristique::Numeric, Caracteristique::Entier(entier)) => {
+                Ok(Caracteristique::Numeric(entier as f64))
+            }
+            (TypeCaracteristique::Numeric, Caracteristique::Numeric(numeric)) => {
+                Ok(Caracteristique::Numeric(numeric))
+            }
+            (TypeCaracteristique::Textuel, Caracteristique::Textuel(texte)) => {
+                Ok(Caracteristique::Textuel(texte))
+            }
+            (TypeCaracteristique::Booleen, Caracteristique::Booleen(booleen)) => {
+                Ok(Caracteristique::Booleen(booleen))
+            }
+            (TypeCaracteristique::Booleen, Caracteristique::Entier(entier @ (0 | 1))) => {
+                Ok(Caracteristique::Booleen(entier == 1))
+            }
+            (TypeCaracteristique::Date, Caracteristique::Date(date)) => {
+                Ok(Caracteristique::Date(date))
+            }
+            (TypeCaracteristique::Textuel, caracteristique) => {
+                Ok(Caracteristique::Textuel(caracteristique.to_string()))
+            }
+            (_, Caracteristique::Textuel(texte)) => self.lit(&texte),
+            (_, caracteristique) => Err(format!("{caracteristique} n'est pas un {self}")),
+        }
+    }
+}
+
+impl SchemaCaracteristique {
+    // Conversion dans le type déclaré, puis vérification des valeurs autorisées et des bornes
+    pub fn verifie(&self, caracteristique: Caracteristique) -> Result<Caracteristique, String> {
+        let caracteristique = self.type_caracteristique.convertit(caracteristique)?;
+
+        let valeur = match &caracteristique {
+            Caracteristique::Manquant => return Ok(caracteristique),
+            Caracteristique::Entier(entier) => Some(*entier as f64),
+            Caracteristique::Numeric(numeric) => Some(*numeric),
+            _ => None,
+        };
+
+        if let Some(valeurs) = &self.valeurs {
+            if !valeurs.contains(&caracteristique) {
+                return Err(format!("{caracteristique} n'est pas une valeur autorisée"));
+            }
+        }
+        if let Some(valeur) = valeur {
+            if self.min.is_some_and(|min| valeur < min) || self.max.is_some_and(|max| valeur > max)
+            {
+                return Err(format!("{caracteristique} est hors des bornes"));
+            }
+        }
+
+        Ok(caracteristique)
+    }
+}
+
+impl SchemaMenages {
+    pub fn new() -> Self {
+        SchemaMenages::default()
+    }
+
+    pub fn populate_from_memory(buf: &[u8]) -> KalkotoResult<Self> {
+        let erreur = |cause: String| {
+            MenageListAdapterError::FileFormat(format!("Schéma des ménages invalide : {cause}"))
+        };
+
+        let s = std::str::from_utf8(buf).map_err(|e| erreur(e.to_string()))?;
+        let schema: SchemaMenages = toml::from_str(s).map_err(|e| erreur(e.to_string()))?;
+
+        let mut noms = HashSet::new();
+        if let Some(doublon) = schema
+            .caracteristiques
+            .iter()
+            .find(|caracteristique| !noms.insert(&caracteristique.name))
+        {
+            return Err(From::from(erreur(format!(
+                "la caractéristique {} est déclarée plusieurs fois",
+                doublon.name
+            ))));
+        }
+
+        Ok(schema)
+    }
+
+    pub fn populate_from_path<P>(path: P) -> KalkotoResult<Self>
+    where
+        P: AsRef<Path>,
+    {
+        match path.as_ref().extension().and_then(OsStr::to_str) {
+            Some("toml") => (),
+            _ => {
+                return Err(From::from(MenageListAdapterError::FileFormat(
+                    "Le schéma des ménages n'est pas un fichier TOML".into(),
+                )))
+            }
+        }
+
+        let buf = std::fs::read(path).map_err(MenageListAdapterError::IO)?;
+        Self::populate_from_memory(&buf)
+    }
+
+    pub fn caracteristique(&self, nom: &str) -> Option<&SchemaCaracteristique> {
+        self.caracteristiques
+            .iter()
+            .find(|caracteristique| caracteristique.name == nom)
+    }
+
+    // Lecture d'un champ texte selon le type déclaré de sa colonne, ou deviné si elle est absente
+    // du schéma
+    pub fn lit(
+        &self,
+        nom_colonne: &str,
+        champ: &str,
+        menage: i32,
+    ) -> Result<Caracteristique, MenageListAdapterError> {
+        match self.caracteristique(nom_colonne) {
+            Some(schema) => schema.type_caracteristique.lit(champ).map_err(|cause| {
+                MenageListAdapterError::Schema {
+                    colonne: nom_colonne.to_string(),
+                    menage,
+                    attendu: schema.to_string(),
+                    cause,
+                }
+            }),
+            None => Ok(Caracteristique::from(champ.to_string())),
+        }
+    }
+
+    pub fn applique(&self, menage: &mut Menage) -> Result<(), MenageListAdapterError> {
+        for schema in &self.caracteristiques {
+            let erreur = |cause: String| MenageListAdapterError::Schema {
+                colonne: schema.name.clone(),
+                menage: menage.index,
+                attendu: schema.to_string(),
+                cause,
+            };
+
+            let caracteristique = menage
+                .caracteristiques
+                .get(&schema.name)
+                .cloned()
+                .ok_or_else(|| erreur("caractéristique absente du fichier ménages".into()))?;
+            let caracteristique = schema.verifie(caracteristique).map_err(erreur)?;
+
+            menage
+                .caracteristiques
+                .insert(schema.name.clone(), caracteristique);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static SCHEMA_TOML: &str = r#"
+[[caracteristique]]
+name = "CodePostal"
+type = "textuel"
+intitule_long = "Code postal"
+
+[[caracteristique]]
+name = "GIR"
+type = "entier"
+valeurs = [1, 2, 3, 4, 5, 6]
+
+[[caracteristique]]
+name = "Revenu"
+type = "numeric"
+min = 0
+"#;
+
+    #[test]
+    fn ok_schema_types_declares() -> KalkotoResult<()> {
+        let schema = SchemaMenages::populate_from_memory(SCHEMA_TOML.as_bytes())?;
+
+        let mut menage = Menage::new(4);
+        menage
+            .caracteristiques
+            .insert("CodePostal".into(), schema.lit("CodePostal", "01500", 4)?);
+        menage
+            .caracteristiques
+            .insert("GIR".into(), Caracteristique::Numeric(3.0));
+        menage
+            .caracteristiques
+            .insert("Revenu".into(), Caracteristique::Entier(1200));
+        schema.applique(&mut menage)?;
+
+        assert_eq!(
+            Caracteristique::Textuel("01500".into()),
+            menage.caracteristiques["CodePostal"]
+        );
+        assert_eq!(Caracteristique::Entier(3), menage.caracteristiques["GIR"]);
+        assert_eq!(
+            Caracteristique::Numeric(1200.0),
+            menage.caracteristiques["Revenu"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn err_schema_valeur_non_autorisee() -> KalkotoResult<()> {
+        let schema = SchemaMenages::populate_from_memory(SCHEMA_TOML.as_bytes())?;
+
+        let mut menage = Menage::new(7);
+        menage.caracteristiques.insert(
+            "CodePostal".into(),
+            Caracteristique::Textuel("75001".into()),
+        );
+        menage
+            .caracteristiques
+            .insert("GIR".into(), Caracteristique::Entier(7));
+        menage
+            .caracteristiques
+            .insert("Revenu".into(), Caracteristique::Numeric(0.0));
+
+        let wanted = true;
+        let result = matches!(
+            schema.applique(&mut menage),
+            Err(MenageListAdapterError::Schema { ref colonne, menage: 7, ref attendu, .. })
+                if colonne == "GIR" && attendu == "entier parmi [1, 2, 3, 4, 5, 6]"
+        );
+        assert_eq!(wanted, result);
+
+        Ok(())
+    }
+}
@@ -1,30 +1,81 @@
 use crate::adapters::input_adapters::{MenageListCreator, PolicyCreator};
 use crate::adapters::output_adapters::OutputWriter;
 use crate::entities::menage_input::*;
+use crate::entities::evaluator::{ComposanteEvaluator, Evaluateurs};
+use crate::entities::formula::Filtre;
+use crate::entities::policy::{Reprise, ResultatsIndividus};
 use crate::entities::policy_input::*;
+use crate::entities::python_source_map::PythonLocalisation;
+use crate::entities::trace::MenageTrace;
 use crate::{KalkotoError, KalkotoResult};
 use rayon::prelude::*;
 use std::{
     collections::{HashMap, HashSet},
-    fmt::{write, Write},
+    fmt::{write, Debug, Write},
     hash::Hash,
 };
 
-#[derive(thiserror::Error, Debug)]
+#[derive(thiserror::Error)]
 pub enum SimulationError {
     #[error("Erreur à la mise en cohérence ménages/policy : {0}")]
     MenagesPolicyMismatchError(String),
     
+    #[cfg(feature = "python")]
     #[error("Problème lors de l'exécution d'une fonction Python :\n\t\t -> {err_msg}\n\t\t -> {source}")]
     PythonError {
         source: pyo3::prelude::PyErr,
         err_msg: String
         },
 
+    #[cfg(feature = "python")]
+    #[error("Erreur Python dans la {localisation}\n\t\t -> {source}\n{menage}")]
+    PythonComposanteError {
+        source: pyo3::prelude::PyErr,
+        localisation: Box<PythonLocalisation>,
+        menage: String,
+    },
+
+    #[cfg(feature = "python")]
+    #[error("Erreur Python à la lecture de la {localisation}\n\t\t -> {source}")]
+    PythonModuleError {
+        source: pyo3::prelude::PyErr,
+        localisation: Box<PythonLocalisation>,
+    },
+
+    #[error("Erreur dans la formule de la composante {composante} :\n\t\t -> {message}\n{menage}")]
+    FormulaError {
+        composante: String,
+        message: String,
+        menage: String,
+    },
+
+    #[error("Erreur Rhai dans la composante {composante} :\n\t\t -> {message}\n{menage}")]
+    RhaiError {
+        composante: String,
+        message: String,
+        menage: String,
+    },
+
+    #[error("Erreur de la fonction Rust de la composante {composante} :\n\t\t -> {message}\n{menage}")]
+    NativeError {
+        composante: String,
+        message: String,
+        menage: String,
+    },
+
+    #[error("Erreur de moteur de calcul : {0}")]
+    EvaluatorError(String),
+
     #[error("Résultats non valides : {0}")]
     ResultsError(String),
 }
 
+impl Debug for SimulationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", self)
+    }
+}
+
 
 #[derive(Default)]
 pub struct EmptyMenageInput;
@@ -49,6 +100,69 @@ pub struct SimulatorBuilder<M, B, V> {
     pub results_baseline: Option<Vec<HashMap<String, f64>>>,
     pub results_variante: Option<Vec<HashMap<String, f64>>>,
     pub results_diff: Option<Vec<HashMap<String, Option<f64>>>>,
+    pub results_individus_baseline: Option<ResultatsIndividus>,
+    pub results_individus_variante: Option<ResultatsIndividus>,
+    pub results_individus_diff: Option<ResultatsIndividus<Option<f64>>>,
+    pub menages_traces: HashSet<String>,
+    pub traces_baseline: Option<Vec<MenageTrace>>,
+    pub traces_variante: Option<Vec<MenageTrace>>,
+    pub evaluateurs: Evaluateurs,
+    pub seed: u64,
+    pub valeurs_manquantes: ValeursManquantes,
+    pub filtre: Option<Filtre>,
+    pub colonne_poids: Option<String>,
+}
+
+impl<M, B, V> SimulatorBuilder<M, B, V> {
+    // Clés des ménages (identifiants, ou à défaut index) dont le détail du calcul est
+    // enregistré lors des simulations
+    pub fn add_menages_traces(self, menages_traces: HashSet<String>) -> Self {
+        SimulatorBuilder {
+            menages_traces,
+            ..self
+        }
+    }
+
+    // Moteur de calcul utilisé pour les composantes dont le champ engine vaut nom
+    pub fn add_evaluateur<E: ComposanteEvaluator + 'static>(
+        mut self,
+        nom: &str,
+        evaluateur: E,
+    ) -> Self {
+        self.evaluateurs.register(nom, evaluateur);
+        self
+    }
+
+    // Graine des tirages aléatoires mis à disposition des composantes : une même graine
+    // donne les mêmes résultats, quel que soit l'ordre ou le découpage des ménages
+    pub fn add_seed(self, seed: u64) -> Self {
+        SimulatorBuilder { seed, ..self }
+    }
+
+    // Traitement des valeurs manquantes appliqué au chargement des ménages
+    pub fn add_valeurs_manquantes(self, valeurs_manquantes: ValeursManquantes) -> Self {
+        SimulatorBuilder {
+            valeurs_manquantes,
+            ..self
+        }
+    }
+
+    // Filtre des ménages appliqué à leur chargement : seule la sous-population qui le
+    // vérifie est simulée
+    pub fn add_filtre(self, filtre: Filtre) -> Self {
+        SimulatorBuilder {
+            filtre: Some(filtre),
+            ..self
+        }
+    }
+
+    // Colonne des poids de sondage des ménages, lue à leur chargement
+    pub fn add_colonne_poids(self, colonne_poids: &str) -> Self {
+        SimulatorBuilder {
+            colonne_poids: Some(colonne_poids.to_string()),
+            ..self
+        }
+    }
 }
 
 
@@ -62,7 +176,10 @@ impl SimulatorBuilder<EmptyMenageInput, EmptyBaselineInput, EmptyVarianteInput>
         menage_input_adapter: M,
     ) -> KalkotoResult<SimulatorBuilder<ValidMenageInput, EmptyBaselineInput, EmptyVarianteInput>>
     {
-        let start_menage_list = MenageInputBuilder::<EmptyList>::new();
+        let start_menage_list = MenageInputBuilder::<EmptyList>::new()
+            .with_valeurs_manquantes(self.valeurs_manquantes.clone())
+            .with_filtre(self.filtre.clone())
+            .with_colonne_poids(self.colonne_poids.clone());
         let menage_input = menage_input_adapter.create_valid_menage_input(start_menage_list)?;
 
         Ok(SimulatorBuilder {
@@ -72,11 +189,36 @@ impl SimulatorBuilder<EmptyMenageInput, EmptyBaselineInput, EmptyVarianteInput>
             results_baseline: self.results_baseline,
             results_variante: self.results_variante,
             results_diff: self.results_diff,
+            results_individus_baseline: self.results_individus_baseline,
+            results_individus_variante: self.results_individus_variante,
+            results_individus_diff: self.results_individus_diff,
+            menages_traces: self.menages_traces,
+            traces_baseline: self.traces_baseline,
+            traces_variante: self.traces_variante,
+            evaluateurs: self.evaluateurs,
+            seed: self.seed,
+            valeurs_manquantes: self.valeurs_manquantes,
+            filtre: self.filtre,
+            colonne_poids: self.colonne_poids,
         })
     }
 }
 
 impl SimulatorBuilder<ValidMenageInput, EmptyBaselineInput, EmptyVarianteInput> {
+    // Table des individus, rattachés aux ménages par leur identifiant : l'adapteur lit la
+    // colonne de rattachement comme colonne identifiant
+    pub fn add_individus_input<M: MenageListCreator>(
+        mut self,
+        individus_input_adapter: M,
+    ) -> KalkotoResult<Self> {
+        let start_individus_list = MenageInputBuilder::<EmptyList>::new()
+            .with_valeurs_manquantes(self.valeurs_manquantes.clone())
+            .table_individus();
+        let individus = individus_input_adapter.create_valid_menage_input(start_individus_list)?;
+        self.menage_input.0.rattache_individus(individus)?;
+        Ok(self)
+    }
+
     pub fn add_valid_baseline_policy<P: PolicyCreator>(
         self,
         baseline_policy_adapter: P,
@@ -88,6 +230,12 @@ impl SimulatorBuilder<ValidMenageInput, EmptyBaselineInput, EmptyVarianteInput>
             .valid_policy
             .caracteristiques_menages
             .difference(&self.menage_input.0.set_caracteristiques_valide)
+            .chain(
+                baseline_policy_input
+                    .valid_policy
+                    .caracteristiques_individus
+                    .difference(&self.menage_input.0.set_caracteristiques_individus),
+            )
             .clone()
             .collect();
 
@@ -101,6 +249,17 @@ impl SimulatorBuilder<ValidMenageInput, EmptyBaselineInput, EmptyVarianteInput>
                 results_baseline: self.results_baseline,
                 results_variante: self.results_variante,
                 results_diff: self.results_diff,
+                results_individus_baseline: self.results_individus_baseline,
+                results_individus_variante: self.results_individus_variante,
+                results_individus_diff: self.results_individus_diff,
+                menages_traces: self.menages_traces,
+                traces_baseline: self.traces_baseline,
+                traces_variante: self.traces_variante,
+                evaluateurs: self.evaluateurs,
+                seed: self.seed,
+                valeurs_manquantes: self.valeurs_manquantes,
+                filtre: self.filtre,
+                colonne_poids: self.colonne_poids,
             }),
             _ => {
                 let error_msg = format!("Les caractéristiques dont dépend la politique baseline sont plus larges que celles présentes dans le fichier ménages.\nMauvaises caractéristiques : {:?}",diff_caracteristiques);
@@ -112,13 +271,21 @@ impl SimulatorBuilder<ValidMenageInput, EmptyBaselineInput, EmptyVarianteInput>
 
 impl<E> SimulatorBuilder<ValidMenageInput, ValidBaselineInput, E> {
     pub fn simulate_baseline_policy(&mut self) -> KalkotoResult<()> {
-        let results = self
+        let (results, results_individus, traces) = self
             .policy_baseline
             .0
             .valid_policy
-            .simulate_all_menages(&self.menage_input.0.liste_menage_valide)?;
+            .simulate_menages_et_individus(
+                &self.menage_input.0.liste_menage_valide,
+                &self.menages_traces,
+                &self.evaluateurs,
+                self.seed,
+                None,
+            )?;
 
         self.results_baseline = Some(results);
+        self.results_individus_baseline = Some(results_individus);
+        self.traces_baseline = Some(traces);
 
         Ok(())
     }
@@ -147,7 +314,11 @@ impl SimulatorBuilder<ValidMenageInput, ValidBaselineInput, EmptyVarianteInput>
             .collect::<HashSet<String>>();
 
         let is_valid = intersect_caracteristiques
-            == variante_policy_input.valid_policy.caracteristiques_menages;
+            == variante_policy_input.valid_policy.caracteristiques_menages
+            && variante_policy_input
+                .valid_policy
+                .caracteristiques_individus
+                .is_subset(&self.menage_input.0.set_caracteristiques_individus);
 
         match is_valid {
             true => Ok(SimulatorBuilder {
@@ -156,7 +327,18 @@ impl SimulatorBuilder<ValidMenageInput, ValidBaselineInput, EmptyVarianteInput>
                 policy_variante: ValidVarianteInput(variante_policy_input),
                 results_baseline: self.results_baseline,
                 results_variante: self.results_variante,
-                results_diff: self.results_diff            
+                results_diff: self.results_diff,
+                results_individus_baseline: self.results_individus_baseline,
+                results_individus_variante: self.results_individus_variante,
+                results_individus_diff: self.results_individus_diff,
+                menages_traces: self.menages_traces,
+                traces_baseline: self.traces_baseline,
+                traces_variante: self.traces_variante,
+                evaluateurs: self.evaluateurs,
+                seed: self.seed,
+                valeurs_manquantes: self.valeurs_manquantes,
+                filtre: self.filtre,
+                colonne_poids: self.colonne_poids,
             }),
             _ => Err(KalkotoError::SimError(SimulationError::MenagesPolicyMismatchError("Les caractéristiques dont dépend la politique variante sont plus larges que celles présentes dans le fichier ménages".to_string()))),
         }
@@ -165,23 +347,33 @@ impl SimulatorBuilder<ValidMenageInput, ValidBaselineInput, EmptyVarianteInput>
 
 impl SimulatorBuilder<ValidMenageInput, ValidBaselineInput, ValidVarianteInput> {
     pub fn simulate_variante_policy(&mut self) -> KalkotoResult<()> {
-        let results = self
+        let results_baseline = self.results_baseline.take().ok_or(
+            SimulationError::ResultsError("Baseline pas encore calculée !".to_string()),
+        )?;
+
+        // Les composantes que la variante ne modifie pas sont reprises de la baseline
+        let reprise = Reprise {
+            policy: &self.policy_baseline.0.valid_policy,
+            resultats: &results_baseline,
+            traces: self.traces_baseline.as_deref().unwrap_or_default(),
+            seed: self.seed,
+        };
+
+        let (results, results_individus, traces) = self
             .policy_variante
             .0
             .valid_policy
-            .simulate_all_menages(&self.menage_input.0.liste_menage_valide)?;
+            .simulate_menages_et_individus(
+                &self.menage_input.0.liste_menage_valide,
+                &self.menages_traces,
+                &self.evaluateurs,
+                self.seed,
+                Some(reprise),
+            )?;
 
         let mut diff_results = vec![];
 
-        for (baseline_result, variante_result) in self
-            .results_baseline
-            .take()
-            .ok_or(SimulationError::ResultsError(
-                "Baseline pas encore calculée !".to_string(),
-            ))?
-            .iter()
-            .zip(results.iter())
-        {
+        for (baseline_result, variante_result) in results_baseline.iter().zip(results.iter()) {
             let mut diff_map = HashMap::<String, Option<f64>>::new();
 
             for (variante_composante_name, variante_composante_value) in variante_result {
@@ -194,8 +386,23 @@ impl SimulatorBuilder<ValidMenageInput, ValidBaselineInput, ValidVarianteInput>
             diff_results.push(diff_map);
         }
 
+        // Écarts au niveau individu, pour les composantes calculées par les deux politiques
+        let results_individus_diff = self.results_individus_baseline.as_ref().map(|baseline| {
+            baseline.iter().zip(&results_individus).map(|(baseline_menage, variante_menage)| {
+                baseline_menage.iter().zip(variante_menage).map(|(baseline_individu, variante_individu)| {
+                    variante_individu
+                        .iter()
+                        .map(|(nom, valeur)| (nom.to_owned(), baseline_individu.get(nom).map(|baseline_valeur| valeur - baseline_valeur)))
+                        .collect()
+                }).collect()
+            }).collect()
+        });
+
         self.results_variante = Some(results);
         self.results_diff = Some(diff_results);
+        self.results_individus_variante = Some(results_individus);
+        self.results_individus_diff = results_individus_diff;
+        self.traces_variante = Some(traces);
 
         Ok(())
     }
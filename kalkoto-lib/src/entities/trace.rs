@@ -0,0 +1,99 @@
+use crate::entities::menage::Caracteristique;
+use crate::entities::simulator::SimulationError;
+use crate::KalkotoResult;
+use serde::Serialize;
+use std::fmt;
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Lecture<V> {
+    pub nom: String,
+    pub valeur: V,
+}
+
+// Entrées lues et valeur calculée par une composante pour un ménage
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ComposanteTrace {
+    pub composante: String,
+    pub intitule_long: String,
+    pub caracteristiques_lues: Vec<Lecture<Caracteristique>>,
+    pub parametres_lus: Vec<Lecture<f64>>,
+    pub variables_lues: Vec<Lecture<f64>>,
+    pub resultat: f64,
+    // Faux pour les moteurs dont les lectures ne sont pas observées (Rhai, fonctions Rust) :
+    // les listes de lectures vides ne signifient alors pas que rien n'a été lu
+    pub lectures_enregistrees: bool,
+}
+
+// Explication pas à pas du calcul d'une politique publique pour un ménage
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct MenageTrace {
+    pub policy: String,
+    pub menage: String, //Clé du ménage : son identifiant, ou à défaut son index
+    pub etapes: Vec<ComposanteTrace>,
+}
+
+impl MenageTrace {
+    pub fn new(policy: &str, menage: String) -> Self {
+        Self {
+            policy: policy.to_owned(),
+            menage,
+            etapes: vec![],
+        }
+    }
+
+    pub fn to_json(traces: &[MenageTrace]) -> KalkotoResult<String> {
+        serde_json::to_string_pretty(traces).map_err(|e| {
+            From::from(SimulationError::ResultsError(format!(
+                "Impossible de sérialiser les traces en JSON : {}",
+                e
+            )))
+        })
+    }
+}
+
+fn write_lectures<V: fmt::Display>(
+    f: &mut fmt::Formatter<'_>,
+    titre: &str,
+    lectures: &[Lecture<V>],
+) -> fmt::Result {
+    let lectures = match lectures.is_empty() {
+        true => "(aucune)".to_string(),
+        false => lectures
+            .iter()
+            .map(|lecture| format!("{} = {}", lecture.nom, lecture.valeur))
+            .collect::<Vec<String>>()
+            .join(", "),
+    };
+    writeln!(f, "       {} : {}", titre, lectures)
+}
+
+impl fmt::Display for MenageTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Calcul de la politique {} pour le ménage {} :",
+            self.policy, self.menage
+        )?;
+        for (numero, etape) in self.etapes.iter().enumerate() {
+            writeln!(
+                f,
+                "  {}. {} ({}) = {}",
+                numero + 1,
+                etape.composante,
+                etape.intitule_long,
+                etape.resultat
+            )?;
+            if !etape.lectures_enregistrees {
+                writeln!(
+                    f,
+                    "       lectures non enregistrées par le moteur de cette composante"
+                )?;
+                continue;
+            }
+            write_lectures(f, "caractéristiques lues", &etape.caracteristiques_lues)?;
+            write_lectures(f, "paramètres lus", &etape.parametres_lus)?;
+            write_lectures(f, "variables lues", &etape.variables_lues)?;
+        }
+        Ok(())
+    }
+}
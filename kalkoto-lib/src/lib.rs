@@ -3,5 +3,6 @@
 pub mod adapters;
 pub mod entities;
 pub mod errors;
+pub mod terminal;
 
 pub use crate::errors::{KalkotoError, KalkotoResult};
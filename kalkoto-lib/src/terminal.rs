@@ -0,0 +1,24 @@
+// Mise en forme des messages affichés dans le terminal. Sans la feature terminal
+// (application web notamment), les messages sont laissés en texte brut
+#[cfg(feature = "terminal")]
+use crossterm::style::Stylize;
+
+#[cfg(feature = "terminal")]
+pub fn succes(message: &str) -> String {
+    message.green().bold().to_string()
+}
+
+#[cfg(not(feature = "terminal"))]
+pub fn succes(message: &str) -> String {
+    message.to_owned()
+}
+
+#[cfg(feature = "terminal")]
+pub fn titre(message: &str) -> String {
+    message.red().bold().to_string()
+}
+
+#[cfg(not(feature = "terminal"))]
+pub fn titre(message: &str) -> String {
+    message.to_owned()
+}